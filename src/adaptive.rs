@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+use solana_program::native_token::lamports_to_sol;
+
+use crate::{stats::read_outcomes, utils::amount_u64_to_f64};
+
+// The reward and fee of a submitted round
+pub struct RoundOutcome {
+    pub difficulty: u32,
+    pub reward: u64,
+    pub fee: u64,
+}
+
+// Moves the min difficulty so the expected reward stays above a multiple of the fee
+pub struct AdaptiveDifficulty {
+    floor: u32,
+    min_floor: u32,
+    window: usize,
+    fee_multiple: f64,
+    ore_price: f64,
+    history: VecDeque<RoundOutcome>,
+}
+
+impl AdaptiveDifficulty {
+    pub fn new(min_floor: u32, window: usize, fee_multiple: f64, ore_price: f64) -> Self {
+        Self {
+            floor: min_floor,
+            min_floor,
+            window: window.max(1),
+            fee_multiple,
+            ore_price,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn floor(&self) -> u32 {
+        self.floor
+    }
+
+    // Keeps the floor at or above a new lower bound
+    pub fn raise_min_floor(&mut self, min_floor: u32) {
+        self.min_floor = self.min_floor.max(min_floor);
+        self.floor = self.floor.max(self.min_floor);
+    }
+
+    // Restores the recent rounds and the last floor from a stats file
+    pub fn load(&mut self, path: &str) {
+        for (outcome, floor) in read_outcomes(path) {
+            self.push(outcome);
+            if let Some(floor) = floor {
+                self.floor = floor.max(self.min_floor);
+            }
+        }
+    }
+
+    pub fn record(&mut self, outcome: RoundOutcome) {
+        self.push(outcome);
+
+        // Step the floor toward the target, one difficulty per round
+        let target = self.target();
+        if target.gt(&self.floor) {
+            self.floor += 1;
+        } else if target.lt(&self.floor) {
+            self.floor -= 1;
+        }
+    }
+
+    fn push(&mut self, outcome: RoundOutcome) {
+        self.history.push_back(outcome);
+        while self.history.len().gt(&self.window) {
+            self.history.pop_front();
+        }
+    }
+
+    // Lowest difficulty whose expected reward covers the fee multiple
+    fn target(&self) -> u32 {
+        let landed: Vec<_> = self.history.iter().filter(|o| o.reward.gt(&0)).collect();
+        if landed.is_empty() {
+            return self.floor;
+        }
+
+        // Rewards double with each difficulty level
+        let reward_per_unit = landed
+            .iter()
+            .map(|o| amount_u64_to_f64(o.reward) / 2f64.powi(o.difficulty as i32))
+            .sum::<f64>()
+            / landed.len() as f64;
+        let fee = self
+            .history
+            .iter()
+            .map(|o| lamports_to_sol(o.fee))
+            .sum::<f64>()
+            / self.history.len() as f64;
+        let max_seen = landed
+            .iter()
+            .map(|o| o.difficulty)
+            .max()
+            .unwrap_or(self.min_floor)
+            .max(self.min_floor);
+        (self.min_floor..=max_seen)
+            .find(|d| {
+                reward_per_unit * 2f64.powi(*d as i32) * self.ore_price >= self.fee_multiple * fee
+            })
+            .unwrap_or(max_seen)
+    }
+}
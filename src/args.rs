@@ -1,4 +1,15 @@
+use std::time::Duration;
+
 use clap::{arg, Parser};
+use ore_api::consts::BUS_COUNT;
+
+use crate::{
+    busses::BusStrategy,
+    pool::CoreList,
+    schedule::{parse_duration, ActiveHours},
+    stats::StatsFormat,
+    utils::amount_string_to_u64,
+};
 
 #[derive(Parser, Debug)]
 pub struct BalanceArgs {
@@ -20,19 +31,37 @@ pub struct BenchmarkArgs {
         default_value = "1"
     )]
     pub cores: u64,
+
+    #[arg(
+        long,
+        help = "Interleave solver memory across numa nodes instead of allocating it on each core's local node"
+    )]
+    pub numa_interleave: bool,
 }
 
 #[derive(Parser, Debug)]
-pub struct BussesArgs {}
+pub struct BussesArgs {
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Clear the screen and print the table again every this many seconds",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub watch: Option<u64>,
+
+    #[arg(long, help = "Print the busses as JSON")]
+    pub json: bool,
+}
 
 #[derive(Parser, Debug)]
 pub struct ClaimArgs {
     #[arg(
         long,
         value_name = "AMOUNT",
-        help = "The amount of rewards to claim. Defaults to max."
+        help = "The amount of rewards to claim. Defaults to max.",
+        value_parser = amount_string_to_u64
     )]
-    pub amount: Option<f64>,
+    pub amount: Option<u64>,
 
     #[arg(
         long,
@@ -45,6 +74,9 @@ pub struct ClaimArgs {
 #[derive(Parser, Debug)]
 pub struct CloseArgs {}
 
+#[derive(Parser, Debug)]
+pub struct NonceArgs {}
+
 #[derive(Parser, Debug)]
 pub struct ConfigArgs {}
 
@@ -52,7 +84,7 @@ pub struct ConfigArgs {}
 #[derive(Parser, Debug)]
 pub struct InitializeArgs {}
 
-#[derive(Parser, Debug)]
+#[derive(Clone, Parser, Debug)]
 pub struct MineArgs {
     #[arg(
         long,
@@ -63,45 +95,446 @@ pub struct MineArgs {
     )]
     pub cores: u64,
 
+    #[arg(
+        long,
+        value_name = "CORE_IDS",
+        help = "Mine only on these core ids, e.g. 0-7. Overrides --cores."
+    )]
+    pub include_cores: Option<CoreList>,
+
+    #[arg(
+        long,
+        value_name = "CORE_IDS",
+        help = "Never mine on these core ids, e.g. 0,1,16-23"
+    )]
+    pub exclude_cores: Option<CoreList>,
+
+    #[arg(
+        long,
+        help = "Pin one worker per physical core, leaving hyperthread siblings free"
+    )]
+    pub physical_cores_only: bool,
+
+    #[arg(
+        long,
+        help = "Also mine on the efficiency cores of hybrid cpus, which are skipped by default"
+    )]
+    pub use_efficiency_cores: bool,
+
+    #[arg(
+        long,
+        help = "Time each core at startup to find efficiency cores the os doesn't report. The result is cached in the config dir."
+    )]
+    pub calibrate_cores: bool,
+
+    #[arg(
+        long,
+        value_name = "INDEX",
+        help = "Index of this machine when several machines mine the same keypair, from 0",
+        default_value = "0"
+    )]
+    pub machine_id: u64,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Number of machines mining the same keypair. Each explores a disjoint nonce range.",
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub machine_count: u64,
+
+    #[arg(
+        long,
+        help = "Interleave solver memory across numa nodes instead of allocating it on each core's local node"
+    )]
+    pub numa_interleave: bool,
+
     #[arg(
         long,
         short,
         value_name = "SECONDS",
-        help = "The number seconds before the deadline to stop mining and start submitting",
-        default_value = "5"
+        help = "The number seconds before the deadline to stop mining and start submitting. Disables auto-tuning from the confirmation latency."
+    )]
+    pub buffer_time: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Lowest buffer time picked by auto-tuning",
+        default_value = "2"
+    )]
+    pub min_buffer_time: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Highest buffer time picked by auto-tuning",
+        default_value = "20"
     )]
-    pub buffer_time: u64,
+    pub max_buffer_time: u64,
 
     #[arg(
         long,
         value_name = "MIN_DIFFICULTY",
-        help = "The minimum difficulty to achieve before stopping",
-        default_value = "3"
+        help = "The minimum difficulty to achieve before stopping. Defaults to the on-chain minimum, and is never set below it."
+    )]
+    pub min_difficulty: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Keep mining until the cutoff time after the minimum difficulty is reached, then submit the best hash"
+    )]
+    pub continue_after_min: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop hashing after this many seconds even if the min difficulty has not been reached"
+    )]
+    pub max_hash_time: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "[DAYS@]HH:MM-HH:MM",
+        help = "Only start rounds inside this local time window, e.g. 22:00-07:00 or sat,sun@00:00-24:00. Can be repeated."
+    )]
+    pub active_hours: Vec<ActiveHours>,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Exit after submitting this many rounds",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub rounds: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Mine a single round, print it as JSON and exit with 0 if it landed, 2 if no solution was submitted, 3 if the submission failed or 4 if the rpc is unreachable",
+        conflicts_with_all = ["rounds", "duration", "worker"]
+    )]
+    pub once: bool,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help = "Exit after the in-flight round once this much time has passed, e.g. 30m or 2h",
+        value_parser = parse_duration
+    )]
+    pub duration: Option<Duration>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append each round's difficulty histogram to this file as a JSON line"
+    )]
+    pub log_histogram: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append a record per submitted round to this file",
+        long_help = "Append a record per submitted round to this file, flushed every round. \
+Fields, in csv column order: timestamp (rfc3339), authority, challenge (base58), hash_time (sec), \
+hashes, difficulty, min_difficulty, landed (true/false), signature (empty if not landed), \
+reward (ORE base units), estimated_reward (ORE base units), priority_fee (microlamports per CU), \
+fee (lamports, estimated), confirm_latency (sec from the last send to confirmation, 0 if not \
+landed), outcome (landed, failed, expired, error or stale). A csv file with other columns, such as one written by an older version, is renamed to \
+<PATH>.1 (or the next free number) and a new file is started. JSON lines also include \
+cores: [{core_id, hashes, hashrate}]."
+    )]
+    pub stats_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        help = "Format of the stats file",
+        default_value = "jsonl"
+    )]
+    pub stats_format: StatsFormat,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Where to save hashing progress so a restart resumes the same challenge. Defaults to a file next to the solana cli config. Ignored when mining several keypairs."
+    )]
+    pub checkpoint_path: Option<String>,
+
+    #[arg(long, help = "Disable nonce checkpointing")]
+    pub no_checkpoint: bool,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Mine with every keypair file in this directory, rotating through their proofs"
+    )]
+    pub keypair_dir: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "BIND_ADDR",
+        help = "Hand out nonce ranges to remote workers connecting to this address, e.g. 0.0.0.0:9000, instead of hashing locally"
+    )]
+    pub coordinator: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COORDINATOR_ADDR",
+        help = "Hash nonce ranges for the coordinator at this address, e.g. 192.168.1.10:9000. Needs no keypair or rpc.",
+        conflicts_with = "coordinator"
+    )]
+    pub worker: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        help = "Niceness of the hashing threads, from 0 (normal) to 19 (lowest). Uses below-normal priority classes on Windows.",
+        value_parser = clap::value_parser!(i32).range(0..=19)
     )]
-    pub min_difficulty: u32,
+    pub nice: Option<i32>,
+
+    #[arg(
+        long,
+        conflicts_with = "nice",
+        help = "Run the hashing threads at the lowest priority. Shorthand for --nice 19."
+    )]
+    pub background: bool,
+
+    #[arg(
+        long,
+        value_name = "CELSIUS",
+        help = "Slow down hashing while the CPU is hotter than this, until it cools 5 degrees below it"
+    )]
+    pub max_temp: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "Share of time each hashing thread spends hashing, sleeping for the rest",
+        default_value = "100",
+        value_parser = clap::value_parser!(u32).range(1..=100)
+    )]
+    pub power_limit: u32,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "Warn when a core hashes below this percentage of the median core for several rounds",
+        default_value = "50"
+    )]
+    pub slow_core_threshold: f64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Restart the round if no hashes are computed for this long. Set to 0 to disable.",
+        default_value = "60"
+    )]
+    pub stall_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Exit with an error after this many stalled rounds in a row",
+        default_value = "3",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_stalls: u32,
+
+    #[arg(long, help = "Print the hashrate of each core after every round")]
+    pub verbose: bool,
+
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "Print a one line session summary every this many rounds. 0 disables it.",
+        default_value = "10"
+    )]
+    pub stats_interval: u64,
+
+    #[arg(
+        long,
+        value_name = "FILEPATH",
+        help = "JSON file of min_difficulty, priority_fee and buffer_time to re-read on SIGHUP. Send SIGUSR1 to print live stats."
+    )]
+    pub tunables_file: Option<String>,
+
+    #[arg(long, help = "Print session summaries as JSON")]
+    pub json: bool,
+
+    #[arg(
+        long,
+        requires = "ore_price",
+        help = "Raise or lower the min difficulty so expected rewards stay above a multiple of the fee. --min-difficulty is the lower bound."
+    )]
+    pub adaptive_difficulty: bool,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Price of 1 ORE in SOL, used to compare rewards against fees"
+    )]
+    pub ore_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "ORE",
+        help = "Skip submitting solutions whose estimated reward is below this, plus the fee when --ore-price is set"
+    )]
+    pub min_reward: Option<f64>,
+
+    #[arg(
+        long,
+        help = "Stop hashing, not only submitting, while the fee payer is below --min-sol-balance"
+    )]
+    pub pause_when_broke: bool,
+
+    #[arg(
+        long,
+        value_name = "ORE",
+        help = "Claim the proof balance in the mine transaction once it exceeds this threshold"
+    )]
+    pub claim_with_mine: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Stop submitting and exit once the session has spent this much on fees and tips"
+    )]
+    pub max_fee_spend: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How the bus to submit to is picked. richest takes the bus with the most rewards left, weighted picks randomly by rewards.",
+        default_value = "richest"
+    )]
+    pub bus_strategy: BusStrategy,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop waiting for the proof to update after a submission after this long, and mine the same challenge again",
+        default_value = "30"
+    )]
+    pub proof_wait_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Stop waiting for the proof to update after this many reads",
+        default_value = "30"
+    )]
+    pub proof_wait_retries: usize,
+
+    #[arg(
+        long,
+        value_name = "EXPONENT",
+        help = "Power the rewards of each bus are raised to with --bus-strategy weighted. Above 1 favors richer busses, below 1 spreads out.",
+        default_value = "1.0"
+    )]
+    pub bus_weight_exponent: f64,
+
+    #[arg(
+        long,
+        value_name = "ORE",
+        help = "Never pick a bus with fewer rewards left than this"
+    )]
+    pub min_bus_rewards: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "ID",
+        help = "Submit to this bus, unless it has too few rewards left for the solution",
+        value_parser = clap::value_parser!(u64).range(0..BUS_COUNT as u64)
+    )]
+    pub bus: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "ID",
+        help = "Never submit to these busses. Pass a comma-separated list.",
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u64).range(0..BUS_COUNT as u64)
+    )]
+    pub exclude_bus: Vec<u64>,
+
+    #[arg(
+        long,
+        value_name = "MULTIPLE",
+        help = "Expected reward to fee ratio targeted by the adaptive difficulty",
+        default_value = "2.0"
+    )]
+    pub reward_fee_multiple: f64,
+
+    #[arg(
+        long,
+        value_name = "ROUNDS",
+        help = "Number of recent rounds considered by the adaptive difficulty",
+        default_value = "20"
+    )]
+    pub adaptive_window: usize,
+
+    #[cfg(feature = "gpu")]
+    #[arg(
+        long,
+        help = "Also hash on the GPU, on a nonce range disjoint from the CPU cores"
+    )]
+    pub gpu: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct PdasArgs {
+    #[arg(
+        index = 1,
+        value_name = "AUTHORITY",
+        help = "The authority to derive addresses for. Defaults to the signer."
+    )]
+    pub authority: Option<String>,
 }
 
 #[derive(Parser, Debug)]
 pub struct ProofArgs {
     #[arg(
-        index = 0,
+        index = 1,
         value_name = "ADDRESS",
         help = "The address of the proof to fetch"
     )]
     pub address: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Fetch the proof of this authority instead of a proof address",
+        conflicts_with = "address"
+    )]
+    pub authority: Option<String>,
 }
 
 #[derive(Parser, Debug)]
 pub struct RewardsArgs {}
 
+#[derive(Parser, Debug)]
+pub struct RpcBenchArgs {
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Times to time getLatestBlockhash on each endpoint, keeping the fastest",
+        default_value = "5"
+    )]
+    pub samples: usize,
+}
+
 #[derive(Parser, Debug)]
 pub struct StakeArgs {
     #[arg(
         long,
         value_name = "AMOUNT",
-        help = "The amount of Ore to stake. Defaults to max."
+        help = "The amount of Ore to stake. Defaults to max.",
+        value_parser = amount_string_to_u64
     )]
-    pub amount: Option<f64>,
+    pub amount: Option<u64>,
 
     #[arg(
         long,
@@ -1,17 +1,16 @@
 use std::str::FromStr;
 
+use colored::*;
 use solana_program::pubkey::Pubkey;
-use solana_sdk::signature::Signer;
 
 use crate::{
     args::BalanceArgs,
-    utils::{amount_u64_to_string, get_proof_with_authority},
+    utils::{amount_u64_to_string, get_proof, mint_pubkey},
     Miner,
 };
 
 impl Miner {
     pub async fn balance(&self, args: BalanceArgs) {
-        let signer = self.signer();
         let address = if let Some(address) = args.address {
             if let Ok(address) = Pubkey::from_str(&address) {
                 address
@@ -20,13 +19,17 @@ impl Miner {
                 return;
             }
         } else {
-            signer.pubkey()
+            self.authority()
         };
-        let proof = get_proof_with_authority(&self.rpc_client, address).await;
-        let token_account_address = spl_associated_token_account::get_associated_token_address(
-            &address,
-            &ore_api::consts::MINT_ADDRESS,
-        );
+        let proof = match get_proof(&self.rpc_client, address).await {
+            Ok(proof) => proof,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return;
+            }
+        };
+        let token_account_address =
+            spl_associated_token_account::get_associated_token_address(&address, &mint_pubkey());
         let token_balance = if let Ok(Some(token_account)) = self
             .rpc_client
             .get_token_account(&token_account_address)
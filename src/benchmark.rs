@@ -1,9 +1,14 @@
-use std::{sync::Arc, time::Instant};
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
 
 use drillx::equix;
 use solana_rpc_client::spinner;
 
-use crate::{args::BenchmarkArgs, Miner};
+use crate::{
+    args::BenchmarkArgs,
+    mine::nonce_start,
+    numa::{interleave_memory, socket_of},
+    Miner,
+};
 
 const TEST_DURATION: i64 = 30;
 
@@ -20,26 +25,25 @@ impl Miner {
             TEST_DURATION
         ));
         let core_ids = core_affinity::get_core_ids().unwrap();
+        let workers = core_ids.len().min(args.cores as usize) as u64;
         let handles: Vec<_> = core_ids
             .into_iter()
-            .map(|i| {
+            .take(workers as usize)
+            .enumerate()
+            .map(|(index, i)| {
+                let numa_interleave = args.numa_interleave;
                 std::thread::spawn({
                     move || {
+                        // Pin to core before allocating solver memory
+                        let _ = core_affinity::set_for_current(i);
+                        if numa_interleave {
+                            interleave_memory().ok();
+                        }
                         let timer = Instant::now();
-                        let first_nonce = u64::MAX
-                            .saturating_div(args.cores)
-                            .saturating_mul(i.id as u64);
+                        let first_nonce = nonce_start(index as u64, workers);
                         let mut nonce = first_nonce;
                         let mut memory = equix::SolverMemory::new();
                         loop {
-                            // Return if core should not be used
-                            if (i.id as u64).ge(&args.cores) {
-                                return 0;
-                            }
-
-                            // Pin to core
-                            let _ = core_affinity::set_for_current(i);
-
                             // Create hash
                             let _hx = drillx::hash_with_memory(
                                 &mut memory,
@@ -57,7 +61,7 @@ impl Miner {
                         }
 
                         // Return hash count
-                        nonce - first_nonce
+                        (i.id, nonce - first_nonce)
                     }
                 })
             })
@@ -65,9 +69,13 @@ impl Miner {
 
         // Join handles and return best nonce
         let mut total_nonces = 0;
+        let mut sockets = BTreeMap::new();
         for h in handles {
-            if let Ok(count) = h.join() {
+            if let Ok((core_id, count)) = h.join() {
                 total_nonces += count;
+                if let Some(socket) = socket_of(core_id) {
+                    *sockets.entry(socket).or_insert(0) += count;
+                }
             }
         }
 
@@ -76,5 +84,14 @@ impl Miner {
             "Hashpower: {} H/sec",
             total_nonces.saturating_div(TEST_DURATION as u64),
         ));
+        if sockets.len().gt(&1) {
+            for (socket, count) in sockets {
+                println!(
+                    "  Socket {}: {} H/sec",
+                    socket,
+                    count.saturating_div(TEST_DURATION as u64)
+                );
+            }
+        }
     }
 }
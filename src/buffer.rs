@@ -0,0 +1,60 @@
+use std::collections::VecDeque;
+
+// Number of recent landed submissions the buffer time is tuned from
+const LATENCY_WINDOW: usize = 20;
+
+// Share of recent submissions that should have landed within the buffer time
+const LATENCY_PERCENTILE: f64 = 0.95;
+
+// Sizes the buffer time from the latency between the end of hashing and confirmation
+pub struct BufferTuner {
+    fixed: Option<u64>,
+    initial: u64,
+    min: u64,
+    max: u64,
+    latencies: VecDeque<f64>,
+}
+
+impl BufferTuner {
+    pub fn new(fixed: Option<u64>, initial: u64, min: u64, max: u64) -> Self {
+        Self {
+            fixed,
+            initial,
+            min,
+            max: max.max(min),
+            latencies: VecDeque::new(),
+        }
+    }
+
+    // Fixes the buffer time, or resumes auto-tuning
+    pub fn set_fixed(&mut self, fixed: Option<u64>) {
+        self.fixed = fixed;
+    }
+
+    pub fn is_auto(&self) -> bool {
+        self.fixed.is_none()
+    }
+
+    pub fn record(&mut self, latency: f64) {
+        self.latencies.push_back(latency);
+        if self.latencies.len().gt(&LATENCY_WINDOW) {
+            self.latencies.pop_front();
+        }
+    }
+
+    // Seconds before the deadline to stop hashing
+    pub fn buffer_time(&self) -> u64 {
+        if let Some(fixed) = self.fixed {
+            return fixed;
+        }
+        if self.latencies.is_empty() {
+            return self.initial.clamp(self.min, self.max);
+        }
+        let mut latencies = self.latencies.iter().copied().collect::<Vec<_>>();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+        let index = ((latencies.len() as f64 * LATENCY_PERCENTILE).ceil() as usize)
+            .saturating_sub(1)
+            .min(latencies.len() - 1);
+        (latencies[index].ceil() as u64).clamp(self.min, self.max)
+    }
+}
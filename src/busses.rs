@@ -1,23 +1,336 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use colored::*;
 use ore_api::{
-    consts::{BUS_ADDRESSES, TOKEN_DECIMALS},
+    consts::{BUS_COUNT, BUS_EPOCH_REWARDS},
     state::Bus,
 };
 use ore_utils::AccountDeserialize;
+use rand::Rng;
+use serde_json::{json, Value};
+use solana_client::client_error::Result as ClientResult;
+
+use crate::{
+    args::BussesArgs,
+    utils::{amount_u64_to_string, bus_pubkeys},
+    Miner,
+};
+
+// Every bus account, or None for ones that couldn't be read
+pub type Busses = [Option<Bus>; BUS_COUNT];
+
+// How the bus a solution is submitted to is picked
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum BusStrategy {
+    Random,
+    // The bus with the most rewards left, ties broken randomly
+    Richest,
+    // A random bus, weighted by the rewards it has left raised to an exponent
+    Weighted,
+}
+
+impl BusStrategy {
+    // Index of the bus to submit to, or None if no bus could be read
+    pub fn select(&self, busses: &[Option<Bus>], exponent: f64) -> Option<usize> {
+        let mut rng = rand::thread_rng();
+        let readable: Vec<(usize, u64)> = busses
+            .iter()
+            .enumerate()
+            .filter_map(|(i, bus)| bus.map(|bus| (i, bus.rewards)))
+            .collect();
+        if readable.is_empty() {
+            return None;
+        }
+        match self {
+            BusStrategy::Random => Some(readable[rng.gen_range(0..readable.len())].0),
+            BusStrategy::Richest => {
+                let most = readable.iter().map(|(_, rewards)| *rewards).max()?;
+                let richest: Vec<usize> = readable
+                    .iter()
+                    .filter(|(_, rewards)| rewards.eq(&most))
+                    .map(|(i, _)| *i)
+                    .collect();
+                Some(richest[rng.gen_range(0..richest.len())])
+            }
+            BusStrategy::Weighted => {
+                let weights: Vec<f64> = readable
+                    .iter()
+                    .map(|(_, rewards)| (*rewards as f64).powf(exponent))
+                    .collect();
+                let total: f64 = weights.iter().sum();
+                if !total.is_normal() {
+                    return Some(readable[rng.gen_range(0..readable.len())].0);
+                }
+                let mut pick = rng.gen_range(0.0..total);
+                for ((i, _), weight) in readable.iter().zip(weights) {
+                    if pick.lt(&weight) {
+                        return Some(*i);
+                    }
+                    pick -= weight;
+                }
+                readable.last().map(|(i, _)| *i)
+            }
+        }
+    }
+}
+
+// Percentage of the bus's epoch allocation it has left
+fn allocation_left(bus: &Bus) -> f64 {
+    bus.rewards as f64 / BUS_EPOCH_REWARDS as f64 * 100.0
+}
 
-use crate::Miner;
+// Bus accounts, shared by every copy of a miner, that are refetched once older than the ttl.
+// While a stream keeps them current they don't age.
+#[derive(Clone)]
+pub struct BusCache {
+    ttl: Duration,
+    entry: Arc<Mutex<Option<(Instant, Busses)>>>,
+    streams: Arc<AtomicUsize>,
+}
+
+impl BusCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Arc::new(Mutex::new(None)),
+            streams: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Cached busses, or None if there are none younger than the ttl
+    fn get(&self) -> Option<Busses> {
+        self.entry
+            .lock()
+            .unwrap()
+            .filter(|(fetched_at, _)| {
+                fetched_at.elapsed().lt(&self.ttl) || self.streams.load(Ordering::Relaxed).gt(&0)
+            })
+            .map(|(_, busses)| busses)
+    }
+
+    pub fn set(&self, busses: Busses) {
+        *self.entry.lock().unwrap() = Some((Instant::now(), busses));
+    }
+
+    // Replaces one cached bus with a streamed update
+    #[cfg(feature = "geyser")]
+    pub fn update(&self, index: usize, bus: Bus) {
+        if let Some((_, busses)) = self.entry.lock().unwrap().as_mut() {
+            busses[index] = Some(bus);
+        }
+    }
+
+    // Marks a stream updating every bus as started or stopped
+    #[cfg(feature = "geyser")]
+    pub fn streaming(&self, started: bool) {
+        if started {
+            self.streams.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.streams.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    // Drops the cached busses, so the next read refetches them
+    pub fn invalidate(&self) {
+        *self.entry.lock().unwrap() = None;
+    }
+}
 
 impl Miner {
-    pub async fn busses(&self) {
-        let client = self.rpc_client.clone();
-        for address in BUS_ADDRESSES.iter() {
-            let data = client.get_account_data(address).await.unwrap();
-            match Bus::try_from_bytes(&data) {
-                Ok(bus) => {
-                    let rewards = (bus.rewards as f64) / 10f64.powf(TOKEN_DECIMALS as f64);
-                    println!("Bus {}: {:} ORE", bus.id, rewards);
+    pub async fn busses(&self, args: BussesArgs) {
+        loop {
+            // Keep watching through a failed read, but fail a one-off read
+            let busses = match self.fetch_busses().await {
+                Ok(busses) => busses,
+                Err(err) => {
+                    println!(
+                        "{} Failed to read the busses: {}",
+                        "ERROR".bold().red(),
+                        err
+                    );
+                    let Some(watch) = args.watch else {
+                        std::process::exit(1);
+                    };
+                    tokio::time::sleep(Duration::from_secs(watch)).await;
+                    continue;
+                }
+            };
+
+            // Richest first
+            let mut rows: Vec<(usize, Bus)> = busses
+                .iter()
+                .enumerate()
+                .filter_map(|(i, bus)| bus.map(|bus| (i, bus)))
+                .collect();
+            rows.sort_by(|(_, a), (_, b)| b.rewards.cmp(&a.rewards));
+
+            if args.watch.is_some() && !args.json {
+                print!("\x1B[2J\x1B[1;1H");
+            }
+            if args.json {
+                let rows: Vec<Value> = rows
+                    .iter()
+                    .map(|(i, bus)| {
+                        json!({
+                            "id": bus.id,
+                            "address": bus_pubkeys()[*i].to_string(),
+                            "rewards": bus.rewards,
+                            "allocation_left": allocation_left(bus),
+                        })
+                    })
+                    .collect();
+                println!("{}", json!(rows));
+            } else {
+                println!(
+                    "{}",
+                    format!(
+                        "{:<4} {:<44} {:>16} {:>6}",
+                        "Bus", "Address", "Rewards (ORE)", "Left"
+                    )
+                    .bold()
+                );
+                for (i, bus) in &rows {
+                    println!(
+                        "{:<4} {:<44} {:>16} {:>5.1}%",
+                        bus.id,
+                        bus_pubkeys()[*i].to_string(),
+                        amount_u64_to_string(bus.rewards),
+                        allocation_left(bus)
+                    );
                 }
-                Err(_) => {}
             }
+
+            let Some(watch) = args.watch else {
+                return;
+            };
+            tokio::time::sleep(Duration::from_secs(watch)).await;
+        }
+    }
+
+    // Every bus, from the cache if it is fresh
+    pub async fn fetch_busses(&self) -> ClientResult<Busses> {
+        match self.bus_cache.get() {
+            Some(busses) => Ok(busses),
+            None => self.refresh_busses().await,
+        }
+    }
+
+    // Refetches every bus in one request and caches them
+    pub async fn refresh_busses(&self) -> ClientResult<Busses> {
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts(&bus_pubkeys())
+            .await?;
+        let busses = parse_busses(accounts.into_iter().map(|a| a.map(|a| a.data)));
+        self.bus_cache.set(busses);
+        Ok(busses)
+    }
+}
+
+// Deserializes the bus account data, in bus_pubkeys order
+pub fn parse_busses(accounts: impl IntoIterator<Item = Option<Vec<u8>>>) -> Busses {
+    let mut busses = [None; BUS_COUNT];
+    for (bus, data) in busses.iter_mut().zip(accounts) {
+        *bus = data.and_then(|data| Bus::try_from_bytes(&data).ok().copied());
+    }
+    busses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 20_000;
+
+    fn busses(rewards: &[Option<u64>]) -> Vec<Option<Bus>> {
+        rewards
+            .iter()
+            .enumerate()
+            .map(|(id, rewards)| {
+                rewards.map(|rewards| Bus {
+                    id: id as u64,
+                    rewards,
+                    theoretical_rewards: 0,
+                    top_balance: 0,
+                })
+            })
+            .collect()
+    }
+
+    // Share of the samples each bus was picked in
+    fn frequencies(busses: &[Option<Bus>], exponent: f64) -> Vec<f64> {
+        let mut counts = vec![0; busses.len()];
+        for _ in 0..SAMPLES {
+            counts[BusStrategy::Weighted.select(busses, exponent).unwrap()] += 1;
+        }
+        counts
+            .into_iter()
+            .map(|count| count as f64 / SAMPLES as f64)
+            .collect()
+    }
+
+    fn assert_close(frequencies: &[f64], expected: &[f64]) {
+        for (frequency, expected) in frequencies.iter().zip(expected) {
+            assert!(
+                (frequency - expected).abs().lt(&0.02),
+                "{:?} != {:?}",
+                frequencies,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_tracks_rewards_to_the_exponent() {
+        let busses = busses(&[Some(100), Some(200), Some(300), Some(400)]);
+        assert_close(&frequencies(&busses, 1.0), &[0.1, 0.2, 0.3, 0.4]);
+        assert_close(
+            &frequencies(&busses, 2.0),
+            &[1.0 / 30.0, 4.0 / 30.0, 0.3, 16.0 / 30.0],
+        );
+    }
+
+    #[test]
+    fn weighted_is_uniform_at_exponent_zero() {
+        let busses = busses(&[Some(1), Some(1_000), Some(1_000_000), Some(0)]);
+        assert_close(&frequencies(&busses, 0.0), &[0.25; 4]);
+    }
+
+    #[test]
+    fn weighted_skips_unreadable_busses() {
+        let busses = busses(&[None, Some(100), None, Some(300)]);
+        assert_close(&frequencies(&busses, 1.0), &[0.0, 0.25, 0.0, 0.75]);
+    }
+
+    #[test]
+    fn no_readable_busses_selects_none() {
+        let busses = busses(&[None, None, None]);
+        for strategy in [
+            BusStrategy::Random,
+            BusStrategy::Richest,
+            BusStrategy::Weighted,
+        ] {
+            assert_eq!(strategy.select(&busses, 1.0), None);
         }
     }
+
+    #[test]
+    fn weighted_falls_back_to_uniform_without_a_normal_total() {
+        // Every bus drained sums to zero, and a huge exponent overflows to infinity
+        let drained = busses(&[Some(0), Some(0), Some(0), Some(0)]);
+        assert_close(&frequencies(&drained, 1.0), &[0.25; 4]);
+        let overflowed = busses(&[Some(u64::MAX), Some(1), Some(u64::MAX), Some(1)]);
+        assert_close(&frequencies(&overflowed, 1_000.0), &[0.25; 4]);
+    }
 }
@@ -0,0 +1,68 @@
+use std::fs;
+
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+
+use crate::utils::config_dir_path;
+
+// Hashing progress on a challenge, saved so a restarted miner can resume it
+pub struct Checkpoint {
+    pub challenge: [u8; 32],
+    pub nonces: Vec<u64>,
+    pub best: Option<(u64, [u8; 16], u32)>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &str) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        let value: Value = serde_json::from_str(&data).ok()?;
+        let challenge = bs58::decode(value["challenge"].as_str()?)
+            .into_vec()
+            .ok()?
+            .try_into()
+            .ok()?;
+        let nonces = value["nonces"]
+            .as_array()?
+            .iter()
+            .map(|n| n.as_u64())
+            .collect::<Option<Vec<_>>>()?;
+        let best = match &value["best"] {
+            Value::Null => None,
+            best => Some((
+                best["nonce"].as_u64()?,
+                bs58::decode(best["digest"].as_str()?)
+                    .into_vec()
+                    .ok()?
+                    .try_into()
+                    .ok()?,
+                best["difficulty"].as_u64()? as u32,
+            )),
+        };
+        Some(Self {
+            challenge,
+            nonces,
+            best,
+        })
+    }
+
+    // Writes to a temporary file first so a crash mid-write can't corrupt the checkpoint
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let value = json!({
+            "challenge": bs58::encode(self.challenge).into_string(),
+            "nonces": self.nonces,
+            "best": self.best.map(|(nonce, digest, difficulty)| json!({
+                "nonce": nonce,
+                "digest": bs58::encode(digest).into_string(),
+                "difficulty": difficulty,
+            })),
+        });
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, value.to_string())?;
+        fs::rename(tmp, path)
+    }
+}
+
+// Defaults to a file next to the solana cli config, keyed by authority
+pub fn default_checkpoint_path(authority: Pubkey) -> String {
+    config_dir_path(&format!("ore-checkpoint-{}.json", authority))
+}
@@ -1,24 +1,33 @@
 use std::str::FromStr;
 
 use colored::*;
-use ore_api::consts::MINT_ADDRESS;
-use solana_program::pubkey::Pubkey;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
 use solana_sdk::signature::Signer;
 use spl_token::amount_to_ui_amount;
 
 use crate::{
     args::ClaimArgs,
-    cu_limits::CU_LIMIT_CLAIM,
+    cluster::for_program,
+    cu_limits::{CU_LIMIT_ATA, CU_LIMIT_CLAIM},
     send_and_confirm::ComputeBudget,
-    utils::{amount_f64_to_u64, ask_confirm, get_proof_with_authority},
+    utils::{ask_confirm, get_proof, mint_pubkey},
     Miner,
 };
 
 impl Miner {
     pub async fn claim(&self, args: ClaimArgs) {
+        if !self.require_authority("claim rewards") {
+            return;
+        }
         let signer = self.signer();
         let pubkey = signer.pubkey();
-        let proof = get_proof_with_authority(&self.rpc_client, pubkey).await;
+        let proof = match get_proof(&self.rpc_client, pubkey).await {
+            Ok(proof) => proof,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return;
+            }
+        };
         let mut ixs = vec![];
         let beneficiary = match args.to {
             Some(to) => {
@@ -26,7 +35,7 @@ impl Miner {
                 let wallet = Pubkey::from_str(&to).expect("Failed to parse wallet address");
                 let benefiary_tokens = spl_associated_token_account::get_associated_token_address(
                     &wallet,
-                    &MINT_ADDRESS,
+                    &mint_pubkey(),
                 );
                 if self
                     .rpc_client
@@ -36,24 +45,33 @@ impl Miner {
                 {
                     ixs.push(
                         spl_associated_token_account::instruction::create_associated_token_account(
-                            &signer.pubkey(),
+                            &self.fee_payer().pubkey(),
                             &wallet,
-                            &ore_api::consts::MINT_ADDRESS,
+                            &mint_pubkey(),
                             &spl_token::id(),
                         ),
                     );
                 }
                 benefiary_tokens
             }
+            // Create the token account in the claim transaction when simulating, as
+            // a separately sent one wouldn't exist for the simulation
+            None if self.send.dry_run => {
+                ixs.push(
+                    spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                        &self.fee_payer().pubkey(),
+                        &pubkey,
+                        &mint_pubkey(),
+                        &spl_token::id(),
+                    ),
+                );
+                spl_associated_token_account::get_associated_token_address(&pubkey, &mint_pubkey())
+            }
             None => self.initialize_ata().await,
         };
 
         // Parse amount to claim
-        let amount = if let Some(amount) = args.amount {
-            amount_f64_to_u64(amount)
-        } else {
-            proof.balance
-        };
+        let amount = args.amount.unwrap_or(proof.balance);
 
         // Confirm user wants to claim
         if !ask_confirm(
@@ -71,10 +89,49 @@ impl Miner {
         }
 
         // Send and confirm
-        ixs.push(ore_api::instruction::claim(pubkey, beneficiary, amount));
-        self.send_and_confirm(&ixs, ComputeBudget::Fixed(CU_LIMIT_CLAIM), false)
-            .await
-            .ok();
+        let compute_budget = if ixs.is_empty() {
+            ComputeBudget::Fixed(CU_LIMIT_CLAIM)
+        } else {
+            ComputeBudget::Fixed(CU_LIMIT_CLAIM + CU_LIMIT_ATA)
+        };
+        ixs.push(for_program(
+            ore_api::instruction::claim(pubkey, beneficiary, amount),
+            pubkey,
+        ));
+        match self.send_and_confirm(&ixs, compute_budget, false).await {
+            Ok(_) if self.send.dry_run => {}
+            Ok(_) => println!(
+                "Claimed {} ORE",
+                amount_to_ui_amount(amount, ore_api::consts::TOKEN_DECIMALS)
+            ),
+            Err(err) => {
+                err.report("claim");
+                if self.send.dry_run {
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    // Instructions claiming to the signer's token account, created if needed
+    pub fn claim_ixs(&self, amount: u64) -> Vec<Instruction> {
+        let signer = self.signer();
+        let token_account_pubkey = spl_associated_token_account::get_associated_token_address(
+            &signer.pubkey(),
+            &mint_pubkey(),
+        );
+        vec![
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                &self.fee_payer().pubkey(),
+                &signer.pubkey(),
+                &mint_pubkey(),
+                &spl_token::id(),
+            ),
+            for_program(
+                ore_api::instruction::claim(signer.pubkey(), token_account_pubkey, amount),
+                signer.pubkey(),
+            ),
+        ]
     }
 
     async fn initialize_ata(&self) -> Pubkey {
@@ -85,7 +142,7 @@ impl Miner {
         // Build instructions.
         let token_account_pubkey = spl_associated_token_account::get_associated_token_address(
             &signer.pubkey(),
-            &ore_api::consts::MINT_ADDRESS,
+            &mint_pubkey(),
         );
 
         // Check if ata already exists
@@ -94,14 +151,17 @@ impl Miner {
         }
         // Sign and send transaction.
         let ix = spl_associated_token_account::instruction::create_associated_token_account(
+            &self.fee_payer().pubkey(),
             &signer.pubkey(),
-            &signer.pubkey(),
-            &ore_api::consts::MINT_ADDRESS,
+            &mint_pubkey(),
             &spl_token::id(),
         );
-        self.send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
+        if let Err(err) = self
+            .send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
             .await
-            .ok();
+        {
+            err.report("token account creation");
+        }
 
         // Return token account address
         token_account_pubkey
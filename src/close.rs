@@ -4,16 +4,26 @@ use spl_token::amount_to_ui_amount;
 
 use crate::{
     args::ClaimArgs,
+    cluster::for_program,
     send_and_confirm::ComputeBudget,
-    utils::{ask_confirm, get_proof_with_authority},
+    utils::{ask_confirm, get_proof},
     Miner,
 };
 
 impl Miner {
     pub async fn close(&self) {
         // Confirm proof exists
+        if !self.require_authority("close the proof account") {
+            return;
+        }
         let signer = self.signer();
-        let proof = get_proof_with_authority(&self.rpc_client, signer.pubkey()).await;
+        let proof = match get_proof(&self.rpc_client, signer.pubkey()).await {
+            Ok(proof) => proof,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return;
+            }
+        };
 
         // Confirm the user wants to close.
         if !ask_confirm(
@@ -36,9 +46,15 @@ impl Miner {
         }
 
         // Submit close transaction
-        let ix = ore_api::instruction::close(signer.pubkey());
-        self.send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
+        let ix = for_program(
+            ore_api::instruction::close(signer.pubkey()),
+            signer.pubkey(),
+        );
+        if let Err(err) = self
+            .send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
             .await
-            .ok();
+        {
+            err.report("close");
+        }
     }
 }
@@ -0,0 +1,85 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use ore_api::{
+    consts::{
+        BUS_ADDRESSES, CONFIG_ADDRESS, MINT_ADDRESS, PROOF, TREASURY_ADDRESS,
+        TREASURY_TOKENS_ADDRESS,
+    },
+    instruction::OreInstruction,
+};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::utils::{
+    bus_pubkeys, config_pubkey, mint_pubkey, treasury_pubkey, treasury_tokens_pubkey,
+};
+
+// Cluster to mine on, selected with --cluster
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Cluster {
+    Mainnet,
+    Devnet,
+    // Any cluster and deployment, given with --rpc and --program-id
+    Custom,
+}
+
+impl Cluster {
+    // Public rpc of the cluster, used when no --rpc is given
+    pub fn default_rpc(&self) -> Option<&'static str> {
+        match self {
+            Self::Mainnet => Some("https://api.mainnet-beta.solana.com"),
+            Self::Devnet => Some("https://api.devnet.solana.com"),
+            Self::Custom => None,
+        }
+    }
+}
+
+// Program every address is derived for and every instruction is sent to
+static PROGRAM_ID: OnceLock<Pubkey> = OnceLock::new();
+
+// Overrides the program id. Must be set before any address is derived, as they are cached.
+pub fn set_program_id(program_id: Pubkey) {
+    PROGRAM_ID.set(program_id).ok();
+}
+
+// The ORE program id, unless overridden with --program-id
+pub fn program_id() -> Pubkey {
+    PROGRAM_ID.get().copied().unwrap_or(ore_api::ID)
+}
+
+// The instruction ore_api built for the ORE program, rebuilt for the configured one, with
+// the proof of the given authority
+pub fn for_program(mut ix: Instruction, authority: Pubkey) -> Instruction {
+    let program_id = program_id();
+    if program_id.eq(&ore_api::ID) {
+        return ix;
+    }
+    let ore_proof = Pubkey::find_program_address(&[PROOF, authority.as_ref()], &ore_api::ID).0;
+    let (proof, proof_bump) =
+        Pubkey::find_program_address(&[PROOF, authority.as_ref()], &program_id);
+    let mut addresses = vec![
+        (ore_api::ID, program_id),
+        (ore_proof, proof),
+        (CONFIG_ADDRESS, config_pubkey()),
+        (MINT_ADDRESS, mint_pubkey()),
+        (TREASURY_ADDRESS, treasury_pubkey()),
+        (TREASURY_TOKENS_ADDRESS, treasury_tokens_pubkey()),
+    ];
+    addresses.extend(BUS_ADDRESSES.into_iter().zip(bus_pubkeys()));
+    let remap = |address: Pubkey| {
+        addresses
+            .iter()
+            .find(|(ore, _)| ore.eq(&address))
+            .map_or(address, |(_, ours)| *ours)
+    };
+    ix.program_id = remap(ix.program_id);
+    for account in ix.accounts.iter_mut() {
+        account.pubkey = remap(account.pubkey);
+    }
+
+    // Open carries the bump of the proof it creates
+    if ix.program_id.eq(&program_id) && ix.data.first().eq(&Some(&(OreInstruction::Open as u8))) {
+        ix.data[1] = proof_bump;
+    }
+    ix
+}
@@ -0,0 +1,70 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use colored::*;
+use solana_sdk::clock::Clock;
+
+// How long the cluster time is extrapolated locally before the clock sysvar is read again
+const RESYNC_INTERVAL: Duration = Duration::from_secs(300);
+
+// Local and cluster time further apart than this usually means a broken NTP setup
+const MAX_DRIFT: i64 = 5;
+
+// The cluster time, from the clock sysvar at the last sync and the local monotonic clock
+// since, shared by every copy of a miner
+#[derive(Clone, Default)]
+pub struct ClusterClock {
+    synced: Arc<Mutex<Option<ClockSync>>>,
+}
+
+#[derive(Clone, Copy)]
+struct ClockSync {
+    at: Instant,
+    unix_timestamp: i64,
+    offset: i64,
+}
+
+impl ClusterClock {
+    // The cluster time, or None if the clock sysvar needs to be read again
+    pub fn now(&self) -> Option<i64> {
+        self.synced
+            .lock()
+            .unwrap()
+            .filter(|sync| sync.at.elapsed().lt(&RESYNC_INTERVAL))
+            .map(|sync| sync.unix_timestamp + sync.at.elapsed().as_secs() as i64)
+    }
+
+    // Seconds the local wall clock is ahead of the cluster at the last sync
+    pub fn offset(&self) -> Option<i64> {
+        self.synced.lock().unwrap().map(|sync| sync.offset)
+    }
+
+    // Syncs to the clock sysvar read at the given instant
+    pub fn sync(&self, clock: &Clock, read_at: Instant) {
+        let local = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64)
+            .saturating_sub(read_at.elapsed().as_secs() as i64);
+        let offset = local.saturating_sub(clock.unix_timestamp);
+        if offset.abs().gt(&MAX_DRIFT) {
+            println!(
+                "{} The local clock is {}s {} the cluster. Check that NTP is running.",
+                "WARNING".bold().yellow(),
+                offset.abs(),
+                if offset.gt(&0) { "ahead of" } else { "behind" }
+            );
+        }
+        *self.synced.lock().unwrap() = Some(ClockSync {
+            at: read_at,
+            unix_timestamp: clock.unix_timestamp,
+            offset,
+        });
+    }
+
+    // Drops the sync, so the next round reads the clock sysvar again
+    pub fn invalidate(&self) {
+        *self.synced.lock().unwrap() = None;
+    }
+}
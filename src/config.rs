@@ -1,13 +1,25 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
 use colored::Colorize;
+use ore_api::{consts::EPOCH_DURATION, state::Config};
 
 use crate::{
-    utils::{amount_u64_to_string, get_config},
+    utils::{amount_u64_to_string, get_config, get_treasury_balance},
     Miner,
 };
 
 impl Miner {
     pub async fn config(&self) {
-        let config = get_config(&self.rpc_client).await;
+        let config = match get_config(&self.rpc_client).await {
+            Ok(config) => config,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return;
+            }
+        };
         println!("{}: {}", "Last reset at".bold(), config.last_reset_at);
         println!("{}: {}", "Min difficulty".bold(), config.min_difficulty);
         println!("{}: {}", "Base reward rate".bold(), config.base_reward_rate);
@@ -16,5 +28,55 @@ impl Miner {
             "Top stake".bold(),
             amount_u64_to_string(config.top_balance)
         );
+        match get_treasury_balance(&self.rpc_client).await {
+            Ok(balance) => println!(
+                "{}: {} ORE",
+                "Treasury".bold(),
+                amount_u64_to_string(balance)
+            ),
+            Err(err) => println!("{} {}", "ERROR".bold().red(), err),
+        }
+    }
+}
+
+// The config account and treasury balance, shared by every copy of a miner, that are refetched
+// once older than the ttl or once their epoch has ended, since a reset rewrites them
+#[derive(Clone)]
+pub struct ConfigCache {
+    ttl: Duration,
+    entry: Arc<Mutex<Option<(Instant, Config, u64)>>>,
+}
+
+impl ConfigCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // The cached config and treasury balance, or None if they have expired
+    pub fn get(&self) -> Option<(Config, u64)> {
+        self.entry
+            .lock()
+            .unwrap()
+            .filter(|(expires_at, _, _)| Instant::now().lt(expires_at))
+            .map(|(_, config, treasury_balance)| (config, treasury_balance))
+    }
+
+    // Caches the config and treasury balance read at the given cluster time
+    pub fn set(&self, config: Config, treasury_balance: u64, now: i64) {
+        let epoch_left = config
+            .last_reset_at
+            .saturating_add(EPOCH_DURATION)
+            .saturating_sub(now)
+            .max(0) as u64;
+        let expires_at = Instant::now() + self.ttl.min(Duration::from_secs(epoch_left));
+        *self.entry.lock().unwrap() = Some((expires_at, config, treasury_balance));
+    }
+
+    // Drops the cached config, so the next read refetches it
+    pub fn invalidate(&self) {
+        *self.entry.lock().unwrap() = None;
     }
 }
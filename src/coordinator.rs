@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use colored::*;
+use drillx::Solution;
+use solana_rpc_client::spinner;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+
+use crate::{
+    args::MineArgs,
+    mine::nonce_start,
+    pool::WorkerPool,
+    protocol::{read_message, write_message, Message},
+};
+
+// Number of nonce ranges handed out to workers each round
+const RANGE_COUNT: u64 = 1 << 16;
+
+// A connected worker
+struct Peer {
+    sender: UnboundedSender<Message>,
+    range: Option<u64>,
+    hashes: u64,
+}
+
+// The job currently being hashed by the workers
+struct Round {
+    job_id: u64,
+    challenge: [u8; 32],
+    cutoff_time: u64,
+    min_difficulty: u32,
+    continue_after_min: bool,
+    next_range: u64,
+    orphaned: Vec<u64>,
+    best: Option<(u64, [u8; 16], u32)>,
+}
+
+#[derive(Default)]
+struct State {
+    peers: HashMap<u64, Peer>,
+    round: Option<Round>,
+    next_peer_id: u64,
+    last_job_id: u64,
+}
+
+impl State {
+    // Hands the peer a nonce range of the current round, reusing ranges of disconnected peers first
+    fn assign(&mut self, peer_id: u64) {
+        let (Some(round), Some(peer)) = (self.round.as_mut(), self.peers.get_mut(&peer_id)) else {
+            return;
+        };
+        let range = match round.orphaned.pop() {
+            Some(range) => range,
+            None => {
+                round.next_range += 1;
+                round.next_range - 1
+            }
+        };
+        peer.range = Some(range);
+        peer.hashes = 0;
+        peer.sender
+            .send(Message::Job {
+                job_id: round.job_id,
+                challenge: round.challenge,
+                start_nonce: nonce_start(range, RANGE_COUNT),
+                end_nonce: nonce_start(range + 1, RANGE_COUNT),
+                cutoff_time: round.cutoff_time,
+                min_difficulty: round.min_difficulty,
+                continue_after_min: round.continue_after_min,
+            })
+            .ok();
+    }
+
+    fn disconnect(&mut self, peer_id: u64) {
+        let Some(peer) = self.peers.remove(&peer_id) else {
+            return;
+        };
+        if let (Some(round), Some(range)) = (self.round.as_mut(), peer.range) {
+            round.orphaned.push(range);
+        }
+    }
+
+    // Records a worker's result once it has been verified against the challenge
+    fn report(&mut self, peer_id: u64, job_id: u64, nonce: u64, digest: [u8; 16], hashes: u64) {
+        let Some(round) = self.round.as_mut().filter(|r| r.job_id.eq(&job_id)) else {
+            return;
+        };
+        if let Some(peer) = self.peers.get_mut(&peer_id) {
+            peer.hashes = hashes;
+        }
+        let solution = Solution::new(digest, nonce.to_le_bytes());
+        if !solution.is_valid(&round.challenge) {
+            return;
+        }
+        let difficulty = solution.to_hash().difficulty();
+        if round.best.map_or(true, |(_, _, d)| difficulty.gt(&d)) {
+            round.best = Some((nonce, digest, difficulty));
+        }
+    }
+}
+
+// Best hash found by the workers in a round
+pub struct RemoteResult {
+    pub best: Option<(u64, [u8; 16], u32)>,
+    pub hashes: u64,
+    pub elapsed: f64,
+}
+
+// Owns the keypair and rpc connection while remote workers do the hashing
+pub struct Coordinator {
+    state: Arc<Mutex<State>>,
+}
+
+impl Coordinator {
+    pub async fn bind(addr: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        println!("Coordinating workers on {}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State::default()));
+        {
+            let state = state.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, addr)) = listener.accept().await {
+                    println!("  Worker connected from {}", addr);
+                    tokio::spawn(serve(stream, state.clone()));
+                }
+            });
+        }
+        Ok(Self { state })
+    }
+
+    // Farms the challenge out to the connected workers until the round ends
+    pub async fn find_hash(
+        &self,
+        args: &MineArgs,
+        challenge: [u8; 32],
+        cutoff_time: u64,
+        min_difficulty: u32,
+        pool: &WorkerPool,
+    ) -> RemoteResult {
+        // Start the round on every connected worker
+        let timer = Instant::now();
+        let job_id = {
+            let mut state = self.state.lock().unwrap();
+            state.last_job_id += 1;
+            state.round = Some(Round {
+                job_id: state.last_job_id,
+                challenge,
+                cutoff_time,
+                min_difficulty,
+                continue_after_min: args.continue_after_min,
+                next_range: 0,
+                orphaned: vec![],
+                best: None,
+            });
+            let peer_ids: Vec<_> = state.peers.keys().copied().collect();
+            for peer_id in peer_ids {
+                state.assign(peer_id);
+            }
+            state.last_job_id
+        };
+
+        // Wait for the round to end
+        let progress_bar = spinner::new_progress_bar();
+        loop {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let state = self.state.lock().unwrap();
+            let difficulty = state
+                .round
+                .as_ref()
+                .and_then(|r| r.best)
+                .map_or(0, |(_, _, d)| d);
+            let hashes: u64 = state.peers.values().map(|p| p.hashes).sum();
+            let elapsed = timer.elapsed().as_secs();
+            progress_bar.set_message(format!(
+                "MIN_DIFFICULTY: {} > {} Coordinating {} workers... {} hashes ({} sec remaining)",
+                min_difficulty,
+                difficulty,
+                state.peers.len(),
+                hashes,
+                cutoff_time.saturating_sub(elapsed),
+            ));
+            let floor = difficulty.ge(&min_difficulty);
+            let cutoff = elapsed.ge(&cutoff_time) && (!args.continue_after_min || floor);
+            let deadline = args.max_hash_time.is_some_and(|t| elapsed.ge(&t));
+            if cutoff
+                || (floor && !args.continue_after_min)
+                || deadline
+                || pool.interrupt.load(Ordering::Relaxed)
+                || pool.stale.load(Ordering::Relaxed)
+            {
+                break;
+            }
+        }
+
+        // Stop the workers and return the best verified hash
+        let mut state = self.state.lock().unwrap();
+        for peer in state.peers.values_mut() {
+            peer.sender.send(Message::Stop { job_id }).ok();
+            peer.range = None;
+        }
+        let hashes = state.peers.values().map(|p| p.hashes).sum();
+        let best = state.round.take().and_then(|r| r.best);
+        progress_bar.finish_and_clear();
+        RemoteResult {
+            best,
+            hashes,
+            elapsed: timer.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+// Relays messages between a worker connection and the shared state
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let (mut reader, mut writer) = stream.into_split();
+    let (sender, mut outbox) = unbounded_channel();
+    let peer_id = {
+        let mut state = state.lock().unwrap();
+        state.next_peer_id += 1;
+        state.next_peer_id
+    };
+
+    // Forward queued messages to the worker
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = outbox.recv().await {
+            if write_message(&mut writer, &message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Handle messages from the worker until it disconnects
+    while let Ok(message) = read_message(&mut reader).await {
+        let mut shared = state.lock().unwrap();
+        match message {
+            Message::Hello { cores } => {
+                println!("  Worker {} joined with {} cores", peer_id, cores);
+                shared.peers.insert(
+                    peer_id,
+                    Peer {
+                        sender: sender.clone(),
+                        range: None,
+                        hashes: 0,
+                    },
+                );
+                shared.assign(peer_id);
+            }
+            Message::Result {
+                job_id,
+                nonce,
+                digest,
+                hashes,
+                ..
+            } => shared.report(peer_id, job_id, nonce, digest, hashes),
+            _ => {}
+        }
+    }
+    println!(
+        "  {} Worker {} disconnected",
+        "WARNING".bold().yellow(),
+        peer_id
+    );
+    state.lock().unwrap().disconnect(peer_id);
+    writer_task.abort();
+}
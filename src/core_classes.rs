@@ -0,0 +1,123 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use core_affinity::CoreId;
+use drillx::equix;
+use serde_json::{json, Value};
+
+use crate::utils::config_dir_path;
+
+// How long each core hashes during calibration
+const CALIBRATION_TIME: Duration = Duration::from_millis(500);
+
+// Cores below this share of the fastest core's speed count as efficiency cores
+const EFFICIENCY_RATIO: f64 = 0.6;
+
+const CACHE_FILE: &str = "ore-core-classes.json";
+
+// Ids of the efficiency cores in a hybrid cpu, read from the os where it exposes them. Else,
+// with `calibration`, from a cached or fresh calibration burst, and none without.
+pub fn efficiency_cores(calibration: bool) -> Vec<usize> {
+    let core_ids = core_affinity::get_core_ids().unwrap_or_default();
+    let ids = core_ids.iter().map(|c| c.id).collect::<Vec<_>>();
+    if let Some(efficiency) = os_efficiency_cores(&ids) {
+        return efficiency;
+    }
+    if !calibration {
+        return vec![];
+    }
+    let path = config_dir_path(CACHE_FILE);
+    if let Some(efficiency) = load_cache(&path, &ids) {
+        return efficiency;
+    }
+    println!("Calibrating core speeds...");
+    let efficiency = calibrate(core_ids);
+    let value = json!({ "core_ids": ids, "efficiency": efficiency });
+    fs::write(&path, value.to_string()).ok();
+    efficiency
+}
+
+// Arm exposes a relative capacity per core, Intel hybrid cpus list their atom cores
+#[cfg(target_os = "linux")]
+fn os_efficiency_cores(ids: &[usize]) -> Option<Vec<usize>> {
+    use std::str::FromStr;
+
+    use crate::pool::CoreList;
+
+    if let Ok(atom) = fs::read_to_string("/sys/devices/cpu_atom/cpus") {
+        return CoreList::from_str(atom.trim()).ok().map(|list| list.0);
+    }
+    let capacities = ids
+        .iter()
+        .map(|id| {
+            fs::read_to_string(format!("/sys/devices/system/cpu/cpu{}/cpu_capacity", id))
+                .ok()?
+                .trim()
+                .parse::<f64>()
+                .ok()
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let max = capacities.iter().copied().fold(0.0, f64::max);
+    Some(
+        ids.iter()
+            .zip(capacities)
+            .filter(|(_, c)| c.lt(&(max * EFFICIENCY_RATIO)))
+            .map(|(id, _)| *id)
+            .collect(),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+fn os_efficiency_cores(_ids: &[usize]) -> Option<Vec<usize>> {
+    None
+}
+
+// Reuses a calibration of the same set of cores
+fn load_cache(path: &str, ids: &[usize]) -> Option<Vec<usize>> {
+    let value: Value = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let cached = value["core_ids"]
+        .as_array()?
+        .iter()
+        .map(|id| id.as_u64().map(|id| id as usize))
+        .collect::<Option<Vec<_>>>()?;
+    if cached.ne(ids) {
+        return None;
+    }
+    value["efficiency"]
+        .as_array()?
+        .iter()
+        .map(|id| id.as_u64().map(|id| id as usize))
+        .collect()
+}
+
+// Hashes on every core at once and flags the ones well below the fastest
+fn calibrate(core_ids: Vec<CoreId>) -> Vec<usize> {
+    let handles = core_ids
+        .into_iter()
+        .map(|core_id| {
+            std::thread::spawn(move || {
+                let _ = core_affinity::set_for_current(core_id);
+                let mut memory = equix::SolverMemory::new();
+                let timer = Instant::now();
+                let mut nonce = 0u64;
+                while timer.elapsed().lt(&CALIBRATION_TIME) {
+                    let _ = drillx::hash_with_memory(&mut memory, &[0; 32], &nonce.to_le_bytes());
+                    nonce += 1;
+                }
+                (core_id.id, nonce as f64 / timer.elapsed().as_secs_f64())
+            })
+        })
+        .collect::<Vec<_>>();
+    let rates = handles
+        .into_iter()
+        .filter_map(|h| h.join().ok())
+        .collect::<Vec<_>>();
+    let max = rates.iter().map(|(_, r)| *r).fold(0.0, f64::max);
+    rates
+        .into_iter()
+        .filter(|(_, r)| r.lt(&(max * EFFICIENCY_RATIO)))
+        .map(|(id, _)| id)
+        .collect()
+}
@@ -1,102 +1,513 @@
 use crate::Miner;
 
-use ore_api::consts::BUS_ADDRESSES;
+use async_trait::async_trait;
+use clap::ValueEnum;
 use reqwest::Client;
 use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
 use url::Url;
 
-enum FeeStrategy {
+// Price used when no priority fee is given
+const DEFAULT_PRIORITY_FEE: u64 = 500_000;
+
+// Priority fee estimation API, selected with --dynamic-fee-provider
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FeeProviderKind {
     Helius,
     Triton,
+    Quicknode,
+    Rpc,
 }
 
-impl Miner {
-    pub async fn dynamic_fee(&self) -> Option<u64> {
-        // Get url
-        let rpc_url = self
-            .dynamic_fee_url
-            .clone()
-            .unwrap_or(self.rpc_client.url());
-
-        // Select fee estiamte strategy
-        let host = Url::parse(&rpc_url)
-            .unwrap()
+impl FeeProviderKind {
+    // Provider serving the given url, falling back to getRecentPrioritizationFees
+    fn detect(url: &str) -> Result<Self, String> {
+        let host = Url::parse(url)
+            .map_err(|err| err.to_string())?
             .host_str()
-            .unwrap()
+            .unwrap_or_default()
             .to_string();
-        let strategy = if host.contains("helius-rpc.com") {
-            FeeStrategy::Helius
+        Ok(if host.contains("helius-rpc.com") {
+            FeeProviderKind::Helius
         } else if host.contains("rpcpool.com") {
-            FeeStrategy::Triton
+            FeeProviderKind::Triton
+        } else if host.contains("quiknode.pro") {
+            FeeProviderKind::Quicknode
         } else {
-            return None;
-        };
+            FeeProviderKind::Rpc
+        })
+    }
 
-        // Build fee estimate request
-        let client = Client::new();
-        let ore_addresses: Vec<String> = std::iter::once(ore_api::ID.to_string())
-            .chain(BUS_ADDRESSES.iter().map(|pubkey| pubkey.to_string()))
-            .collect();
-        let body = match strategy {
-            FeeStrategy::Helius => {
-                json!({
-                    "jsonrpc": "2.0",
-                    "id": "priority-fee-estimate",
-                    "method": "getPriorityFeeEstimate",
-                    "params": [{
-                        "accountKeys": ore_addresses,
-                        "options": {
-                            "recommended": true
-                        }
-                    }]
-                })
+    fn provider(&self) -> Box<dyn FeeProvider> {
+        match self {
+            FeeProviderKind::Helius => Box::new(Helius),
+            FeeProviderKind::Triton => Box::new(Triton),
+            FeeProviderKind::Quicknode => Box::new(Quicknode),
+            FeeProviderKind::Rpc => Box::new(Rpc),
+        }
+    }
+}
+
+// Estimates a compute unit price for a transaction writing the given accounts
+#[async_trait]
+pub trait FeeProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn estimate(
+        &self,
+        url: &str,
+        accounts: &[Pubkey],
+        strategy: FeeStrategy,
+    ) -> Result<u64, String>;
+}
+
+// Posts a json rpc request and returns its result
+async fn post(url: &str, method: &str, params: Value) -> Result<Value, String> {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": "priority-fee-estimate",
+        "method": method,
+        "params": params,
+    });
+    let response: Value = Client::new()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?
+        .json()
+        .await
+        .map_err(|err| err.to_string())?;
+    match response.get("result") {
+        Some(result) => Ok(result.clone()),
+        None => Err(format!(
+            "Failed to parse priority fee. Response: {:?}",
+            response
+        )),
+    }
+}
+
+// Helius getPriorityFeeEstimate
+struct Helius;
+
+#[async_trait]
+impl FeeProvider for Helius {
+    fn name(&self) -> &'static str {
+        "helius"
+    }
+
+    async fn estimate(
+        &self,
+        url: &str,
+        accounts: &[Pubkey],
+        _strategy: FeeStrategy,
+    ) -> Result<u64, String> {
+        let account_keys: Vec<String> = accounts.iter().map(|pubkey| pubkey.to_string()).collect();
+        let params = json!([{
+            "accountKeys": account_keys,
+            "options": {
+                "recommended": true
             }
-            FeeStrategy::Triton => {
-                json!({
-                    "jsonrpc": "2.0",
-                    "id": "priority-fee-estimate",
-                    "method": "getRecentPrioritizationFees",
-                    "params": [
-                        ore_addresses,
-                        {
-                            "percentile": 5000,
-                        }
-                    ]
-                })
+        }]);
+        let result = post(url, "getPriorityFeeEstimate", params).await?;
+        result["priorityFeeEstimate"]
+            .as_f64()
+            .map(|fee| fee as u64)
+            .ok_or_else(|| format!("Failed to parse priority fee. Result: {:?}", result))
+    }
+}
+
+// Triton getRecentPrioritizationFees with a percentile
+struct Triton;
+
+#[async_trait]
+impl FeeProvider for Triton {
+    fn name(&self) -> &'static str {
+        "triton"
+    }
+
+    async fn estimate(
+        &self,
+        url: &str,
+        accounts: &[Pubkey],
+        strategy: FeeStrategy,
+    ) -> Result<u64, String> {
+        let account_keys: Vec<String> = accounts.iter().map(|pubkey| pubkey.to_string()).collect();
+        let params = json!([
+            account_keys,
+            {
+                "percentile": strategy.percentile() as u64 * 100,
             }
+        ]);
+        let result = post(url, "getRecentPrioritizationFees", params).await?;
+        result
+            .as_array()
+            .and_then(|arr| arr.last())
+            .and_then(|last| last["prioritizationFee"].as_u64())
+            .ok_or_else(|| format!("Failed to parse priority fee. Result: {:?}", result))
+    }
+}
+
+// QuickNode qn_estimatePriorityFees, which takes a single account
+struct Quicknode;
+
+#[async_trait]
+impl FeeProvider for Quicknode {
+    fn name(&self) -> &'static str {
+        "quicknode"
+    }
+
+    async fn estimate(
+        &self,
+        url: &str,
+        accounts: &[Pubkey],
+        strategy: FeeStrategy,
+    ) -> Result<u64, String> {
+        let mut params = json!({
+            "last_n_blocks": 100,
+            "api_version": 2,
+        });
+        if let Some(account) = accounts.first() {
+            params["account"] = json!(account.to_string());
+        }
+        let result = post(url, "qn_estimatePriorityFees", params).await?;
+        let level = match strategy {
+            FeeStrategy::Median => "medium",
+            FeeStrategy::P75 => "high",
+            FeeStrategy::P90 | FeeStrategy::MaxOfRecent => "extreme",
         };
+        result["per_compute_unit"][level]
+            .as_f64()
+            .map(|fee| fee as u64)
+            .ok_or_else(|| format!("Failed to parse priority fee. Result: {:?}", result))
+    }
+}
 
-        // Send request
-        let response: Value = client
-            .post(rpc_url)
-            .json(&body)
-            .send()
+// getRecentPrioritizationFees, aggregated with --fee-strategy
+struct Rpc;
+
+#[async_trait]
+impl FeeProvider for Rpc {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    async fn estimate(
+        &self,
+        url: &str,
+        accounts: &[Pubkey],
+        strategy: FeeStrategy,
+    ) -> Result<u64, String> {
+        let fees = RpcClient::new(url.to_string())
+            .get_recent_prioritization_fees(accounts)
             .await
-            .unwrap()
-            .json()
+            .map_err(|err| err.to_string())?
+            .into_iter()
+            .map(|f| f.prioritization_fee)
+            .collect();
+        strategy
+            .aggregate(fees)
+            .ok_or("No recent prioritization fees".to_string())
+    }
+}
+
+// How recent fee samples are reduced to one price
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FeeStrategy {
+    Median,
+    P75,
+    P90,
+    MaxOfRecent,
+}
+
+impl FeeStrategy {
+    fn percentile(&self) -> u8 {
+        match self {
+            FeeStrategy::Median => 50,
+            FeeStrategy::P75 => 75,
+            FeeStrategy::P90 => 90,
+            FeeStrategy::MaxOfRecent => 100,
+        }
+    }
+
+    // Aggregates fee samples, or None if there are none
+    pub fn aggregate(&self, mut fees: Vec<u64>) -> Option<u64> {
+        if fees.is_empty() {
+            return None;
+        }
+        fees.sort_unstable();
+        let index = (fees.len() - 1) * self.percentile() as usize / 100;
+        Some(fees[index])
+    }
+}
+
+// How the price added by --fee-per-difficulty grows with difficulty over the minimum
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FeeScaling {
+    Linear,
+    Exponential,
+}
+
+impl FeeScaling {
+    // Multiple of --fee-per-difficulty paid at this much difficulty over the minimum
+    pub fn factor(&self, excess: u32) -> u64 {
+        match self {
+            FeeScaling::Linear => excess as u64,
+            FeeScaling::Exponential => 1u64.checked_shl(excess).unwrap_or(u64::MAX),
+        }
+    }
+}
+
+// Compute unit price and fee strategy of one command, falling back to the global flags
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FeeOverride {
+    pub priority_fee: Option<u64>,
+    pub fee_strategy: Option<FeeStrategy>,
+}
+
+// How compute unit prices are chosen
+#[derive(Clone, Debug)]
+pub struct FeeConfig {
+    pub priority_fee: Option<u64>,
+    pub dynamic_fee: bool,
+    pub dynamic_fee_url: Option<String>,
+    pub dynamic_fee_provider: Option<FeeProviderKind>,
+    pub fee_strategy: FeeStrategy,
+    pub priority_fee_cap: Option<u64>,
+    pub bump_after_slots: Option<u64>,
+    pub bump_factor: f64,
+    pub fee_per_difficulty: Option<u64>,
+    pub fee_scaling: FeeScaling,
+    pub claim: FeeOverride,
+    pub stake: FeeOverride,
+}
+
+impl FeeConfig {
+    // Fees of a transaction with the given ore instructions, such as "claim"
+    pub fn for_kind(&self, kind: &str) -> FeeConfig {
+        let fee_override = match kind {
+            "claim" => self.claim,
+            "stake" => self.stake,
+            _ => return self.clone(),
+        };
+        FeeConfig {
+            priority_fee: fee_override.priority_fee.or(self.priority_fee),
+            fee_strategy: fee_override.fee_strategy.unwrap_or(self.fee_strategy),
+            ..self.clone()
+        }
+    }
+
+    // Price paid without dynamic fees, or when estimation fails
+    pub fn static_fee(&self) -> u64 {
+        self.priority_fee.unwrap_or(DEFAULT_PRIORITY_FEE)
+    }
+
+    // Lowest price paid with dynamic fees
+    pub fn floor(&self) -> u64 {
+        self.priority_fee.unwrap_or(0)
+    }
+
+    // Price for a solution of the given difficulty, or None without --fee-per-difficulty
+    pub fn scaled_fee(&self, difficulty: u32, min_difficulty: u32) -> Option<u64> {
+        let scale = self.fee_per_difficulty?;
+        let factor = self
+            .fee_scaling
+            .factor(difficulty.saturating_sub(min_difficulty));
+        Some(
+            self.cap(
+                self.static_fee()
+                    .saturating_add(scale.saturating_mul(factor)),
+            ),
+        )
+    }
+
+    // Highest price paid. Dynamic fees are held to the default price, or the floor if it's
+    // higher, unless a cap was given.
+    pub fn max_fee(&self) -> Option<u64> {
+        self.priority_fee_cap.or(self
+            .dynamic_fee
+            .then(|| DEFAULT_PRIORITY_FEE.max(self.floor())))
+    }
+
+    // Limits a fee to the cap
+    pub fn cap(&self, fee: u64) -> u64 {
+        self.max_fee().map_or(fee, |cap| fee.min(cap))
+    }
+
+    // Price paid for a dynamic estimate: at least the floor, then at most the cap, so the cap
+    // wins when the floor is above it
+    pub fn bound(&self, estimate: u64) -> u64 {
+        self.cap(estimate.max(self.floor()))
+    }
+}
+
+impl Miner {
+    // Provider estimating dynamic fees, chosen by --dynamic-fee-provider or the url
+    fn fee_provider(&self) -> Result<Box<dyn FeeProvider>, String> {
+        let kind = match self.fees.dynamic_fee_provider {
+            Some(kind) => kind,
+            None => FeeProviderKind::detect(&self.dynamic_fee_url())?,
+        };
+        Ok(kind.provider())
+    }
+
+    fn dynamic_fee_url(&self) -> String {
+        self.fees
+            .dynamic_fee_url
+            .clone()
+            .unwrap_or(self.rpc_client.url())
+    }
+
+    // Estimates a compute unit price for a transaction writing the given accounts,
+    // with the name of the provider that estimated it
+    pub async fn dynamic_fee(
+        &self,
+        accounts: &[Pubkey],
+        fees: &FeeConfig,
+    ) -> Result<(u64, &'static str), String> {
+        let provider = self.fee_provider()?;
+        let fee = provider
+            .estimate(&self.dynamic_fee_url(), accounts, fees.fee_strategy)
             .await
+            .map_err(|err| format!("{}: {}", provider.name(), err))?;
+        Ok((fee, provider.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use drillx::Solution;
+    use solana_program::instruction::Instruction;
+    use spl_associated_token_account::instruction::create_associated_token_account;
+
+    use super::*;
+    use crate::{
+        tx_log::instruction_kind,
+        utils::{bus_pubkeys, mint_pubkey, proof_pubkey},
+    };
+
+    fn fees(priority_fee: Option<u64>, priority_fee_cap: Option<u64>) -> FeeConfig {
+        FeeConfig {
+            priority_fee,
+            dynamic_fee: true,
+            dynamic_fee_url: None,
+            dynamic_fee_provider: None,
+            fee_strategy: FeeStrategy::Median,
+            priority_fee_cap,
+            bump_after_slots: None,
+            bump_factor: 1.0,
+            fee_per_difficulty: None,
+            fee_scaling: FeeScaling::Linear,
+            claim: FeeOverride::default(),
+            stake: FeeOverride::default(),
+        }
+    }
+
+    #[test]
+    fn aggregates_samples() {
+        let samples: Vec<u64> = (1..=11).rev().map(|i| i * 100).collect();
+        assert_eq!(FeeStrategy::Median.aggregate(samples.clone()), Some(600));
+        assert_eq!(FeeStrategy::P75.aggregate(samples.clone()), Some(800));
+        assert_eq!(FeeStrategy::P90.aggregate(samples.clone()), Some(1000));
+        assert_eq!(FeeStrategy::MaxOfRecent.aggregate(samples), Some(1100));
+    }
+
+    #[test]
+    fn aggregates_few_samples() {
+        assert_eq!(FeeStrategy::Median.aggregate(vec![]), None);
+        assert_eq!(FeeStrategy::P90.aggregate(vec![42]), Some(42));
+        assert_eq!(FeeStrategy::Median.aggregate(vec![10, 0]), Some(0));
+        assert_eq!(FeeStrategy::MaxOfRecent.aggregate(vec![10, 0]), Some(10));
+    }
+
+    #[test]
+    fn cap_limits_spikes() {
+        let fees = fees(None, Some(50_000));
+        let spike = FeeStrategy::MaxOfRecent
+            .aggregate(vec![1_000, 2_000, 10_000_000])
             .unwrap();
+        assert_eq!(fees.bound(spike), 50_000);
+        assert_eq!(fees.bound(1_000), 1_000);
+    }
+
+    #[test]
+    fn floor_raises_low_estimates() {
+        let fees = fees(Some(10_000), Some(50_000));
+        assert_eq!(fees.bound(0), 10_000);
+        assert_eq!(fees.bound(20_000), 20_000);
+    }
+
+    #[test]
+    fn cap_wins_over_a_higher_floor() {
+        let fees = fees(Some(80_000), Some(50_000));
+        assert_eq!(fees.bound(0), 50_000);
+        assert_eq!(fees.bound(1_000_000), 50_000);
+    }
+
+    // Fees applied to a transaction of the given instructions
+    fn fees_for(fees: &FeeConfig, ixs: &[Instruction]) -> FeeConfig {
+        fees.for_kind(&instruction_kind(ixs))
+    }
 
-        // Parse response
-        let calculated_fee = match strategy {
-            FeeStrategy::Helius => response["result"]["priorityFeeEstimate"]
-                .as_f64()
-                .map(|fee| fee as u64)
-                .ok_or_else(|| format!("Failed to parse priority fee. Response: {:?}", response))
-                .unwrap(),
-            FeeStrategy::Triton => response["result"]
-                .as_array()
-                .and_then(|arr| arr.last())
-                .and_then(|last| last["prioritizationFee"].as_u64())
-                .ok_or_else(|| format!("Failed to parse priority fee. Response: {:?}", response))
-                .unwrap(),
+    #[test]
+    fn applies_the_fee_of_each_instruction_set() {
+        let signer = Pubkey::new_unique();
+        let solution = Solution::new([0; 16], [0; 8]);
+        let auth = ore_api::instruction::auth(proof_pubkey(signer));
+        let mine = ore_api::instruction::mine(signer, signer, bus_pubkeys()[0], solution);
+        let reset = ore_api::instruction::reset(signer);
+        let claim = ore_api::instruction::claim(signer, Pubkey::new_unique(), 1);
+        let create_ata =
+            create_associated_token_account(&signer, &signer, &mint_pubkey(), &spl_token::id());
+        let stake = ore_api::instruction::stake(signer, Pubkey::new_unique(), 1);
+        let fees = FeeConfig {
+            claim: FeeOverride {
+                priority_fee: Some(1_000),
+                fee_strategy: Some(FeeStrategy::P90),
+            },
+            stake: FeeOverride {
+                priority_fee: Some(2_000),
+                fee_strategy: None,
+            },
+            ..fees(Some(100_000), None)
         };
 
-        // Check if the calculated fee is higher than max
-        if let Some(max_fee) = self.priority_fee {
-            Some(calculated_fee.min(max_fee))
-        } else {
-            Some(calculated_fee)
+        // Mine transactions, with a reset or an auto-claim, pay the global fee
+        for ixs in [
+            vec![auth.clone(), mine.clone()],
+            vec![auth.clone(), reset, mine.clone()],
+            vec![auth, mine, claim.clone()],
+        ] {
+            let applied = fees_for(&fees, &ixs);
+            assert_eq!(applied.static_fee(), 100_000);
+            assert!(matches!(applied.fee_strategy, FeeStrategy::Median));
         }
+
+        // Claims and stakes pay their own, ignoring instructions of other programs
+        let applied = fees_for(&fees, &[create_ata, claim]);
+        assert_eq!(applied.static_fee(), 1_000);
+        assert!(matches!(applied.fee_strategy, FeeStrategy::P90));
+        let applied = fees_for(&fees, &[stake.clone()]);
+        assert_eq!(applied.static_fee(), 2_000);
+        assert!(matches!(applied.fee_strategy, FeeStrategy::Median));
+
+        // An override without a fee falls back to the global one
+        let fees = FeeConfig {
+            stake: FeeOverride::default(),
+            ..fees
+        };
+        assert_eq!(fees_for(&fees, &[stake]).static_fee(), 100_000);
+    }
+
+    #[test]
+    fn dynamic_fees_are_capped_by_default() {
+        assert_eq!(fees(None, None).bound(10_000_000), DEFAULT_PRIORITY_FEE);
+        assert_eq!(fees(Some(800_000), None).bound(10_000_000), 800_000);
+        let static_fees = FeeConfig {
+            dynamic_fee: false,
+            ..fees(None, None)
+        };
+        assert_eq!(static_fees.cap(10_000_000), 10_000_000);
     }
 }
@@ -0,0 +1,89 @@
+// Each difficulty level halves the chance of a hash, so reaching difficulty `d`
+// takes 2^d hashes on average (a geometric distribution with p = 2^-d)
+pub fn expected_hashes(difficulty: u32) -> f64 {
+    2f64.powi(difficulty as i32)
+}
+
+// Expected seconds to find a hash at or above the difficulty at the given hashrate
+// (the distribution is memoryless, so the time already spent does not count)
+pub fn eta_secs(difficulty: u32, hashrate: f64) -> Option<f64> {
+    if hashrate.le(&0.0) {
+        return None;
+    }
+    Some(expected_hashes(difficulty) / hashrate)
+}
+
+// Chance of at least one hash at or above the difficulty within the given time
+pub fn probability_within(difficulty: u32, hashrate: f64, secs: f64) -> f64 {
+    let hashes = (hashrate * secs).max(0.0);
+    if hashes.eq(&0.0) {
+        return 0.0;
+    }
+    let p = 1.0 / expected_hashes(difficulty);
+    if p.ge(&1.0) {
+        return 1.0;
+    }
+    1.0 - (hashes * (-p).ln_1p()).exp()
+}
+
+// Highest difficulty expected to be reached within the given time
+pub fn reachable_difficulty(hashrate: f64, secs: f64) -> u32 {
+    let hashes = hashrate * secs;
+    if hashes.lt(&1.0) {
+        return 0;
+    }
+    hashes.log2().floor() as u32
+}
+
+// Formats seconds as e.g. `35s`, `4m10s` or `2h3m`
+pub fn format_eta(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs.lt(&60) {
+        format!("{}s", secs)
+    } else if secs.lt(&3600) {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_needs_a_hashrate() {
+        assert_eq!(eta_secs(10, 0.0), None);
+        assert_eq!(eta_secs(10, -1.0), None);
+        assert_eq!(probability_within(10, 0.0, 60.0), 0.0);
+    }
+
+    #[test]
+    fn difficulty_zero_is_immediate() {
+        assert_eq!(expected_hashes(0), 1.0);
+        assert_eq!(eta_secs(0, 100.0), Some(0.01));
+        assert_eq!(probability_within(0, 100.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn expected_time_doubles_per_level() {
+        assert_eq!(eta_secs(10, 1024.0), Some(1.0));
+        assert_eq!(eta_secs(11, 1024.0), Some(2.0));
+    }
+
+    #[test]
+    fn probability_at_the_expected_time() {
+        // Hashing the expected number of hashes succeeds with probability 1 - 1/e
+        let p = probability_within(20, 2f64.powi(20), 1.0);
+        assert!((p - (1.0 - (-1.0f64).exp())).abs().lt(&1e-6));
+    }
+
+    #[test]
+    fn large_difficulty() {
+        let eta = eta_secs(60, 1e6).unwrap();
+        assert!(eta.is_finite() && eta.gt(&1e12));
+        let p = probability_within(60, 1e6, 60.0);
+        assert!(p.gt(&0.0) && p.lt(&1e-9));
+        assert_eq!(reachable_difficulty(1e6, 60.0), 25);
+    }
+}
@@ -0,0 +1,476 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::{Buf, Bytes, BytesMut};
+use colored::*;
+use h2::client::SendRequest;
+use ore_api::state::{Bus, Proof};
+use ore_utils::AccountDeserialize;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName},
+    TlsConnector,
+};
+use url::Url;
+
+use crate::{
+    proof_updates::{ProofSender, RESUBSCRIBE_DELAY},
+    utils::{bus_pubkeys, proof_pubkey},
+    Miner,
+};
+
+// Interval between pings keeping the stream open through load balancers
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+
+// Time without any message, pings included, before the stream is considered dead
+const STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Yellowstone Geyser gRPC endpoint, set with --geyser-url and --geyser-token
+#[derive(Clone, Debug)]
+pub struct GeyserConfig {
+    pub url: String,
+    pub token: Option<String>,
+}
+
+// A message of the subscription stream this miner reads
+#[derive(Debug, PartialEq)]
+enum Update {
+    Account { pubkey: Pubkey, data: Vec<u8> },
+    Ping,
+    Other,
+}
+
+// Streams the proof of the authority and the busses, reconnecting whenever the stream
+// drops. The websocket and rpc cover for it while it is down.
+pub async fn subscribe(config: GeyserConfig, miner: Miner, authority: Pubkey, sender: ProofSender) {
+    let proof_address = proof_pubkey(authority);
+    let mut warned = false;
+    loop {
+        let result = stream(&config, &miner, proof_address, &sender, &mut warned).await;
+        if sender.is_closed() {
+            return;
+        }
+        if !warned {
+            println!(
+                "{} Geyser stream dropped: {}. Falling back to the websocket and rpc until it reconnects.",
+                "WARNING".bold().yellow(),
+                result.err().unwrap_or_default()
+            );
+            warned = true;
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+// Reads one subscription until it fails
+async fn stream(
+    config: &GeyserConfig,
+    miner: &Miner,
+    proof_address: Pubkey,
+    sender: &ProofSender,
+    warned: &mut bool,
+) -> Result<(), String> {
+    let busses = bus_pubkeys();
+    let request = subscribe_request(
+        &[("proof", &[proof_address]), ("busses", &busses)],
+        commitment_level(miner.send.commitment),
+        None,
+    );
+    let mut client = connect(&config.url).await?;
+    let mut builder = http::Request::post(format!(
+        "{}/geyser.Geyser/Subscribe",
+        config.url.trim_end_matches('/')
+    ))
+    .header("content-type", "application/grpc")
+    .header("te", "trailers");
+    if let Some(token) = &config.token {
+        builder = builder.header("x-token", token);
+    }
+    let request_head = builder.body(()).map_err(|err| err.to_string())?;
+    client = client.ready().await.map_err(|err| err.to_string())?;
+    let (response, mut requests) = client
+        .send_request(request_head, false)
+        .map_err(|err| err.to_string())?;
+    requests
+        .send_data(frame(&request), false)
+        .map_err(|err| err.to_string())?;
+    let response = response.await.map_err(|err| err.to_string())?;
+    if response.status().ne(&http::StatusCode::OK) {
+        return Err(format!("status {}", response.status()));
+    }
+    if let Some(status) = response.headers().get("grpc-status") {
+        if status.ne("0") {
+            return Err(format!("grpc status {:?}", status));
+        }
+    }
+    let mut body = response.into_body();
+
+    // Updates only carry changes, so start from the current accounts
+    sender.connected();
+    *warned = false;
+    if let Ok(data) = miner.rpc_client.get_account_data(&proof_address).await {
+        if let Ok(proof) = Proof::try_from_bytes(&data) {
+            sender.send(*proof);
+        }
+    }
+    let streaming_busses = miner.refresh_busses().await.is_ok();
+    if streaming_busses {
+        miner.bus_cache.streaming(true);
+    }
+
+    let result = async {
+        let mut buffer = BytesMut::new();
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        let mut ping_id = 0;
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    ping_id += 1;
+                    let request = subscribe_request(
+                        &[("proof", &[proof_address]), ("busses", &busses)],
+                        commitment_level(miner.send.commitment),
+                        Some(ping_id),
+                    );
+                    requests
+                        .send_data(frame(&request), false)
+                        .map_err(|err| err.to_string())?;
+                }
+                chunk = tokio::time::timeout(STREAM_TIMEOUT, body.data()) => {
+                    let chunk = match chunk {
+                        Err(_) => return Err("timed out".to_string()),
+                        Ok(None) => return Err("closed by the server".to_string()),
+                        Ok(Some(chunk)) => chunk.map_err(|err| err.to_string())?,
+                    };
+                    body.flow_control()
+                        .release_capacity(chunk.len())
+                        .map_err(|err| err.to_string())?;
+                    buffer.extend_from_slice(&chunk);
+                    while let Some(message) = next_message(&mut buffer)? {
+                        let Some(Update::Account { pubkey, data }) = parse_update(&message) else {
+                            continue;
+                        };
+                        if pubkey.eq(&proof_address) {
+                            if let Ok(proof) = Proof::try_from_bytes(&data) {
+                                if !sender.send(*proof) {
+                                    return Ok(());
+                                }
+                            }
+                        } else if let Some(index) = busses.iter().position(|bus| bus.eq(&pubkey)) {
+                            if let Ok(bus) = Bus::try_from_bytes(&data) {
+                                miner.bus_cache.update(index, *bus);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .await;
+    if streaming_busses {
+        miner.bus_cache.streaming(false);
+    }
+    sender.disconnected();
+    result
+}
+
+// Opens an HTTP/2 connection to the endpoint, over TLS for https urls
+async fn connect(url: &str) -> Result<SendRequest<Bytes>, String> {
+    let url = Url::parse(url).map_err(|err| err.to_string())?;
+    let host = url.host_str().ok_or("url has no host")?.to_string();
+    let tls = match url.scheme() {
+        "https" => true,
+        "http" => false,
+        scheme => return Err(format!("unsupported scheme {}", scheme)),
+    };
+    let port = url.port_or_known_default().ok_or("url has no port")?;
+    let tcp = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|err| err.to_string())?;
+    tcp.set_nodelay(true).ok();
+    if !tls {
+        return handshake(tcp).await;
+    }
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    let mut tls_config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls_config.alpn_protocols = vec![b"h2".to_vec()];
+    let server_name = ServerName::try_from(host.as_str()).map_err(|err| err.to_string())?;
+    let stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, tcp)
+        .await
+        .map_err(|err| err.to_string())?;
+    handshake(stream).await
+}
+
+// Runs the connection in the background, returning the handle requests are sent through
+async fn handshake<T>(io: T) -> Result<SendRequest<Bytes>, String>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (client, connection) = h2::client::handshake(io)
+        .await
+        .map_err(|err| err.to_string())?;
+    tokio::spawn(async move {
+        connection.await.ok();
+    });
+    Ok(client)
+}
+
+// Geyser's CommitmentLevel for the configured commitment
+fn commitment_level(commitment: CommitmentConfig) -> u64 {
+    if commitment.is_finalized() {
+        2
+    } else if commitment.is_confirmed() {
+        1
+    } else {
+        0
+    }
+}
+
+// A gRPC message: uncompressed, with its length
+fn frame(message: &[u8]) -> Bytes {
+    let mut framed = Vec::with_capacity(message.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    framed.into()
+}
+
+// Takes the next complete gRPC message off the buffer
+fn next_message(buffer: &mut BytesMut) -> Result<Option<Bytes>, String> {
+    if buffer.len().lt(&5) {
+        return Ok(None);
+    }
+    if buffer[0].ne(&0) {
+        return Err("compressed messages aren't supported".to_string());
+    }
+    let len = u32::from_be_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as usize;
+    if buffer.len().lt(&(len + 5)) {
+        return Ok(None);
+    }
+    buffer.advance(5);
+    Ok(Some(buffer.split_to(len).freeze()))
+}
+
+// SubscribeRequest for the named account filters, carrying a ping if given
+fn subscribe_request(filters: &[(&str, &[Pubkey])], commitment: u64, ping: Option<i32>) -> Vec<u8> {
+    let mut request = vec![];
+    for (name, accounts) in filters {
+        // SubscribeRequestFilterAccounts { account = 2 }
+        let mut filter = vec![];
+        for account in accounts.iter() {
+            put_bytes(&mut filter, 2, account.to_string().as_bytes());
+        }
+        // map<string, SubscribeRequestFilterAccounts> accounts = 1
+        let mut entry = vec![];
+        put_bytes(&mut entry, 1, name.as_bytes());
+        put_bytes(&mut entry, 2, &filter);
+        put_bytes(&mut request, 1, &entry);
+    }
+    // CommitmentLevel commitment = 6
+    put_varint_field(&mut request, 6, commitment);
+    // SubscribeRequestPing { int32 id = 1 } ping = 9
+    if let Some(id) = ping {
+        let mut ping = vec![];
+        put_varint_field(&mut ping, 1, id as u64);
+        put_bytes(&mut request, 9, &ping);
+    }
+    request
+}
+
+// Decodes a SubscribeUpdate, or None if it is malformed
+fn parse_update(message: &[u8]) -> Option<Update> {
+    for (field, value) in fields(message)? {
+        match (field, value) {
+            // SubscribeUpdateAccount account = 2 { SubscribeUpdateAccountInfo account = 1 }
+            (2, Value::Bytes(account)) => {
+                let (_, info) = fields(account)?
+                    .into_iter()
+                    .find(|(field, _)| field.eq(&1))?;
+                let Value::Bytes(info) = info else {
+                    return None;
+                };
+                let mut pubkey = None;
+                let mut data = vec![];
+                for (field, value) in fields(info)? {
+                    match (field, value) {
+                        (1, Value::Bytes(bytes)) => pubkey = Pubkey::try_from(bytes).ok(),
+                        (6, Value::Bytes(bytes)) => data = bytes.to_vec(),
+                        _ => {}
+                    }
+                }
+                return Some(Update::Account {
+                    pubkey: pubkey?,
+                    data,
+                });
+            }
+            // SubscribeUpdatePing ping = 6
+            (6, _) => return Some(Update::Ping),
+            _ => {}
+        }
+    }
+    Some(Update::Other)
+}
+
+// A protobuf field value, varints and length delimited only
+#[derive(Clone, Copy)]
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+// Every field of a protobuf message, or None if it is malformed
+fn fields(mut message: &[u8]) -> Option<Vec<(u64, Value)>> {
+    let mut fields = vec![];
+    while !message.is_empty() {
+        let key = read_varint(&mut message)?;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(&mut message)?),
+            1 => Value::Bytes(take(&mut message, 8)?),
+            2 => {
+                let len = read_varint(&mut message)? as usize;
+                Value::Bytes(take(&mut message, len)?)
+            }
+            5 => Value::Bytes(take(&mut message, 4)?),
+            _ => return None,
+        };
+        fields.push((key >> 3, value));
+    }
+    Some(fields)
+}
+
+fn take<'a>(message: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if message.len().lt(&len) {
+        return None;
+    }
+    let (head, tail) = message.split_at(len);
+    *message = tail;
+    Some(head)
+}
+
+fn read_varint(message: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, tail) = message.split_first()?;
+        *message = tail;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value.ge(&0x80) {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    put_varint(buf, field << 3);
+    put_varint(buf, value);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_varint(buf, (field << 3) | 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_subscribe_requests() {
+        let account = Pubkey::new_unique();
+        let request = subscribe_request(&[("proof", &[account])], 1, Some(3));
+        let fields = fields(&request).unwrap();
+        assert_eq!(fields.len(), 3);
+
+        let (1, Value::Bytes(entry)) = fields[0] else {
+            panic!("no accounts filter");
+        };
+        let entry = super::fields(entry).unwrap();
+        assert!(matches!(entry[0], (1, Value::Bytes(b"proof"))));
+        let (2, Value::Bytes(filter)) = entry[1] else {
+            panic!("no filter");
+        };
+        let filter = super::fields(filter).unwrap();
+        let (2, Value::Bytes(address)) = filter[0] else {
+            panic!("no account");
+        };
+        assert_eq!(address, account.to_string().as_bytes());
+
+        assert!(matches!(fields[1], (6, Value::Varint(1))));
+        let (9, Value::Bytes(ping)) = fields[2] else {
+            panic!("no ping");
+        };
+        assert!(matches!(
+            super::fields(ping).unwrap()[0],
+            (1, Value::Varint(3))
+        ));
+    }
+
+    #[test]
+    fn decodes_account_updates() {
+        let pubkey = Pubkey::new_unique();
+        let mut info = vec![];
+        put_bytes(&mut info, 1, pubkey.as_ref());
+        put_varint_field(&mut info, 2, 1_000_000);
+        put_bytes(&mut info, 6, &[1, 2, 3]);
+        put_varint_field(&mut info, 7, 42);
+        let mut account = vec![];
+        put_bytes(&mut account, 1, &info);
+        put_varint_field(&mut account, 2, 250_000_000);
+        let mut update = vec![];
+        put_bytes(&mut update, 1, b"busses");
+        put_bytes(&mut update, 2, &account);
+        assert_eq!(
+            parse_update(&update),
+            Some(Update::Account {
+                pubkey,
+                data: vec![1, 2, 3]
+            })
+        );
+
+        let mut ping = vec![];
+        put_bytes(&mut ping, 6, &[]);
+        assert_eq!(parse_update(&ping), Some(Update::Ping));
+        assert_eq!(parse_update(&[0x12, 0x05, 0x00]), None);
+    }
+
+    #[test]
+    fn splits_grpc_frames() {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&frame(b"first"));
+        buffer.extend_from_slice(&frame(b"second")[..4]);
+        assert_eq!(
+            next_message(&mut buffer).unwrap(),
+            Some(Bytes::from("first"))
+        );
+        assert_eq!(next_message(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(&frame(b"second")[4..]);
+        assert_eq!(
+            next_message(&mut buffer).unwrap(),
+            Some(Bytes::from("second"))
+        );
+        assert!(buffer.is_empty());
+    }
+}
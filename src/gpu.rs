@@ -0,0 +1,51 @@
+use drillx::{Hash, Solution};
+
+// Hashx index space per nonce, as laid out by the drillx kernel
+const INDEX_SPACE: usize = 65536;
+
+// Exported by drillx when built with its gpu feature (see README)
+extern "C" {
+    static BATCH_SIZE: u32;
+    fn hash(challenge: *const u8, nonce: *const u8, out: *mut u64);
+    fn solve_all_stages(hashes: *const u64, out: *mut u8, sols: *mut u32);
+}
+
+// Number of nonces dispatched to the device per kernel launch
+pub fn batch_size() -> u64 {
+    unsafe { BATCH_SIZE as u64 }
+}
+
+// Buffer the kernel writes the hashx outputs of a batch into
+pub fn batch_buffer() -> Vec<u64> {
+    vec![0; batch_size() as usize * INDEX_SPACE]
+}
+
+// Hashes a batch of nonces on the device and returns every valid (nonce, hash)
+pub fn hash_batch(buffer: &mut [u64], challenge: &[u8; 32], start_nonce: u64) -> Vec<(u64, Hash)> {
+    unsafe {
+        hash(
+            challenge.as_ptr(),
+            start_nonce.to_le_bytes().as_ptr(),
+            buffer.as_mut_ptr(),
+        );
+    }
+
+    // Solve the equix stages of each nonce and score it on the host
+    let mut hashes = vec![];
+    for i in 0..batch_size() {
+        let mut digest = [0u8; 16];
+        let mut sols = 0u32;
+        unsafe {
+            solve_all_stages(
+                buffer.as_ptr().add(i as usize * INDEX_SPACE),
+                digest.as_mut_ptr(),
+                &mut sols,
+            );
+        }
+        if sols.gt(&0) {
+            let nonce = start_nonce.wrapping_add(i);
+            hashes.push((nonce, Solution::new(digest, nonce.to_le_bytes()).to_hash()));
+        }
+    }
+    hashes
+}
@@ -1,10 +1,21 @@
+use colored::*;
 use ore_api::consts::TREASURY_ADDRESS;
 use solana_sdk::{signature::Signer, transaction::Transaction};
 
-use crate::Miner;
+use crate::{cluster::program_id, Miner};
 
 impl Miner {
     pub async fn initialize(&self) {
+        // The instruction carries bumps for the ORE program's own addresses
+        if program_id().ne(&ore_api::ID) {
+            println!(
+                "{} Initialize only supports the ORE program {}",
+                "ERROR".bold().red(),
+                ore_api::ID
+            );
+            return;
+        }
+
         // Return early if program is already initialized
         if self.rpc_client.get_account(&TREASURY_ADDRESS).await.is_ok() {
             return;
@@ -0,0 +1,185 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use colored::*;
+use reqwest::Client;
+use serde_json::{json, Value};
+use solana_program::{hash::Hash, native_token::sol_to_lamports, pubkey::Pubkey};
+use solana_sdk::{signature::Signer, system_instruction, transaction::Transaction};
+
+use crate::Miner;
+
+// Accounts the block engine accepts tips at
+const TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+// Percentiles of recently landed tips published by the tip floor api
+const TIP_FLOOR_PERCENTILES: [(u8, &str); 5] = [
+    (25, "landed_tips_25th_percentile"),
+    (50, "landed_tips_50th_percentile"),
+    (75, "landed_tips_75th_percentile"),
+    (95, "landed_tips_95th_percentile"),
+    (99, "landed_tips_99th_percentile"),
+];
+
+// How long a fetched tip floor is reused
+const TIP_FLOOR_MAX_AGE: Duration = Duration::from_secs(10);
+
+// Index of the tip account used by the next bundle
+static NEXT_TIP_ACCOUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Last tip floor fetched and when
+static TIP_FLOOR: Mutex<Option<(Value, Instant)>> = Mutex::new(None);
+
+// Set once a failed tip floor fetch has been reported
+static TIP_FLOOR_WARNED: AtomicBool = AtomicBool::new(false);
+
+// How the tip of each bundle is chosen
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum JitoTipStrategy {
+    // Always --jito-tip
+    Fixed,
+    // The --jito-tip-percentile of recently landed tips, falling back to --jito-tip
+    Percentile,
+}
+
+// Where and how bundles are sent
+#[derive(Clone, Debug)]
+pub struct JitoConfig {
+    pub url: String,
+    pub tip: u64,
+    pub rpc_fallback: bool,
+    pub tip_strategy: JitoTipStrategy,
+    pub tip_percentile: u8,
+    pub tip_floor_url: String,
+    pub max_tip: Option<u64>,
+}
+
+// Rotates through the tip accounts so bundles don't all write-lock the same one
+pub fn next_tip_account() -> Pubkey {
+    let index = NEXT_TIP_ACCOUNT.fetch_add(1, Ordering::Relaxed) % TIP_ACCOUNTS.len();
+    Pubkey::from_str(TIP_ACCOUNTS[index]).unwrap()
+}
+
+// Tip at the percentile of a tip floor response, interpolating between the published
+// percentiles
+fn tip_at_percentile(floor: &Value, percentile: u8) -> Option<u64> {
+    let floor = floor.get(0)?;
+    let points: Vec<(f64, f64)> = TIP_FLOOR_PERCENTILES
+        .iter()
+        .filter_map(|(p, key)| Some((*p as f64, floor[*key].as_f64()?)))
+        .collect();
+    let percentile = percentile as f64;
+    let (first, last) = (points.first()?, points.last()?);
+    let sol = if percentile.le(&first.0) {
+        first.1
+    } else if percentile.ge(&last.0) {
+        last.1
+    } else {
+        let upper = points.iter().position(|(p, _)| p.ge(&percentile))?;
+        let ((p0, t0), (p1, t1)) = (points[upper - 1], points[upper]);
+        t0 + (t1 - t0) * (percentile - p0) / (p1 - p0)
+    };
+    Some(sol_to_lamports(sol))
+}
+
+impl Miner {
+    // Tip for the next bundle, or 0 without Jito
+    pub async fn jito_tip(&self) -> u64 {
+        let Some(jito) = &self.jito else {
+            return 0;
+        };
+        let tip = match jito.tip_strategy {
+            JitoTipStrategy::Fixed => jito.tip,
+            JitoTipStrategy::Percentile => match self.tip_floor(&jito.tip_floor_url).await {
+                Ok(floor) => tip_at_percentile(&floor, jito.tip_percentile).unwrap_or(jito.tip),
+                Err(err) => {
+                    if !TIP_FLOOR_WARNED.swap(true, Ordering::Relaxed) {
+                        println!(
+                            "  {} Failed to fetch the Jito tip floor ({}). Tipping {} lamports instead.",
+                            "WARNING".bold().yellow(),
+                            err,
+                            jito.tip
+                        );
+                    }
+                    jito.tip
+                }
+            },
+        };
+        jito.max_tip.map_or(tip, |max_tip| tip.min(max_tip))
+    }
+
+    // Recently landed tips from the tip floor api, reused for a few seconds
+    async fn tip_floor(&self, url: &str) -> Result<Value, String> {
+        if let Some((floor, fetched_at)) = TIP_FLOOR.lock().unwrap().clone() {
+            if fetched_at.elapsed().lt(&TIP_FLOOR_MAX_AGE) {
+                return Ok(floor);
+            }
+        }
+        let floor: Value = Client::new()
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?
+            .json()
+            .await
+            .map_err(|err| err.to_string())?;
+        *TIP_FLOOR.lock().unwrap() = Some((floor.clone(), Instant::now()));
+        Ok(floor)
+    }
+
+    // Signed transfer of the tip from the fee payer
+    pub fn tip_tx(&self, tip: u64, hash: Hash) -> Transaction {
+        let fee_payer = self.fee_payer();
+        let ix = system_instruction::transfer(&fee_payer.pubkey(), &next_tip_account(), tip);
+        let mut tx = Transaction::new_with_payer(&[ix], Some(&fee_payer.pubkey()));
+        tx.sign(&[&fee_payer], hash);
+        tx
+    }
+
+    // Sends signed transactions to the block engine as one bundle and returns its id
+    pub async fn send_bundle(&self, url: &str, txs: &[&Transaction]) -> Result<String, String> {
+        let mut encoded = vec![];
+        for tx in txs {
+            let bytes = bincode::serialize(tx).map_err(|err| err.to_string())?;
+            encoded.push(bs58::encode(bytes).into_string());
+        }
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendBundle",
+            "params": [encoded]
+        });
+        let response: Value = Client::new()
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?
+            .json()
+            .await
+            .map_err(|err| err.to_string())?;
+        match response["result"].as_str() {
+            Some(id) => Ok(id.to_string()),
+            None => Err(response["error"]["message"]
+                .as_str()
+                .map(|msg| msg.to_string())
+                .unwrap_or(format!("Unexpected response: {}", response))),
+        }
+    }
+}
@@ -1,40 +1,86 @@
+mod adaptive;
 mod args;
 mod balance;
 mod benchmark;
+mod buffer;
 mod busses;
+mod checkpoint;
 mod claim;
 mod close;
+mod cluster;
+mod cluster_clock;
 mod config;
+mod coordinator;
+mod core_classes;
 mod cu_limits;
 mod dynamic_fee;
+mod eta;
+#[cfg(feature = "geyser")]
+mod geyser;
+#[cfg(feature = "gpu")]
+mod gpu;
 #[cfg(feature = "admin")]
 mod initialize;
+mod jito;
 mod mine;
+mod nonce;
+mod numa;
 mod open;
+mod pdas;
+mod pool;
 mod proof;
+mod proof_updates;
+mod protocol;
+mod rate_limiter;
 mod rewards;
+mod rpc_bench;
+mod rpc_pool;
+mod schedule;
 mod send_and_confirm;
+mod signals;
 mod stake;
+mod stats;
+mod thermal;
+mod tpu;
+mod tx_log;
 mod upgrade;
 mod utils;
+mod worker;
 
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use args::*;
+use busses::BusCache;
 use clap::{command, Parser, Subcommand};
+use cluster::Cluster;
+use cluster_clock::ClusterClock;
+use colored::*;
+use config::ConfigCache;
+use dynamic_fee::{FeeConfig, FeeOverride, FeeProviderKind, FeeScaling, FeeStrategy};
+use jito::{JitoConfig, JitoTipStrategy};
+use rpc_pool::{Broadcast, RpcPool};
+use send_and_confirm::{ConfirmRpc, SendConfig};
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client::rpc_client::RpcClientConfig;
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
-    signature::{read_keypair_file, Keypair},
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
 };
 
+#[derive(Clone)]
 struct Miner {
     pub keypair_filepath: Option<String>,
-    pub priority_fee: Option<u64>,
-    pub dynamic_fee_url: Option<String>,
-    pub dynamic_fee: bool,
+    pub fees: FeeConfig,
     pub rpc_client: Arc<RpcClient>,
+    pub rpc_pool: RpcPool,
     pub fee_payer_filepath: Option<String>,
+    pub authority: Option<Pubkey>,
+    pub jito: Option<JitoConfig>,
+    pub send: SendConfig,
+    pub bus_cache: BusCache,
+    pub config_cache: ConfigCache,
+    pub cluster_clock: ClusterClock,
 }
 
 #[derive(Subcommand, Debug)]
@@ -58,14 +104,23 @@ enum Commands {
     Config(ConfigArgs),
 
     #[command(about = "Start mining")]
-    Mine(MineArgs),
+    Mine(Box<MineArgs>),
 
-    #[command(about = "Fetch a proof account by address")]
+    #[command(about = "Create a durable nonce account for the signer")]
+    Nonce(NonceArgs),
+
+    #[command(about = "Print the program addresses derived for an authority")]
+    Pdas(PdasArgs),
+
+    #[command(about = "Fetch a proof account by address or authority")]
     Proof(ProofArgs),
 
     #[command(about = "Fetch the current reward rate for each difficulty level")]
     Rewards(RewardsArgs),
 
+    #[command(about = "Measure the latency and slot of each rpc endpoint")]
+    RpcBench(RpcBenchArgs),
+
     #[command(about = "Stake to earn a rewards multiplier")]
     Stake(StakeArgs),
 
@@ -83,10 +138,237 @@ struct Args {
     #[arg(
         long,
         value_name = "NETWORK_URL",
-        help = "Network address of your RPC provider",
+        help = "Network address of your RPC provider. Repeat or separate with commas to fail over to backup endpoints in order.",
+        value_delimiter = ',',
+        global = true
+    )]
+    rpc: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CLUSTER",
+        help = "Cluster to mine on. Picks its public rpc when no --rpc is given. Custom needs --rpc and --program-id.",
+        global = true
+    )]
+    cluster: Option<Cluster>,
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Id of the ORE program to mine with, for other deployments and forks",
+        global = true
+    )]
+    program_id: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "With more than one --rpc, measure each endpoint's latency and slot on this interval. Blockhashes and sends go to the fastest, and other reads are spread across the rest. 0 keeps the order given.",
+        default_value = "60",
+        global = true
+    )]
+    rpc_probe_interval: u64,
+
+    #[arg(
+        long,
+        value_name = "SLOTS",
+        help = "Slots an rpc may be behind the best one before reads skip it",
+        default_value = "10",
+        global = true
+    )]
+    max_slot_lag: u64,
+
+    #[arg(
+        long,
+        value_name = "RPS",
+        help = "Requests per second to send to --rpc at most, across endpoints. 0 for no limit.",
+        default_value = "0",
+        global = true
+    )]
+    rpc_rate_limit: f64,
+
+    #[arg(
+        long,
+        value_name = "NETWORK_URL",
+        help = "RPC to send transactions through. Accounts are still read from --rpc.",
+        global = true
+    )]
+    send_rpc: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "RPC",
+        help = "RPC to poll for transaction confirmations",
+        default_value = "read",
+        global = true
+    )]
+    confirm_rpc: ConfirmRpc,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Number of times to resend a transaction that hasn't landed",
+        default_value = "150",
+        global = true
+    )]
+    send_retries: usize,
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Delay before the first resend, doubling with each retry",
+        default_value = "0",
+        global = true
+    )]
+    send_retry_delay_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Longest delay between resends",
+        default_value = "5000",
+        global = true
+    )]
+    send_retry_max_delay_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "BOOL",
+        help = "Send transactions without simulating them first. Failures are reported once they land. Set it to false to simulate them.",
+        default_value = "true",
+        default_missing_value = "true",
+        num_args = 0..=1,
+        require_equals = true,
+        action = clap::ArgAction::Set,
+        global = true
+    )]
+    skip_preflight: bool,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "SOL kept in the fee payer. Mining stops submitting at or below it until topped up.",
+        default_value = "0.005",
+        global = true
+    )]
+    min_sol_balance: f64,
+
+    #[arg(
+        long,
+        help = "Build, sign and simulate transactions, printing their logs and fee, without sending them",
+        global = true
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Sign with a cached blockhash up to this old instead of fetching one per transaction. 0 always fetches.",
+        default_value = "5000",
+        global = true
+    )]
+    blockhash_max_age_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Reuse bus accounts read within this long instead of fetching them again",
+        default_value = "3000",
+        global = true
+    )]
+    bus_cache_ttl_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Reuse the config account read within this long instead of fetching it every round. It is always refetched once its epoch ends.",
+        default_value = "60",
+        global = true
+    )]
+    config_cache_ttl: u64,
+
+    #[arg(
+        long,
+        help = "Send transactions over QUIC straight to the current and upcoming leaders, falling back to the RPC. Confirmation still goes through the RPC.",
+        global = true
+    )]
+    tpu: bool,
+
+    #[arg(
+        long,
+        help = "Write the simulation logs of transactions failing preflight to --tx-log instead of printing them",
+        global = true
+    )]
+    quiet: bool,
+
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        help = "Commitment accounts are read and transactions are confirmed at: processed, confirmed or finalized",
+        default_value = "confirmed",
+        value_parser = CommitmentLevel::from_str,
+        global = true
+    )]
+    commitment: CommitmentLevel,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "Margin added to the simulated compute units of a transaction",
+        default_value = "20",
+        global = true
+    )]
+    cu_margin: u64,
+
+    #[arg(
+        long,
+        value_name = "WEBSOCKET_URL",
+        help = "Websocket to confirm transactions and watch proofs with. Defaults to the websocket of the first --rpc.",
+        global = true
+    )]
+    ws_url: Option<String>,
+
+    #[cfg(feature = "geyser")]
+    #[arg(
+        long,
+        value_name = "GEYSER_URL",
+        help = "Yellowstone Geyser gRPC endpoint to stream the proof and bus accounts from, ahead of the websocket",
+        global = true
+    )]
+    geyser_url: Option<String>,
+
+    #[cfg(feature = "geyser")]
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        help = "Access token sent to the --geyser-url",
+        global = true
+    )]
+    geyser_token: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "File to append a JSON line to for every transaction sent and its outcome",
+        global = true
+    )]
+    tx_log: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Durable nonce account to sign transactions with, so they don't expire before landing. Create one with the nonce command.",
+        global = true
+    )]
+    nonce_account: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Stop waiting for a transaction to confirm after this long. Mining moves on and the transaction is checked in the background.",
         global = true
     )]
-    rpc: Option<String>,
+    confirm_timeout: Option<u64>,
 
     #[clap(
         global = true,
@@ -108,16 +390,23 @@ struct Args {
     #[arg(
         long,
         value_name = "FEE_PAYER_FILEPATH",
-        help = "Filepath to keypair to use as transaction fee payer.",
+        help = "Filepath to keypair to use as transaction fee payer. It pays the fees and any account rent, so the keypair can hold no SOL.",
         global = true
     )]
     fee_payer: Option<String>,
 
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Proof authority to act for, when the keypair is only its delegated miner. Defaults to the keypair.",
+        global = true
+    )]
+    authority: Option<String>,
+
     #[arg(
         long,
         value_name = "MICROLAMPORTS",
-        help = "Price to pay for compute units. If dynamic fees are being used, this value will be the max.",
-        default_value = "500000",
+        help = "Price to pay for compute units [default: 500000]. If dynamic fees are being used, this value is the min rather than the max, and the max defaults to 500000 unless --priority-fee-cap is set.",
         global = true
     )]
     priority_fee: Option<u64>,
@@ -130,9 +419,168 @@ struct Args {
     )]
     dynamic_fee_url: Option<String>,
 
+    #[arg(
+        long,
+        value_name = "PROVIDER",
+        help = "Priority fee API of the dynamic fee url. Detected from the url if not given.",
+        global = true
+    )]
+    dynamic_fee_provider: Option<FeeProviderKind>,
+
     #[arg(long, help = "Use dynamic priority fees", global = true)]
     dynamic_fee: bool,
 
+    #[arg(
+        long,
+        help = "Submit transactions to a Jito block engine as bundles with a tip",
+        global = true
+    )]
+    jito: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Jito block engine bundle endpoint",
+        default_value = "https://mainnet.block-engine.jito.wtf/api/v1/bundles",
+        global = true
+    )]
+    jito_url: String,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Tip paid by the fee payer for each bundle",
+        default_value = "10000",
+        global = true
+    )]
+    jito_tip: u64,
+
+    #[arg(
+        long,
+        help = "Also send the transaction to the RPC when submitting bundles",
+        global = true
+    )]
+    jito_rpc_fallback: bool,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How the tip of each bundle is chosen. percentile follows recently landed tips.",
+        default_value = "fixed",
+        global = true
+    )]
+    jito_tip_strategy: JitoTipStrategy,
+
+    #[arg(
+        long,
+        value_name = "PERCENTILE",
+        help = "Percentile of recently landed tips paid with --jito-tip-strategy percentile",
+        default_value = "50",
+        value_parser = clap::value_parser!(u8).range(1..=99),
+        global = true
+    )]
+    jito_tip_percentile: u8,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Jito tip floor endpoint read by --jito-tip-strategy percentile",
+        default_value = "https://bundles.jito.wtf/api/v1/bundles/tip_floor",
+        global = true
+    )]
+    jito_tip_floor_url: String,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Most a bundle tips, whatever the tip strategy",
+        global = true
+    )]
+    jito_max_tip: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How recent fees paid for the transaction's accounts are aggregated into the dynamic fee",
+        default_value = "median",
+        global = true
+    )]
+    fee_strategy: FeeStrategy,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Highest price to pay for compute units when dynamic or difficulty scaled fees are being used [default with dynamic fees: 500000, or --priority-fee if higher]",
+        global = true
+    )]
+    priority_fee_cap: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SLOTS",
+        help = "Resend with a higher priority fee if a transaction hasn't landed within this many slots",
+        global = true
+    )]
+    bump_after_slots: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        help = "Multiplier applied to the priority fee on each bump, up to --priority-fee-cap",
+        default_value = "1.5",
+        global = true
+    )]
+    bump_factor: f64,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Price added to --priority-fee for solutions over the min difficulty, scaled by --fee-scaling",
+        global = true
+    )]
+    fee_per_difficulty: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SCALING",
+        help = "How --fee-per-difficulty grows with each difficulty over the min. exponential doubles it, linear adds it.",
+        default_value = "exponential",
+        global = true
+    )]
+    fee_scaling: FeeScaling,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Price to pay for compute units in claim transactions, including claims made while mining [default: --priority-fee]",
+        global = true
+    )]
+    claim_priority_fee: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "Dynamic fee strategy of claim transactions [default: --fee-strategy]",
+        global = true
+    )]
+    claim_fee_strategy: Option<FeeStrategy>,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        help = "Price to pay for compute units in stake transactions [default: --priority-fee]",
+        global = true
+    )]
+    stake_priority_fee: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "Dynamic fee strategy of stake transactions [default: --fee-strategy]",
+        global = true
+    )]
+    stake_fee_strategy: Option<FeeStrategy>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -153,20 +601,120 @@ async fn main() {
         solana_cli_config::Config::default()
     };
 
+    // Select the cluster and program, before any address is derived
+    if let Some(program_id) = &args.program_id {
+        match Pubkey::from_str(program_id) {
+            Ok(program_id) => cluster::set_program_id(program_id),
+            Err(_) => {
+                eprintln!("error: Invalid program id `{}`", program_id);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let (Some(Cluster::Custom), true) = (args.cluster, args.program_id.is_none()) {
+        eprintln!("error: --cluster custom needs --program-id");
+        std::process::exit(1);
+    }
+    if let (Some(Cluster::Custom), true) = (args.cluster, args.rpc.is_empty()) {
+        eprintln!("error: --cluster custom needs --rpc");
+        std::process::exit(1);
+    }
+
     // Initialize miner.
-    let cluster = args.rpc.unwrap_or(cli_config.json_rpc_url);
+    let urls = if !args.rpc.is_empty() {
+        args.rpc
+    } else if let Some(url) = args.cluster.and_then(|cluster| cluster.default_rpc()) {
+        vec![url.to_string()]
+    } else {
+        vec![cli_config.json_rpc_url]
+    };
     let default_keypair = args.keypair.unwrap_or(cli_config.keypair_path.clone());
     let fee_payer_filepath = args.fee_payer.unwrap_or(default_keypair.clone());
-    let rpc_client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
+    let authority = args
+        .authority
+        .map(|a| Pubkey::from_str(&a).expect("Failed to parse authority address"));
+    let commitment = CommitmentConfig {
+        commitment: args.commitment,
+    };
+    let ws_url = args
+        .ws_url
+        .unwrap_or(solana_cli_config::Config::compute_websocket_url(&urls[0]));
+    let broadcast_urls: Vec<String> = args.send_rpc.iter().chain(urls.iter()).cloned().collect();
 
-    let miner = Arc::new(Miner::new(
-        Arc::new(rpc_client),
-        args.priority_fee,
-        Some(default_keypair),
-        args.dynamic_fee_url,
-        args.dynamic_fee,
-        Some(fee_payer_filepath),
-    ));
+    let rpc_pool = RpcPool::new(urls).with_rate_limit(args.rpc_rate_limit);
+    rpc_pool.set_max_slot_lag(args.max_slot_lag);
+    if rpc_pool.len().gt(&1) && args.rpc_probe_interval.gt(&0) {
+        rpc_pool.spawn_probes(Duration::from_secs(args.rpc_probe_interval));
+    }
+    let miner = Arc::new(
+        Miner::new(
+            rpc_pool,
+            Some(default_keypair),
+            Some(fee_payer_filepath),
+            authority,
+            FeeConfig {
+                priority_fee: args.priority_fee,
+                dynamic_fee: args.dynamic_fee,
+                dynamic_fee_url: args.dynamic_fee_url,
+                dynamic_fee_provider: args.dynamic_fee_provider,
+                fee_strategy: args.fee_strategy,
+                priority_fee_cap: args.priority_fee_cap,
+                bump_after_slots: args.bump_after_slots,
+                bump_factor: args.bump_factor,
+                fee_per_difficulty: args.fee_per_difficulty,
+                fee_scaling: args.fee_scaling,
+                claim: FeeOverride {
+                    priority_fee: args.claim_priority_fee,
+                    fee_strategy: args.claim_fee_strategy,
+                },
+                stake: FeeOverride {
+                    priority_fee: args.stake_priority_fee,
+                    fee_strategy: args.stake_fee_strategy,
+                },
+            },
+            args.jito.then_some(JitoConfig {
+                url: args.jito_url,
+                tip: args.jito_tip,
+                rpc_fallback: args.jito_rpc_fallback,
+                tip_strategy: args.jito_tip_strategy,
+                tip_percentile: args.jito_tip_percentile,
+                tip_floor_url: args.jito_tip_floor_url,
+                max_tip: args.jito_max_tip,
+            }),
+            SendConfig {
+                send_rpc: args
+                    .send_rpc
+                    .map(|url| Arc::new(RpcClient::new_with_commitment(url, commitment))),
+                broadcast: Some(Broadcast::new(broadcast_urls, commitment))
+                    .filter(|b| b.len().gt(&1)),
+                confirm_rpc: args.confirm_rpc,
+                retries: args.send_retries,
+                retry_delay: Duration::from_millis(args.send_retry_delay_ms),
+                max_retry_delay: Duration::from_millis(args.send_retry_max_delay_ms),
+                skip_preflight: args.skip_preflight,
+                cu_margin: args.cu_margin,
+                ws_url: Some(ws_url).filter(|url| !url.is_empty()),
+                #[cfg(feature = "geyser")]
+                geyser: args.geyser_url.map(|url| geyser::GeyserConfig {
+                    url,
+                    token: args.geyser_token,
+                }),
+                tx_log: args.tx_log,
+                nonce_account: args
+                    .nonce_account
+                    .map(|a| Pubkey::from_str(&a).expect("Failed to parse nonce account address")),
+                confirm_timeout: args.confirm_timeout.map(Duration::from_secs),
+                min_sol_balance: args.min_sol_balance,
+                dry_run: args.dry_run,
+                blockhash_max_age: Duration::from_millis(args.blockhash_max_age_ms),
+                tpu: args.tpu,
+                quiet: args.quiet,
+                commitment,
+            },
+        )
+        .with_bus_cache_ttl(Duration::from_millis(args.bus_cache_ttl_ms))
+        .with_config_cache_ttl(Duration::from_secs(args.config_cache_ttl)),
+    );
 
     // Execute user command.
     match args.command {
@@ -176,8 +724,8 @@ async fn main() {
         Commands::Benchmark(args) => {
             miner.benchmark(args).await;
         }
-        Commands::Busses(_) => {
-            miner.busses().await;
+        Commands::Busses(args) => {
+            miner.busses(args).await;
         }
         Commands::Claim(args) => {
             miner.claim(args).await;
@@ -189,7 +737,13 @@ async fn main() {
             miner.config().await;
         }
         Commands::Mine(args) => {
-            miner.mine(args).await;
+            miner.mine(*args).await;
+        }
+        Commands::Nonce(_) => {
+            miner.nonce().await;
+        }
+        Commands::Pdas(args) => {
+            miner.pdas(args);
         }
         Commands::Proof(args) => {
             miner.proof(args).await;
@@ -197,6 +751,9 @@ async fn main() {
         Commands::Rewards(_) => {
             miner.rewards().await;
         }
+        Commands::RpcBench(args) => {
+            miner.rpc_bench(args).await;
+        }
         Commands::Stake(args) => {
             miner.stake(args).await;
         }
@@ -212,35 +769,88 @@ async fn main() {
 
 impl Miner {
     pub fn new(
-        rpc_client: Arc<RpcClient>,
-        priority_fee: Option<u64>,
+        rpc_pool: RpcPool,
         keypair_filepath: Option<String>,
-        dynamic_fee_url: Option<String>,
-        dynamic_fee: bool,
         fee_payer_filepath: Option<String>,
+        authority: Option<Pubkey>,
+        fees: FeeConfig,
+        jito: Option<JitoConfig>,
+        send: SendConfig,
     ) -> Self {
+        let rpc_client = RpcClient::new_sender(
+            rpc_pool.clone(),
+            RpcClientConfig::with_commitment(send.commitment),
+        );
         Self {
-            rpc_client,
+            rpc_client: Arc::new(rpc_client),
+            rpc_pool,
             keypair_filepath,
-            priority_fee,
-            dynamic_fee_url,
-            dynamic_fee,
+            fees,
             fee_payer_filepath,
+            authority,
+            jito,
+            send,
+            bus_cache: BusCache::new(Duration::ZERO),
+            config_cache: ConfigCache::new(Duration::ZERO),
+            cluster_clock: ClusterClock::default(),
+        }
+    }
+
+    // This miner, reusing bus accounts for the given time
+    pub fn with_bus_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.bus_cache = BusCache::new(ttl);
+        self
+    }
+
+    // This miner, reusing the config account for the given time
+    pub fn with_config_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.config_cache = ConfigCache::new(ttl);
+        self
+    }
+
+    // Copy of this miner signing with another keypair, as its own authority
+    pub fn with_keypair(&self, keypair_filepath: String) -> Self {
+        let mut miner = self.clone();
+        miner.authority = None;
+        if miner.fee_payer_filepath.eq(&miner.keypair_filepath) {
+            miner.fee_payer_filepath = Some(keypair_filepath.clone());
         }
+        miner.keypair_filepath = Some(keypair_filepath);
+        miner
     }
 
     pub fn signer(&self) -> Keypair {
         match self.keypair_filepath.clone() {
             Some(filepath) => read_keypair_file(filepath.clone())
-                .expect(format!("No keypair found at {}", filepath).as_str()),
+                .unwrap_or_else(|_| panic!("No keypair found at {}", filepath)),
             None => panic!("No keypair provided"),
         }
     }
 
+    // Authority of the proof account, which may have delegated mining to the signer
+    pub fn authority(&self) -> Pubkey {
+        self.authority.unwrap_or_else(|| self.signer().pubkey())
+    }
+
+    // Returns false, with an error, if the signer cannot act as the authority for this instruction
+    pub fn require_authority(&self, action: &str) -> bool {
+        let authority = self.authority();
+        if authority.eq(&self.signer().pubkey()) {
+            return true;
+        }
+        println!(
+            "{} The program requires the signature of authority {} to {}. Run this command with --keypair set to the authority's keypair.",
+            "ERROR".bold().red(),
+            authority,
+            action
+        );
+        false
+    }
+
     pub fn fee_payer(&self) -> Keypair {
         match self.fee_payer_filepath.clone() {
             Some(filepath) => read_keypair_file(filepath.clone())
-                .expect(format!("No fee payer keypair found at {}", filepath).as_str()),
+                .unwrap_or_else(|_| panic!("No fee payer keypair found at {}", filepath)),
             None => panic!("No fee payer keypair provided"),
         }
     }
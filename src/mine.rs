@@ -1,177 +1,1831 @@
-use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
+use chrono::Local;
 use colored::*;
-use drillx::{
-    equix::{self},
-    Hash, Solution,
-};
+use core_affinity::CoreId;
+use drillx::{equix, Solution};
 use ore_api::{
-    consts::{BUS_ADDRESSES, BUS_COUNT, EPOCH_DURATION},
+    consts::{BUS_COUNT, EPOCH_DURATION},
+    error::OreError,
     state::{Bus, Config, Proof},
 };
 use ore_utils::AccountDeserialize;
 use rand::Rng;
-use solana_program::pubkey::Pubkey;
-use solana_rpc_client::spinner;
-use solana_sdk::signer::Signer;
+use serde_json::json;
+use solana_client::client_error::ClientErrorKind;
+use solana_program::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+};
+use solana_rpc_client::{nonblocking::rpc_client::RpcClient, spinner};
+use solana_sdk::signature::{read_keypair_file, Signature, Signer};
+use tokio::{sync::Notify, task::JoinHandle};
 
 use crate::{
+    adaptive::{AdaptiveDifficulty, RoundOutcome},
     args::MineArgs,
-    send_and_confirm::ComputeBudget,
+    buffer::BufferTuner,
+    checkpoint::{default_checkpoint_path, Checkpoint},
+    cluster::{for_program, program_id},
+    coordinator::Coordinator,
+    cu_limits::{CU_LIMIT_ATA, CU_LIMIT_CLAIM},
+    eta::{eta_secs, format_eta, probability_within, reachable_difficulty},
+    pool::{select_cores, CoreStats, Job, WorkerConfig, WorkerPool, WorkerResult, HISTOGRAM_SIZE},
+    proof_updates::ProofUpdates,
+    rpc_pool::RpcPool,
+    schedule,
+    send_and_confirm::{challenge_moved, ComputeBudget, SubmitError, SubmitRound, MAX_CU_LIMIT},
+    signals::{Signals, Tunables},
+    stats::{read_tx_counters, RoundRecord, SessionStats},
+    thermal::{cpu_temperature, Throttle},
     utils::{
-        amount_u64_to_string, get_clock, get_config, get_updated_proof_with_authority, proof_pubkey,
+        amount_f64_to_u64, amount_u64_to_string, append_line, bus_pubkeys, fetch_round_context,
+        fetch_round_state, get_config, get_proof, get_proofs, get_updated_proof_with_authority,
+        proof_pubkey, ProofWait, RoundState, UtilsError,
     },
-    Miner,
+    worker, Miner,
 };
 
+// Lowest difficulty shown in the per-round distribution
+const HISTOGRAM_MIN_DISPLAY: u32 = 8;
+
+// Buffer time used until the first submissions land
+const DEFAULT_BUFFER_TIME: u64 = 5;
+
+// Seconds a proof has to submit its next solution
+const EPOCH_WINDOW: u64 = 60;
+
+// Time the cores hash at startup to estimate the hashrate before the first round
+const WARM_UP_TIME: Duration = Duration::from_secs(1);
+
+// How often a paused miner checks whether its fee payer was topped up
+const BROKE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// Times an expired solution is resubmitted while its window is still open
+const MAX_RESUBMITS: usize = 2;
+
+// How long a timed out submission is watched for in the background, about as long as a
+// blockhash stays valid
+const LATE_CONFIRM_WINDOW: Duration = Duration::from_secs(90);
+
+// Exit codes of `mine --once`
+const EXIT_NO_SOLUTION: i32 = 2;
+const EXIT_SUBMIT_FAILED: i32 = 3;
+const EXIT_RPC_UNREACHABLE: i32 = 4;
+const EXIT_FEE_LIMIT: i32 = 5;
+const EXIT_WRONG_CLUSTER: i32 = 6;
+
+// Time before starting a round over after its reads failed
+const READ_ERROR_DELAY: Duration = Duration::from_secs(5);
+
+// How often the spinner message is refreshed while hashing
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// How often the proof is polled for a new challenge while hashing
+const CHALLENGE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+// How often hashing progress is saved to the checkpoint file
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+// Number of consecutive slow rounds before a core is reported
+const SLOW_CORE_ROUNDS: u32 = 3;
+
+// Outcome of hashing one challenge
+pub struct HashResult {
+    pub solution: Solution,
+    pub cores: Vec<CoreStats>,
+    pub hashes: u64,
+    pub elapsed: f64,
+    pub stalled: bool,
+}
+
+// A submitted solution whose reward is known once the proof updates
+struct SubmittedRound {
+    balance: u64,
+    record: RoundRecord,
+}
+
+// Mining state of a single keypair
+struct Wallet {
+    miner: Miner,
+    pubkey: Pubkey,
+    label: String,
+    checkpoint_path: Option<String>,
+    last_hash_at: i64,
+    last_round: Option<SubmittedRound>,
+    start_balance: Option<u64>,
+    balance: u64,
+    claimed: u64,
+    retry_floor: Option<([u8; 32], u32)>,
+    proof_updates: Option<ProofUpdates>,
+}
+
+// What the proof wait needs of a wallet, copied so the wait can run alongside a submission
+#[derive(Clone)]
+struct ProofTarget {
+    pubkey: Pubkey,
+    last_hash_at: i64,
+    proof_updates: Option<ProofUpdates>,
+}
+
+// Wallet to mine next, its round state, and whether the config came from the cache
+type NextRound = Result<(usize, RoundState, bool), UtilsError>;
+
+fn proof_targets(wallets: &[Wallet]) -> Vec<ProofTarget> {
+    wallets
+        .iter()
+        .map(|wallet| ProofTarget {
+            pubkey: wallet.pubkey,
+            last_hash_at: wallet.last_hash_at,
+            proof_updates: wallet.proof_updates.clone(),
+        })
+        .collect()
+}
+
 impl Miner {
-    pub async fn mine(&self, args: MineArgs) {
-        // Open account, if needed.
-        let signer = self.signer();
-        self.open().await;
+    pub async fn mine(&self, mut args: MineArgs) {
+        // Check cores (a coordinator leaves hashing to its workers)
+        let core_ids = if args.coordinator.is_some() {
+            vec![]
+        } else {
+            match select_cores(
+                args.cores,
+                args.include_cores.as_ref(),
+                args.exclude_cores.as_ref(),
+                args.physical_cores_only,
+                args.use_efficiency_cores,
+                args.calibrate_cores,
+            ) {
+                Ok(core_ids) => core_ids,
+                Err(err) => {
+                    println!("{} {}", "ERROR".bold().red(), err);
+                    return;
+                }
+            }
+        };
+
+        if args.physical_cores_only && !core_ids.is_empty() {
+            println!(
+                "Mining on {} cores, one per physical core ({} physical, {} logical available)",
+                core_ids.len(),
+                num_cpus::get_physical(),
+                num_cpus::get()
+            );
+        }
+
+        // Check machine index
+        if args.machine_id.ge(&args.machine_count) {
+            println!(
+                "{} --machine-id must be below --machine-count ({})",
+                "ERROR".bold().red(),
+                args.machine_count
+            );
+            return;
+        }
+        if args.machine_count.gt(&1) {
+            println!("Machine {} of {}", args.machine_id, args.machine_count);
+        }
 
         // Check num threads
         self.check_num_cores(args.cores);
 
+        // Check gpu
+        #[cfg(feature = "gpu")]
+        let gpu = args.gpu;
+        #[cfg(not(feature = "gpu"))]
+        let gpu = false;
+
+        // Start worker threads, shared by all wallets
+        let nice = if args.background { Some(19) } else { args.nice };
+        let throttle = Arc::new(Throttle::new(args.power_limit));
+        if let Some(max_temp) = args.max_temp {
+            if cpu_temperature().is_some() {
+                throttle.monitor(max_temp);
+            } else {
+                println!(
+                    "{} CPU temperature is not readable on this system. Ignoring --max-temp.",
+                    "WARNING".bold().yellow()
+                );
+            }
+        }
+        let config = WorkerConfig {
+            gpu,
+            nice,
+            numa_interleave: args.numa_interleave,
+            machine_id: args.machine_id,
+            machine_count: args.machine_count,
+        };
+        let mut hashrate_estimate = match args.worker {
+            None => warm_up_hashrate(&core_ids),
+            Some(_) => None,
+        };
+        let pool = WorkerPool::new(core_ids, config, throttle);
+
+        // Stop hashing on the first ctrl-c, exit immediately on the second
+        let interrupt = pool.interrupt.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if interrupt.swap(true, Ordering::Relaxed) {
+                    std::process::exit(130);
+                }
+                println!(
+                    "\n{} Shutting down after this round. Press Ctrl-C again to exit immediately.",
+                    "INFO".bold().blue()
+                );
+            }
+        });
+
+        // Hash for a remote coordinator, without a keypair or rpc
+        if let Some(addr) = &args.worker {
+            worker::run(addr, &args, &pool).await;
+            return;
+        }
+
+        // A single-shot run reports an unreachable rpc with its own exit code
+        if args.once {
+            if let Err(err) = self.rpc_client.get_slot().await {
+                println!("{}", json!({ "landed": false, "error": err.to_string() }));
+                std::process::exit(EXIT_RPC_UNREACHABLE);
+            }
+        }
+
+        // Make sure this is a cluster the program is on before opening anything
+        if let Err(err) = get_config(&self.rpc_client).await {
+            if !err.is_transient() {
+                self.report_wrong_cluster(&err, args.once).await;
+                return;
+            }
+        }
+
+        // Load wallets and open accounts, if needed.
+        let Some(mut wallets) = self.wallets(&args) else {
+            return;
+        };
+        if wallets.is_empty() {
+            println!("{} No keypairs found", "ERROR".bold().red());
+            return;
+        }
+        for wallet in wallets.iter() {
+            if !wallet.miner.open().await {
+                return;
+            }
+        }
+
+        // Listen for remote workers
+        let coordinator = match &args.coordinator {
+            Some(addr) => match Coordinator::bind(addr).await {
+                Ok(coordinator) => Some(coordinator),
+                Err(err) => {
+                    println!(
+                        "{} Failed to listen on {}: {}",
+                        "ERROR".bold().red(),
+                        addr,
+                        err
+                    );
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        // Restore adaptive difficulty from the stats file
+        let mut adaptive = AdaptiveDifficulty::new(
+            args.min_difficulty.unwrap_or(0),
+            args.adaptive_window,
+            args.reward_fee_multiple,
+            args.ore_price.unwrap_or(0.0),
+        );
+        if let Some(path) = &args.stats_file {
+            adaptive.load(path);
+        }
+
+        // Tune the buffer time from confirmation latency, unless fixed
+        let mut buffer = BufferTuner::new(
+            args.buffer_time,
+            DEFAULT_BUFFER_TIME,
+            args.min_buffer_time,
+            args.max_buffer_time,
+        );
+
+        // Dump live stats on SIGUSR1 and reload tunables on SIGHUP
+        self.rpc_pool.set_verbose(args.verbose);
+        let mut stats = SessionStats::new(self.rpc_pool.clone(), self.send.broadcast.clone());
+        if let Some(path) = &args.stats_file {
+            stats.tx = read_tx_counters(path);
+        }
+        stats.ore_price = args.ore_price;
+        let session = Arc::new(Mutex::new(stats.to_json(0)));
+        let reload = Arc::new(AtomicBool::new(false));
+        Signals {
+            current: pool.current.clone(),
+            session: session.clone(),
+            reload: reload.clone(),
+            gpu,
+            throttle: pool.throttle.clone(),
+        }
+        .listen();
+
+        // Warn before the first round if the floor is usually out of reach
+        let mut warned_eta = None;
+        if let (Some(hashrate), Some(min_difficulty), false) = (
+            hashrate_estimate,
+            args.min_difficulty,
+            args.adaptive_difficulty,
+        ) {
+            warn_eta(
+                min_difficulty,
+                hashrate,
+                buffer.buffer_time(),
+                &mut warned_eta,
+            );
+        }
+
         // Start mining loop
-        let mut last_hash_at = 0;
+        let mut warned_floor = None;
+        let deadline = args.duration.map(|d| Instant::now() + d);
+        let proof_wait = ProofWait {
+            timeout: Duration::from_secs(args.proof_wait_timeout),
+            max_retries: args.proof_wait_retries,
+        };
+        let mut submitted = 0;
+        let mut stalls = 0;
+        let mut last_hashed_at: Option<Instant> = None;
+        let mut prefetched: Option<JoinHandle<NextRound>> = None;
         loop {
-            // Fetch proof
-            let config = get_config(&self.rpc_client).await;
-            let proof =
-                get_updated_proof_with_authority(&self.rpc_client, signer.pubkey(), last_hash_at)
-                    .await;
-            last_hash_at = proof.last_hash_at;
+            *session.lock().unwrap() = stats.to_json(session_earned(&wallets));
+
+            // Apply reloaded tunables at the round boundary
+            if reload.swap(false, Ordering::Relaxed) {
+                let tunables = match &args.tunables_file {
+                    Some(path) => Tunables::load(path),
+                    None => Err("no --tunables-file given".to_string()),
+                };
+                match tunables {
+                    Ok(tunables) => {
+                        if let Some(min_difficulty) = tunables.min_difficulty {
+                            args.min_difficulty = Some(min_difficulty);
+                            println!("  Min difficulty: {}", min_difficulty);
+                        }
+                        if let Some(priority_fee) = tunables.priority_fee {
+                            for wallet in wallets.iter_mut() {
+                                wallet.miner.fees.priority_fee = Some(priority_fee);
+                            }
+                            println!("  Priority fee: {} microlamports", priority_fee);
+                        }
+                        if let Some(buffer_time) = tunables.buffer_time {
+                            buffer.set_fixed(Some(buffer_time));
+                            println!("  Buffer time: {} sec", buffer_time);
+                        }
+                    }
+                    Err(err) => println!(
+                        "{} Failed to reload tunables: {}",
+                        "WARNING".bold().yellow(),
+                        err
+                    ),
+                }
+            }
+
+            // Stop once the time limit is up
+            if deadline.is_some_and(|d| Instant::now().ge(&d)) {
+                stats.print_summary(session_earned(&wallets), args.json);
+                return;
+            }
+
+            // Pause outside of active hours
+            if !schedule::is_active(&args.active_hours, Local::now()) {
+                let Some(resume_at) = schedule::next_active(&args.active_hours, Local::now())
+                else {
+                    println!("{} No upcoming active hours", "ERROR".bold().red());
+                    return;
+                };
+                println!(
+                    "\nOutside active hours. Resuming at {}",
+                    resume_at.format("%a %H:%M")
+                );
+                if let Some(task) = prefetched.take() {
+                    task.abort();
+                }
+                let wait = (resume_at - Local::now()).to_std().unwrap_or_default();
+                let wait = deadline.map_or(wait, |d| {
+                    wait.min(d.saturating_duration_since(Instant::now()))
+                });
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = wait_for_interrupt(&pool) => {
+                        if stats.rounds.gt(&0) {
+                            stats.print_summary(session_earned(&wallets), args.json);
+                        }
+                        return;
+                    }
+                }
+                continue;
+            }
+
+            // Read the next round, unless it was read while the last submission confirmed, giving
+            // up on the wait at the time limit
+            let next = async {
+                if let Some(task) = prefetched.take() {
+                    if let Ok(next) = task.await {
+                        return next;
+                    }
+                }
+                self.next_round(&proof_targets(&wallets), proof_wait).await
+            };
+            let next = match deadline {
+                Some(deadline) => tokio::select! {
+                    next = next => Some(next),
+                    _ = tokio::time::sleep_until(deadline.into()) => None,
+                },
+                None => Some(next.await),
+            };
+            let Some(next) = next else {
+                stats.print_summary(session_earned(&wallets), args.json);
+                return;
+            };
+
+            // Reads have already been retried, so wait a little before starting over. A proof
+            // that is gone is opened again, and anything else missing means the wrong cluster.
+            let (index, state, config_cached) = match next {
+                Ok(next) => next,
+                Err(UtilsError::NotFound(address))
+                    if wallets.iter().any(|w| proof_pubkey(w.pubkey).eq(&address)) =>
+                {
+                    let wallet = wallets
+                        .iter()
+                        .find(|w| proof_pubkey(w.pubkey).eq(&address))
+                        .unwrap();
+                    println!(
+                        "{}{} Proof account {} not found. Opening it.",
+                        wallet.label,
+                        "WARNING".bold().yellow(),
+                        address
+                    );
+                    if !wallet.miner.open().await {
+                        return;
+                    }
+                    continue;
+                }
+                Err(err) if !err.is_transient() => {
+                    self.report_wrong_cluster(&err, args.once).await;
+                    return;
+                }
+                Err(err) => {
+                    println!(
+                        "{} Failed to read the round state: {}. Trying again in {}s.",
+                        "ERROR".bold().red(),
+                        err,
+                        READ_ERROR_DELAY.as_secs()
+                    );
+                    if args.once {
+                        println!("{}", json!({ "landed": false, "error": err.to_string() }));
+                        std::process::exit(EXIT_RPC_UNREACHABLE);
+                    }
+                    tokio::time::sleep(READ_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            let RoundState { config, proof, .. } = state;
+            let mut next_targets = proof_targets(&wallets);
+            next_targets[index].last_hash_at = proof.last_hash_at;
+            if !config_cached {
+                self.config_cache
+                    .set(config, state.treasury_balance, state.now());
+            }
+            stats.treasury_balance = Some(state.treasury_balance);
+            stats.top_balance = Some(config.top_balance);
+            let wallet = &mut wallets[index];
+            wallet.miner.bus_cache.set(state.busses);
+            wallet.last_hash_at = proof.last_hash_at;
+            wallet.start_balance.get_or_insert(proof.balance);
+            wallet.balance = proof.balance;
+
+            // Record the outcome of the last submission
+            let mut last_reward = None;
+            if let Some(last_round) = wallet.last_round.take() {
+                let record = record_round(&args, last_round, proof.balance);
+                last_reward = Some((record.reward, record.estimated_reward));
+                adaptive.record(RoundOutcome {
+                    difficulty: record.difficulty,
+                    reward: record.reward,
+                    fee: record.fee,
+                });
+            }
+            if pool.interrupt.load(Ordering::Relaxed) {
+                stats.print_summary(session_earned(&wallets), args.json);
+                return;
+            }
+
+            // Never aim below the on-chain minimum, which the program rejects
+            let on_chain_min = config.min_difficulty as u32;
+            adaptive.raise_min_floor(on_chain_min);
+            let (mut min_difficulty, mut floor_source) = if args.adaptive_difficulty {
+                (adaptive.floor(), "adaptive")
+            } else {
+                match args.min_difficulty {
+                    Some(min_difficulty) => (min_difficulty, "--min-difficulty"),
+                    None => (on_chain_min, "on-chain"),
+                }
+            };
+            if min_difficulty.lt(&on_chain_min) {
+                if warned_floor.ne(&Some(on_chain_min)) {
+                    println!(
+                        "{} --min-difficulty {} is below the on-chain minimum. Using {} instead.",
+                        "WARNING".bold().yellow(),
+                        min_difficulty,
+                        on_chain_min
+                    );
+                    warned_floor = Some(on_chain_min);
+                }
+                min_difficulty = on_chain_min;
+                floor_source = "on-chain";
+            }
+
+            // Keep hashing a skipped challenge until a better solution turns up
+            if let Some((challenge, retry_floor)) = wallet.retry_floor {
+                if challenge.eq(&proof.challenge) && retry_floor.gt(&min_difficulty) {
+                    min_difficulty = retry_floor;
+                    floor_source = "--min-reward";
+                }
+            }
             println!(
-                "\nStake: {} ORE\n  Multiplier: {:12}x",
+                "\n{}Stake: {} ORE\n  Multiplier: {:12}x\n  Min difficulty: {} ({})",
+                wallet.label,
                 amount_u64_to_string(proof.balance),
-                calculate_multiplier(proof.balance, config.top_balance)
+                calculate_multiplier(proof.balance, config.top_balance),
+                min_difficulty,
+                floor_source
+            );
+            let stake_to_max = config.top_balance.saturating_sub(proof.balance);
+            println!(
+                "  Top stake: {} ORE ({:.2}% of it staked{})\n  Treasury: {} ORE",
+                amount_u64_to_string(config.top_balance),
+                stake_share(proof.balance, config.top_balance) * 100.0,
+                if stake_to_max.gt(&0) {
+                    format!(
+                        ", {} ORE more for the max multiplier",
+                        amount_u64_to_string(stake_to_max)
+                    )
+                } else {
+                    ", max multiplier reached".to_string()
+                },
+                amount_u64_to_string(state.treasury_balance)
+            );
+            if let Some((reward, estimated_reward)) = last_reward {
+                println!(
+                    "  Last reward: {} ORE (estimated {} ORE)",
+                    amount_u64_to_string(reward),
+                    amount_u64_to_string(estimated_reward)
+                );
+            }
+            if stats.tx.submitted.gt(&0) {
+                println!("  {}", stats.tx.summary());
+            }
+            let buffer_time = buffer.buffer_time();
+            println!(
+                "  Buffer time: {} sec ({})",
+                buffer_time,
+                if buffer.is_auto() { "auto" } else { "fixed" }
             );
 
             // Calc cutoff time
-            let cutoff_time = self.get_cutoff(proof, args.buffer_time).await;
+            let cutoff_time = get_cutoff(&state, buffer_time);
+            if let (true, Some(offset)) = (args.verbose, self.cluster_clock.offset()) {
+                println!("  Local clock offset: {:+}s from the cluster", offset);
+            }
+            if let (true, Some(depth)) = (args.verbose, self.rpc_pool.queue_depth()) {
+                println!("  RPC requests waiting on the rate limit: {}", depth);
+            }
+            if let Some(hashed_at) = last_hashed_at {
+                println!(
+                    "  Idle between rounds: {}ms",
+                    hashed_at.elapsed().as_millis()
+                );
+            }
 
-            // Run drillx
-            let solution =
-                Self::find_hash_par(proof, /*cutoff_time 0, */args.cores, args.min_difficulty)
-                    .await;
+            // Wait for the fee payer to be topped up before hashing
+            if args.pause_when_broke {
+                if let Some(balance) = wallet.miner.below_reserve().await {
+                    wallet.miner.warn_broke(balance);
+                    println!(
+                        "  Hashing paused. Checking the balance every {}s.",
+                        BROKE_POLL_INTERVAL.as_secs()
+                    );
+                    while wallet.miner.below_reserve().await.is_some() {
+                        if pool.interrupt.load(Ordering::Relaxed) {
+                            stats.print_summary(session_earned(&wallets), args.json);
+                            return;
+                        }
+                        tokio::time::sleep(BROKE_POLL_INTERVAL).await;
+                    }
+                    println!("{} Fee payer funded. Resuming.", "INFO".bold().blue());
+                    wallet.last_hash_at = 0;
+                    continue;
+                }
+            }
+
+            // Warn about the floor at the hashrate measured last round, or at startup
+            if let Some(hashrate) = hashrate_estimate {
+                warn_eta(min_difficulty, hashrate, buffer_time, &mut warned_eta);
+            }
+
+            // Watch for the challenge changing under us
+            pool.stale.store(false, Ordering::Relaxed);
+            let watcher = tokio::spawn(watch_challenge(
+                self.rpc_client.clone(),
+                self.rpc_pool.clone(),
+                wallet.pubkey,
+                wallet.proof_updates.clone(),
+                proof.challenge,
+                pool.stale.clone(),
+            ));
+
+            // Keep a fresh blockhash cached for the submission, and warm the bus cache
+            // before the cutoff so they're ready when the solution is found
+            let refresher = tokio::spawn(refresh_blockhash(
+                wallet.miner.clone(),
+                self.rpc_pool.clone(),
+            ));
+            let bus_refresher = tokio::spawn(refresh_busses(
+                wallet.miner.clone(),
+                self.rpc_pool.clone(),
+                Instant::now() + Duration::from_secs(cutoff_time),
+            ));
+
+            // Run drillx, locally or on the remote workers
+            let result = match &coordinator {
+                None => {
+                    Self::find_hash_par(
+                        &pool,
+                        &args,
+                        proof.challenge,
+                        cutoff_time,
+                        min_difficulty,
+                        wallet.checkpoint_path.as_deref(),
+                        &wallet.label,
+                    )
+                    .await
+                }
+                Some(coordinator) => {
+                    let remote = coordinator
+                        .find_hash(&args, proof.challenge, cutoff_time, min_difficulty, &pool)
+                        .await;
+                    let Some((nonce, digest, difficulty)) = remote.best else {
+                        watcher.abort();
+                        refresher.abort();
+                        bus_refresher.abort();
+                        println!(
+                            "{}{} No results from workers",
+                            wallet.label,
+                            "WARNING".bold().yellow()
+                        );
+                        exit_once(&args, "no results from workers");
+                        wallet.last_hash_at = 0;
+                        continue;
+                    };
+                    println!(
+                        "{}Best hash: {} (difficulty: {}) in {:.1} sec\n  Hashes: {}",
+                        wallet.label,
+                        bs58::encode(Solution::new(digest, nonce.to_le_bytes()).to_hash().h)
+                            .into_string(),
+                        difficulty,
+                        remote.elapsed,
+                        remote.hashes
+                    );
+                    HashResult {
+                        solution: Solution::new(digest, nonce.to_le_bytes()),
+                        cores: vec![],
+                        hashes: remote.hashes,
+                        elapsed: remote.elapsed,
+                        stalled: false,
+                    }
+                }
+            };
+            watcher.abort();
+            refresher.abort();
+            bus_refresher.abort();
+            let hashed_at = Instant::now();
+            last_hashed_at = Some(hashed_at);
+
+            // Start over after a stall, giving up once it keeps happening
+            if result.stalled {
+                stalls += 1;
+                if stalls.ge(&args.max_stalls) {
+                    println!(
+                        "{} Hashing stalled {} rounds in a row. Exiting.",
+                        "ERROR".bold().red(),
+                        stalls
+                    );
+                    stats.print_summary(session_earned(&wallets), args.json);
+                    std::process::exit(1);
+                }
+                exit_once(&args, "hashing stalled");
+                wallet.last_hash_at = 0;
+                continue;
+            }
+            stalls = 0;
+
+            // Refine the hashrate the floor is checked against
+            if result.hashes.gt(&0) {
+                hashrate_estimate = Some(result.hashes as f64 / result.elapsed.max(f64::EPSILON));
+            }
+            let solution = result.solution;
+            stats.record_round(solution.to_hash().difficulty(), result.hashes);
+
+            // Discard the solution if another submission landed while hashing
+            let interrupted = pool.interrupt.load(Ordering::Relaxed);
+            if pool.stale.load(Ordering::Relaxed) {
+                if interrupted {
+                    stats.print_summary(session_earned(&wallets), args.json);
+                    return;
+                }
+                println!(
+                    "{}{} Round aborted: the challenge changed while hashing. Starting over on the new challenge.",
+                    wallet.label,
+                    "WARNING".bold().yellow()
+                );
+                exit_once(&args, "challenge changed while hashing");
+                continue;
+            }
+
+            // Verify the digest against the challenge before spending a transaction on it
+            if !solution.is_valid(&proof.challenge) {
+                if interrupted {
+                    stats.print_summary(session_earned(&wallets), args.json);
+                    return;
+                }
+                println!(
+                    "{}{} Solution failed local verification. Restarting the round.\n  Challenge: {}\n  Nonce: {}\n  Digest: {}",
+                    wallet.label,
+                    "ERROR".bold().red(),
+                    bs58::encode(proof.challenge).into_string(),
+                    u64::from_le_bytes(solution.n),
+                    bs58::encode(solution.d).into_string()
+                );
+                exit_once(&args, "solution failed local verification");
+                wallet.last_hash_at = 0;
+                continue;
+            }
+
+            // Skip submission if the solution is below the on-chain minimum
+            if solution
+                .to_hash()
+                .difficulty()
+                .lt(&(config.min_difficulty as u32))
+            {
+                if interrupted {
+                    stats.print_summary(session_earned(&wallets), args.json);
+                    return;
+                }
+                println!(
+                    "{}{} Solution is below the on-chain minimum difficulty ({}). Skipping submission.",
+                    wallet.label,
+                    "WARNING".bold().yellow(),
+                    config.min_difficulty
+                );
+                exit_once(&args, "solution below the on-chain minimum difficulty");
+
+                // Refetch the proof without waiting for a new challenge
+                wallet.last_hash_at = 0;
+                continue;
+            }
+
+            // Estimate the reward on the selected bus
+            let full_reward =
+                estimate_reward(config, proof.balance, solution.to_hash().difficulty(), None);
+            let (mut bus, bus_state) = self.find_bus(&args, full_reward, &[]).await;
+            let estimated_reward = estimate_reward(
+                config,
+                proof.balance,
+                solution.to_hash().difficulty(),
+                bus_state,
+            );
+            if let Some(bus_state) = bus_state {
+                println!(
+                    "  Bus {}: {} ORE",
+                    bus_state.id,
+                    amount_u64_to_string(bus_state.rewards)
+                );
+            }
+            println!(
+                "  Estimated reward: {} ORE",
+                amount_u64_to_string(estimated_reward)
+            );
 
             // Build instruction set
-            let mut ixs = vec![ore_api::instruction::auth(proof_pubkey(signer.pubkey()))];
-            let mut compute_budget = 500_000;
-            if self.should_reset(config).await && rand::thread_rng().gen_range(0..100).eq(&0) {
-                compute_budget += 100_000;
-                ixs.push(ore_api::instruction::reset(signer.pubkey()));
+            let signer = wallet.miner.signer();
+            let mut ixs = vec![ore_api::instruction::auth(proof_pubkey(wallet.pubkey))];
+            let mut fallback_budget = 500_000;
+            let has_reset = should_reset(&state) && rand::thread_rng().gen_range(0..100).eq(&0);
+            if has_reset {
+                fallback_budget += 100_000;
+                ixs.push(for_program(
+                    ore_api::instruction::reset(signer.pubkey()),
+                    wallet.pubkey,
+                ));
             }
 
             // Build mine ix
-            ixs.push(ore_api::instruction::mine(
-                signer.pubkey(),
-                signer.pubkey(),
-                self.find_bus().await,
-                solution,
+            let mine_index = ixs.len();
+            ixs.push(for_program(
+                ore_api::instruction::mine(signer.pubkey(), wallet.pubkey, bus, solution),
+                wallet.pubkey,
             ));
 
+            // Claim in the same transaction once the balance passes the threshold
+            let mine_ixs = ixs.len();
+            let mut claim_amount = 0;
+            let mut separate_claim = false;
+            if let Some(threshold) = args.claim_with_mine {
+                if proof.balance.gt(&amount_f64_to_u64(threshold))
+                    && wallet.miner.authority().eq(&signer.pubkey())
+                {
+                    claim_amount = proof.balance;
+                    ixs.extend(wallet.miner.claim_ixs(claim_amount));
+                    if wallet.miner.fits_in_packet(&ixs) {
+                        fallback_budget += CU_LIMIT_CLAIM + CU_LIMIT_ATA;
+                    } else {
+                        ixs.truncate(mine_ixs);
+                        separate_claim = true;
+                    }
+                }
+            }
+
+            // Size the compute budget from simulation
+            let mut compute_budget = wallet.miner.simulated_cu_limit(&ixs, fallback_budget).await;
+            if ixs.len().gt(&mine_ixs) && compute_budget.ge(&MAX_CU_LIMIT) {
+                ixs.truncate(mine_ixs);
+                fallback_budget -= CU_LIMIT_CLAIM + CU_LIMIT_ATA;
+                separate_claim = true;
+                compute_budget = wallet.miner.simulated_cu_limit(&ixs, fallback_budget).await;
+            }
+            if separate_claim && args.verbose {
+                println!("  Claim does not fit in the mine transaction. Claiming separately.");
+            }
+            if args.verbose {
+                println!("  Compute unit limit: {}", compute_budget);
+            }
+
+            // Pay more to land solutions worth more
+            let difficulty = solution.to_hash().difficulty();
+            let scaled_fee = wallet
+                .miner
+                .fees
+                .scaled_fee(difficulty, min_difficulty)
+                .map(|price| (price, difficulty));
+            let cu_price = scaled_fee.map_or(wallet.miner.fees.static_fee(), |(price, _)| price);
+            if let (true, Some((price, _))) = (args.verbose, scaled_fee) {
+                println!(
+                    "  Priority fee: {} microlamports (difficulty {})",
+                    price, difficulty
+                );
+            }
+
+            // Skip the transaction if the reward does not cover the threshold and fee
+            let tip = match wallet.miner.jito {
+                Some(_) => wallet.miner.jito_tip().await + 5000,
+                None => 0,
+            };
+            let fee = estimate_fee(cu_price, compute_budget) + tip;
+            if let Some(min_reward) = args.min_reward {
+                let fee_in_ore = args
+                    .ore_price
+                    .filter(|price| price.gt(&0.0))
+                    .map_or(0.0, |price| lamports_to_sol(fee) / price);
+                let required = amount_f64_to_u64(min_reward + fee_in_ore);
+                if estimated_reward.lt(&required) {
+                    if interrupted {
+                        stats.print_summary(session_earned(&wallets), args.json);
+                        return;
+                    }
+                    println!(
+                        "{}{} Estimated reward is below the required {} ORE. Skipping submission and hashing for a better solution.",
+                        wallet.label,
+                        "WARNING".bold().yellow(),
+                        amount_u64_to_string(required)
+                    );
+                    exit_once(&args, "estimated reward below --min-reward");
+
+                    // The challenge only changes when a solution lands, so don't wait for a new one
+                    let retry_floor = (solution.to_hash().difficulty() + 1).max(min_difficulty);
+                    wallet.retry_floor = Some((proof.challenge, retry_floor));
+                    wallet.last_hash_at = 0;
+                    continue;
+                }
+            }
+
+            // Keep hashing, but don't submit until the fee payer is topped up
+            if let Some(balance) = wallet.miner.below_reserve().await {
+                if interrupted {
+                    stats.print_summary(session_earned(&wallets), args.json);
+                    return;
+                }
+                wallet.miner.warn_broke(balance);
+                exit_once(&args, "fee payer below --min-sol-balance");
+                wallet.last_hash_at = 0;
+                continue;
+            }
+
+            // Start waiting on the next proof once the transaction is sent, so the next round is
+            // read by the time it confirms
+            let proof_address = proof_pubkey(wallet.pubkey);
+            let window_closes = window_closes_at(&state);
+            let sent = Arc::new(Notify::new());
+            let prefetch = {
+                let (miner, sent) = (self.clone(), sent.clone());
+                tokio::spawn(async move {
+                    sent.notified().await;
+                    miner.next_round(&next_targets, proof_wait).await
+                })
+            };
+            let round = SubmitRound {
+                deadline: window_closes,
+                proof: (proof_address, proof.challenge),
+                sent: &sent,
+            };
+
             // Submit transaction
-            self.send_and_confirm(&ixs, ComputeBudget::Fixed(compute_budget), false)
-                .await
-                .ok();
+            let mut submission = wallet
+                .miner
+                .send_and_confirm_by(
+                    &ixs,
+                    ComputeBudget::Simulated(fallback_budget),
+                    false,
+                    Some(round),
+                    scaled_fee,
+                )
+                .await;
+
+            // Resubmit with a reset if the epoch ended while the solution was in flight
+            let mut needs_reset = submission
+                .as_ref()
+                .is_err_and(|err| err.ore_error().eq(&Some(OreError::NeedsReset)));
+
+            // A bus without enough rewards fails the same way, so read the busses again
+            if needs_reset {
+                wallet.miner.bus_cache.invalidate();
+            }
+
+            // The solution is still valid if the bus ran dry before it landed, so move it to
+            // another bus while the window is open
+            let mut tried = vec![];
+            while needs_reset && Instant::now().lt(&window_closes) {
+                let Some(index) = bus_pubkeys().iter().position(|address| address.eq(&bus)) else {
+                    break;
+                };
+                tried.push(index);
+                let busses = wallet.miner.refresh_busses().await.unwrap_or_default();
+                let drained = busses[index].is_some_and(|b| b.rewards.lt(&full_reward));
+                if !drained || tried.len().ge(&BUS_COUNT) {
+                    break;
+                }
+                let (next_bus, next_state) = self.find_bus(&args, full_reward, &tried).await;
+                println!(
+                    "{}{} Bus {} ran out of rewards. Resubmitting the solution to bus {}{}.",
+                    wallet.label,
+                    "WARNING".bold().yellow(),
+                    index,
+                    bus_pubkeys()
+                        .iter()
+                        .position(|address| address.eq(&next_bus))
+                        .unwrap_or_default(),
+                    next_state.map_or("".to_string(), |state| format!(
+                        " ({} ORE left)",
+                        amount_u64_to_string(state.rewards)
+                    ))
+                );
+                bus = next_bus;
+                ixs[mine_index] = for_program(
+                    ore_api::instruction::mine(signer.pubkey(), wallet.pubkey, bus, solution),
+                    wallet.pubkey,
+                );
+                submission = wallet
+                    .miner
+                    .send_and_confirm_by(
+                        &ixs,
+                        ComputeBudget::Simulated(fallback_budget),
+                        false,
+                        Some(round),
+                        scaled_fee,
+                    )
+                    .await;
+                needs_reset = submission
+                    .as_ref()
+                    .is_err_and(|err| err.ore_error().eq(&Some(OreError::NeedsReset)));
+            }
+            if needs_reset && !has_reset {
+                println!(
+                    "{}{} The epoch needs a reset. Resubmitting with a reset instruction.",
+                    wallet.label,
+                    "WARNING".bold().yellow()
+                );
+                ixs.insert(
+                    1,
+                    for_program(ore_api::instruction::reset(signer.pubkey()), wallet.pubkey),
+                );
+                fallback_budget += 100_000;
+                submission = wallet
+                    .miner
+                    .send_and_confirm_by(
+                        &ixs,
+                        ComputeBudget::Simulated(fallback_budget),
+                        false,
+                        Some(round),
+                        scaled_fee,
+                    )
+                    .await;
+            }
+
+            // The proof is unchanged when the transaction expires without landing, so resend
+            // the same solution while its window is open rather than waiting on the proof
+            let mut resubmits = 0;
+            while resubmits.lt(&MAX_RESUBMITS)
+                && Instant::now().lt(&window_closes)
+                && submission.as_ref().is_err_and(|err| {
+                    matches!(
+                        err,
+                        SubmitError::BlockhashExpired
+                            | SubmitError::Dropped { .. }
+                            | SubmitError::RpcError(_)
+                    )
+                })
+                && !challenge_moved(&self.rpc_client, proof_address, proof.challenge).await
+            {
+                resubmits += 1;
+                println!(
+                    "{}{} The transaction expired before landing. Resubmitting the solution ({}s left in the window).",
+                    wallet.label,
+                    "WARNING".bold().yellow(),
+                    window_closes.saturating_duration_since(Instant::now()).as_secs()
+                );
+                submission = wallet
+                    .miner
+                    .send_and_confirm_by(
+                        &ixs,
+                        ComputeBudget::Simulated(fallback_budget),
+                        false,
+                        Some(round),
+                        scaled_fee,
+                    )
+                    .await;
+            }
+
+            // A reset rewrites the config, and a too easy hash may mean the minimum difficulty
+            // changed, so read it again next round
+            let config_changed = has_reset
+                || needs_reset
+                || submission
+                    .as_ref()
+                    .is_err_and(|err| err.ore_error().eq(&Some(OreError::HashTooEasy)));
+            if config_changed {
+                self.config_cache.invalidate();
+            }
+
+            // Timing errors may mean the local clock drifted from the cluster, so resync it
+            if submission.as_ref().is_err_and(|err| {
+                matches!(
+                    err.ore_error(),
+                    Some(OreError::ClockInvalid | OreError::Spam)
+                )
+            }) {
+                self.cluster_clock.invalidate();
+            }
+            let signature = submission
+                .as_ref()
+                .ok()
+                .map(|confirmed| confirmed.signature);
+            let landed = signature.is_some();
+            if let Some(sent_at) = submission.as_ref().ok().and_then(|c| c.sent_at) {
+                println!(
+                    "  Solution to send: {}ms",
+                    sent_at.saturating_duration_since(hashed_at).as_millis()
+                );
+            }
+            let outcome = submission
+                .as_ref()
+                .map_or_else(|err| err.outcome(), |_| "landed");
+
+            // Count the fee and tip actually paid, which may differ from the estimate
+            let tip = submission.as_ref().map_or(0, |confirmed| confirmed.tip);
+            let fee = submission
+                .as_ref()
+                .ok()
+                .and_then(|confirmed| confirmed.fee_paid)
+                .unwrap_or(estimate_fee(cu_price, compute_budget))
+                + if tip.gt(&0) { tip + 5000 } else { 0 };
+            stats.record_tx(outcome, fee, tip);
+            if let Some(compute_units) = submission.as_ref().ok().and_then(|c| c.compute_units) {
+                stats.record_cu(compute_units, compute_budget);
+            }
+            submitted += 1;
+            if landed {
+                buffer.record(hashed_at.elapsed().as_secs_f64());
+            }
+
+            // Claim once the mine lands when the claim did not fit in its transaction
+            let mut claimed = 0;
+            if landed && claim_amount.gt(&0) {
+                let claim_landed = !separate_claim
+                    || match wallet
+                        .miner
+                        .send_and_confirm(
+                            &wallet.miner.claim_ixs(claim_amount),
+                            ComputeBudget::Simulated(CU_LIMIT_CLAIM + CU_LIMIT_ATA),
+                            false,
+                        )
+                        .await
+                    {
+                        Ok(confirmed) => {
+                            stats.record_fee(confirmed.fee_paid.unwrap_or(estimate_fee(
+                                wallet.miner.fees.for_kind("claim").static_fee(),
+                                CU_LIMIT_CLAIM + CU_LIMIT_ATA,
+                            )));
+                            true
+                        }
+                        Err(_) => false,
+                    };
+                if claim_landed {
+                    claimed = claim_amount;
+                    wallet.claimed += claimed;
+                    println!("  Claimed: {} ORE", amount_u64_to_string(claimed));
+                } else {
+                    println!(
+                        "{}{} Failed to claim {} ORE. Retrying next round.",
+                        wallet.label,
+                        "WARNING".bold().yellow(),
+                        amount_u64_to_string(claim_amount)
+                    );
+                }
+            }
+            // The proof doesn't change after a simulation, so hash the same challenge again
+            if submission.is_ok() && wallet.miner.send.dry_run {
+                wallet.last_hash_at = 0;
+            }
+
+            // Mine the round read during confirmation, unless it may have been read before the
+            // config was rewritten or a separate claim changed the balance
+            let separately_claimed = separate_claim && claim_amount.gt(&0);
+            if landed && !wallet.miner.send.dry_run && !config_changed && !separately_claimed {
+                prefetched = Some(prefetch);
+            } else {
+                prefetch.abort();
+            }
+            if let Err(err) = &submission {
+                // Mine the same challenge again if the transaction did not land
+                match err {
+                    SubmitError::InsufficientBalance(balance) => wallet.miner.warn_broke(*balance),
+                    SubmitError::Stale => println!(
+                        "{}{} Another solution landed first. Moving on to the new challenge.",
+                        wallet.label,
+                        "INFO".bold().blue()
+                    ),
+                    SubmitError::WindowClosed { attempts } => println!(
+                        "{}{} The proof's window closed after {} attempts without the transaction landing. Hashing again.",
+                        wallet.label,
+                        "WARNING".bold().yellow(),
+                        attempts
+                    ),
+                    SubmitError::Timeout { signatures } => {
+                        println!(
+                            "{}{} Confirmation timed out. Moving on and checking the transaction in the background.",
+                            wallet.label,
+                            "WARNING".bold().yellow()
+                        );
+                        watch_signatures(
+                            self.rpc_client.clone(),
+                            signatures.clone(),
+                            wallet.label.clone(),
+                        );
+                    }
+                    SubmitError::ProgramError(_)
+                        if matches!(
+                            err.ore_error(),
+                            Some(OreError::HashInvalid | OreError::HashTooEasy)
+                        ) =>
+                    {
+                        println!(
+                            "{}{} The program rejected the solution: {}. Discarding it and hashing again.",
+                            wallet.label,
+                            "ERROR".bold().red(),
+                            err.ore_error().unwrap()
+                        )
+                    }
+                    SubmitError::BlockhashExpired => println!(
+                        "{}{} The blockhash expired before the transaction landed. Refetching the proof.",
+                        wallet.label,
+                        "ERROR".bold().red()
+                    ),
+                    SubmitError::RpcError(rpc_err) => println!(
+                        "{}{} The RPC failed the submission: {}. Refetching the proof.",
+                        wallet.label,
+                        "ERROR".bold().red(),
+                        rpc_err
+                    ),
+                    _ => println!(
+                        "{}{} Submission failed: {}. Refetching the proof.",
+                        wallet.label,
+                        "ERROR".bold().red(),
+                        err
+                    ),
+                }
+                wallet.last_hash_at = 0;
+            }
+            wallet.last_round = Some(SubmittedRound {
+                balance: proof.balance.saturating_sub(claimed),
+                record: RoundRecord {
+                    timestamp: Local::now().to_rfc3339(),
+                    authority: wallet.pubkey.to_string(),
+                    challenge: bs58::encode(proof.challenge).into_string(),
+                    hash_time: result.elapsed,
+                    hashes: result.hashes,
+                    difficulty,
+                    min_difficulty,
+                    landed,
+                    outcome,
+                    signature: signature.map(|s| s.to_string()).unwrap_or_default(),
+                    reward: 0,
+                    estimated_reward,
+                    stake: proof.balance,
+                    stake_to_max: config.top_balance.saturating_sub(proof.balance),
+                    priority_fee: cu_price,
+                    fee,
+                    confirm_latency: submission
+                        .as_ref()
+                        .map_or(0.0, |confirmed| confirmed.latency.as_secs_f64()),
+                    cores: result.cores,
+                },
+            });
+
+            // Report the single round of --once and exit with its outcome
+            if args.once {
+                let balance = match &submission {
+                    Ok(_) => get_proof(&self.rpc_client, wallet.pubkey)
+                        .await
+                        .map_or(proof.balance, |proof| proof.balance),
+                    Err(_) => proof.balance,
+                };
+                let mut line = wallet
+                    .last_round
+                    .take()
+                    .map(|r| record_round(&args, r, balance).to_json())
+                    .unwrap_or_default();
+                let code = match &submission {
+                    Ok(_) => 0,
+                    Err(err) => {
+                        line["error"] = json!(err.to_string());
+                        let unreachable = match err {
+                            SubmitError::RpcError(rpc_err) => matches!(
+                                rpc_err.kind(),
+                                ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+                            ),
+                            _ => false,
+                        };
+                        if unreachable {
+                            EXIT_RPC_UNREACHABLE
+                        } else {
+                            EXIT_SUBMIT_FAILED
+                        }
+                    }
+                };
+                println!("{}", line);
+                std::process::exit(code);
+            }
+
+            // Exit after submitting the final solution
+            let limit_reached = args.rounds.is_some_and(|r| submitted.ge(&r))
+                || deadline.is_some_and(|d| Instant::now().ge(&d));
+            let over_budget = args
+                .max_fee_spend
+                .is_some_and(|max| stats.fees.ge(&sol_to_lamports(max)));
+            if over_budget {
+                println!(
+                    "{} Spent {} SOL on fees, reaching --max-fee-spend. Exiting.",
+                    "ERROR".bold().red(),
+                    lamports_to_sol(stats.fees)
+                );
+            }
+            if interrupted || limit_reached || over_budget {
+                if let Ok(proof) = get_proof(&self.rpc_client, wallet.pubkey).await {
+                    wallet.balance = proof.balance;
+                }
+                if let Some(last_round) = wallet.last_round.take() {
+                    record_round(&args, last_round, wallet.balance);
+                }
+                stats.print_summary(session_earned(&wallets), args.json);
+                if over_budget {
+                    std::process::exit(EXIT_FEE_LIMIT);
+                }
+                return;
+            }
+
+            // Report progress every few rounds
+            if args.stats_interval.gt(&0) && (stats.rounds % args.stats_interval).eq(&0) {
+                stats.print_line(session_earned(&wallets), args.json);
+            }
+        }
+    }
+
+    // Load the keypairs to mine with, or None if the keypair dir can't be read
+    fn wallets(&self, args: &MineArgs) -> Option<Vec<Wallet>> {
+        let miners = match &args.keypair_dir {
+            Some(dir) => {
+                let entries = match std::fs::read_dir(dir) {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        println!(
+                            "{} Failed to read keypair dir {}: {}",
+                            "ERROR".bold().red(),
+                            dir,
+                            err
+                        );
+                        return None;
+                    }
+                };
+                let mut paths = entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext.eq("json")))
+                    .filter(|path| read_keypair_file(path).is_ok())
+                    .collect::<Vec<_>>();
+                paths.sort();
+                paths
+                    .into_iter()
+                    .map(|path| self.with_keypair(path.to_string_lossy().to_string()))
+                    .collect()
+            }
+            None => vec![self.clone()],
+        };
+        let multiple = miners.len().gt(&1);
+        let wallets = miners
+            .into_iter()
+            .map(|miner| {
+                let pubkey = miner.authority();
+                let checkpoint_path = if args.no_checkpoint {
+                    None
+                } else {
+                    Some(
+                        args.checkpoint_path
+                            .clone()
+                            .filter(|_| !multiple)
+                            .unwrap_or(default_checkpoint_path(pubkey)),
+                    )
+                };
+                Wallet {
+                    proof_updates: miner.subscribe_proof(pubkey),
+                    miner,
+                    pubkey,
+                    label: if multiple {
+                        format!("[{}] ", &pubkey.to_string()[..4])
+                    } else {
+                        "".to_string()
+                    },
+                    checkpoint_path,
+                    last_hash_at: 0,
+                    last_round: None,
+                    start_balance: None,
+                    balance: 0,
+                    claimed: 0,
+                    retry_floor: None,
+                }
+            })
+            .collect();
+        Some(wallets)
+    }
+
+    // Pick the wallet whose mining window opens first. If no proof updates within the wait,
+    // the proof is taken as it is, to mine the same challenge again.
+    // Reads the config and clock while waiting for the proof of the next wallet, then reads the
+    // rest of the round in one request
+    async fn next_round(&self, targets: &[ProofTarget], wait: ProofWait) -> NextRound {
+        let cached_config = self.config_cache.get();
+        let (wait, context) = tokio::join!(
+            self.next_wallet(targets, wait),
+            fetch_round_context(&self.rpc_client, cached_config, &self.cluster_clock),
+        );
+        let (index, _) = wait?;
+
+        // A reset during the wait rewrites the config, so it's read again then
+        let context = context.ok().filter(|(config, _)| {
+            self.cluster_clock
+                .now()
+                .is_some_and(|now| now.lt(&config.last_reset_at.saturating_add(EPOCH_DURATION)))
+        });
+        let config_cached = cached_config.is_some() && context.is_some();
+        let state = fetch_round_state(
+            &self.rpc_client,
+            targets[index].pubkey,
+            context,
+            &self.cluster_clock,
+        )
+        .await?;
+        Ok((index, state, config_cached))
+    }
+
+    async fn next_wallet(
+        &self,
+        wallets: &[ProofTarget],
+        wait: ProofWait,
+    ) -> Result<(usize, Proof), UtilsError> {
+        if wallets.len().eq(&1) {
+            let wallet = &wallets[0];
+            let (proof, updated) = get_updated_proof_with_authority(
+                &self.rpc_client,
+                wallet.pubkey,
+                wallet.last_hash_at,
+                wait,
+                wallet.proof_updates.clone(),
+            )
+            .await?;
+            if !updated {
+                warn_proof_not_updated(wait);
+            }
+            return Ok((0, proof));
+        }
+        let started = Instant::now();
+        let mut retries = 0;
+        loop {
+            let authorities: Vec<Pubkey> = wallets.iter().map(|w| w.pubkey).collect();
+            let proofs = get_proofs(&self.rpc_client, &authorities).await;
+
+            // Skip wallets whose last submission has not landed yet, or whose proof couldn't be
+            // read, until the wait runs out
+            let expired = started.elapsed().ge(&wait.timeout) || retries.ge(&wait.max_retries);
+            let mut last_err = None;
+            let mut read = vec![];
+            match proofs {
+                Ok(proofs) => {
+                    for (i, proof) in proofs.into_iter().enumerate() {
+                        match proof {
+                            Some(proof) => read.push((i, proof)),
+                            None => {
+                                last_err = Some(UtilsError::NotFound(proof_pubkey(authorities[i])))
+                            }
+                        }
+                    }
+                }
+                Err(err) => last_err = Some(err),
+            }
+            let next = read
+                .into_iter()
+                .filter(|(i, proof)| expired || proof.last_hash_at.ne(&wallets[*i].last_hash_at))
+                .min_by_key(|(_, proof)| proof.last_hash_at);
+            if let Some((index, proof)) = next {
+                if expired {
+                    warn_proof_not_updated(wait);
+                }
+                return Ok((index, proof));
+            }
+            if let (true, Some(err)) = (expired, last_err) {
+                return Err(err);
+            }
+            retries += 1;
+            tokio::time::sleep(Duration::from_millis(1000)).await;
         }
     }
 
-    async fn find_hash_par(
-        proof: Proof,
-        // cutoff_time: u64,
-        cores: u64,
+    pub async fn find_hash_par(
+        pool: &WorkerPool,
+        args: &MineArgs,
+        challenge: [u8; 32],
+        cutoff_time: u64,
         min_difficulty: u32,
-    ) -> Solution {
-        // Dispatch job to each thread
-        let stop_flag = Arc::new(AtomicBool::new(false));
-        let progress_bar = Arc::new(spinner::new_progress_bar());
+        checkpoint_path: Option<&str>,
+        label: &str,
+    ) -> HashResult {
+        // Resume from a checkpoint of the same challenge
+        let checkpoint = checkpoint_path
+            .and_then(Checkpoint::load)
+            .filter(|c| c.challenge.eq(&challenge) && c.nonces.len().eq(&pool.slots()));
+        let (start_nonces, resumed) = match checkpoint {
+            Some(checkpoint) => {
+                println!("  Resuming challenge from checkpoint");
+                let resumed = checkpoint.best.filter(|(nonce, digest, _)| {
+                    Solution::new(*digest, nonce.to_le_bytes()).is_valid(&challenge)
+                });
+                (checkpoint.nonces, resumed)
+            }
+            None => (pool.start_nonces(), None),
+        };
+
+        // Dispatch job to each worker
+        let throttled_before = pool.throttle.throttled();
+        let progress_bar = spinner::new_progress_bar();
         progress_bar.set_message("Mining...");
-        let core_ids = core_affinity::get_core_ids().unwrap();
-        let handles: Vec<_> = core_ids
-            .into_iter()
-            .map(|i| {
-                let proof = proof.clone();
-                let progress_bar = progress_bar.clone();
-                let stop_flag = stop_flag.clone();
-                std::thread::spawn(move || {
-                    let mut nonce = u64::MAX.saturating_div(cores).saturating_mul(i.id as u64);
-                    let mut best_nonce = nonce;
-                    let mut best_difficulty = 0;
-                    let mut best_hash = Hash::default();
-                    let mut memory = equix::SolverMemory::new();
-                    // Return if core should not be used
-                    if (i.id as u64).ge(&cores) {
-                        return (0, 0, Hash::default());
-                    }
-
-                    // Pin to core
-                    let _ = core_affinity::set_for_current(i);
-
-                    // Start hashing
-                    // let timer = Instant::now();
-                    loop {
-                        // Verificar se a flag de parada foi acionada
-                        if stop_flag.load(Ordering::Relaxed) {
-                            break;
+        let job = Arc::new(Job::new(
+            challenge,
+            cutoff_time,
+            min_difficulty,
+            args,
+            progress_bar.clone(),
+            pool,
+            start_nonces,
+        ));
+        let mut skip = false;
+        if let Some(best) = resumed {
+            *job.best.lock().unwrap() = Some(best);
+            job.global_best.store(best.2, Ordering::Relaxed);
+            if best.2.ge(&min_difficulty) {
+                job.floor_flag.store(true, Ordering::Relaxed);
+                skip = !args.continue_after_min;
+            }
+        }
+
+        // Sample the hash counters once per second and refresh the spinner a few times per second,
+        // stopping the round if the counters stop moving
+        let reporter = {
+            let job = job.clone();
+            let gpu = pool.gpu;
+            let throttle = pool.throttle.clone();
+            let checkpoint_path = checkpoint_path.map(|p| p.to_string());
+            let stall_timeout = args.stall_timeout;
+            let core_ids = pool.core_ids();
+            std::thread::spawn(move || {
+                let mut last_count = 0;
+                let mut last_gpu_count = 0;
+                let mut last_sample = Instant::now();
+                let mut last_progress = Instant::now();
+                let mut worker_counts = vec![0; core_ids.len()];
+                let mut worker_progress = vec![Instant::now(); core_ids.len()];
+                let mut last_checkpoint = Instant::now();
+                let mut last_message = Instant::now();
+                while !job.stop_flag.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(100));
+
+                    // Refresh the spinner
+                    if last_message.elapsed().ge(&PROGRESS_INTERVAL) {
+                        job.progress_bar.set_message(job.status(gpu, &throttle));
+                        last_message = Instant::now();
+                    }
+
+                    // Save progress every few seconds
+                    if let Some(path) = &checkpoint_path {
+                        if last_checkpoint.elapsed().ge(&CHECKPOINT_INTERVAL) {
+                            save_checkpoint(&job, path);
+                            last_checkpoint = Instant::now();
                         }
-                        // Create hash
-                        if let Ok(hx) = drillx::hash_with_memory(
-                            &mut memory,
-                            &proof.challenge,
-                            &nonce.to_le_bytes(),
-                        ) {
-                            let difficulty = hx.difficulty();
-                            if difficulty.gt(&best_difficulty) {
-                                best_nonce = nonce;
-                                best_difficulty = difficulty;
-                                best_hash = hx;
+                    }
+
+                    let elapsed = last_sample.elapsed();
+                    if elapsed.ge(&Duration::from_secs(1)) {
+                        let count = job.hash_count();
+                        let gpu_count = job.gpu_hash_count.load(Ordering::Relaxed);
+                        let secs = elapsed.as_secs_f64();
+
+                        // Watch for stalled workers
+                        for (i, hashes) in job.worker_hashes.iter().take(core_ids.len()).enumerate()
+                        {
+                            let hashes = hashes.load(Ordering::Relaxed);
+                            if hashes.gt(&worker_counts[i]) {
+                                worker_counts[i] = hashes;
+                                worker_progress[i] = Instant::now();
                             }
-                            
-                            progress_bar.set_message(format!(
-                                "MIN_DIFFICULTY: {} > {} Mining...",
-                                min_difficulty,
-                                best_difficulty
+                        }
+                        if count.gt(&last_count) || gpu_count.gt(&last_gpu_count) {
+                            last_progress = Instant::now();
+                        } else if stall_timeout.gt(&0)
+                            && last_progress.elapsed().as_secs().ge(&stall_timeout)
+                        {
+                            job.stall_flag.store(true, Ordering::Relaxed);
+                            job.stop_flag.store(true, Ordering::Relaxed);
+                            job.progress_bar.println(format!(
+                                "{} No hashes for {} sec. Restarting the round.",
+                                "WARNING".bold().yellow(),
+                                stall_timeout
                             ));
-
-                            // Exit loop if difficulty meets or exceeds min_difficulty
-                            if best_difficulty.ge(&min_difficulty) {
-                                stop_flag.store(true, Ordering::Relaxed);
-                                break;
+                            for (core_id, progress) in core_ids.iter().zip(worker_progress.iter()) {
+                                job.progress_bar.println(format!(
+                                    "  Core {}: last progress {} sec ago",
+                                    core_id,
+                                    progress.elapsed().as_secs()
+                                ));
                             }
                         }
-                        nonce += 1;
+
+                        job.hashrate.store(
+                            ((count - last_count) as f64 / secs) as u64,
+                            Ordering::Relaxed,
+                        );
+                        job.gpu_hashrate.store(
+                            ((gpu_count - last_gpu_count) as f64 / secs) as u64,
+                            Ordering::Relaxed,
+                        );
+                        last_count = count;
+                        last_gpu_count = gpu_count;
+                        last_sample = Instant::now();
                     }
-                    // Return the best nonce
-                    (best_nonce, best_difficulty, best_hash)
-                })
+                }
             })
-            .collect();
+        };
 
-        // Join handles and return best nonce
-        let mut best_nonce = 0;
-        let mut best_difficulty = 0;
-        let mut best_hash = Hash::default();
-        for h in handles {
-            if let Ok((nonce, difficulty, hash)) = h.join() {
-                if difficulty > best_difficulty {
-                    best_difficulty = difficulty;
-                    best_nonce = nonce;
-                    best_hash = hash;
-                }
-            }
+        // Wait for the workers and return best nonce
+        let mut best = if skip {
+            WorkerResult::new(0)
+        } else {
+            pool.run(job.clone())
+        };
+        if let Some((nonce, digest, difficulty)) = resumed {
+            best.merge(WorkerResult {
+                nonce,
+                difficulty,
+                hash: Solution::new(digest, nonce.to_le_bytes()).to_hash(),
+                histogram: [0; HISTOGRAM_SIZE],
+            });
+        }
+        if let Some(path) = checkpoint_path {
+            save_checkpoint(&job, path);
         }
+        job.stop_flag.store(true, Ordering::Relaxed);
+        reporter.join().ok();
 
         // Update log
+        let elapsed = job.timer.elapsed().as_secs_f64();
+        let total_hashes = job.hash_count();
+        let total_gpu_hashes = job.gpu_hash_count.load(Ordering::Relaxed);
+        let end_reason = if job.stall_flag.load(Ordering::Relaxed) {
+            "stall"
+        } else if job.cutoff_flag.load(Ordering::Relaxed) {
+            "cutoff"
+        } else if job.deadline_flag.load(Ordering::Relaxed) {
+            "max hash time"
+        } else if job.stale.load(Ordering::Relaxed) {
+            "challenge change"
+        } else {
+            "min difficulty"
+        };
         progress_bar.finish_with_message(format!(
-            "Best hash: {} (difficulty: {})",
-            bs58::encode(best_hash.h).into_string(),
-            best_difficulty
+            "{}Best hash: {} (difficulty: {}) in {:.1} sec, ended by {}\n  Hashes: {} ({:.0} H/s){}",
+            label,
+            bs58::encode(best.hash.h).into_string(),
+            best.difficulty,
+            elapsed,
+            end_reason,
+            total_hashes,
+            total_hashes as f64 / elapsed.max(f64::EPSILON),
+            if pool.gpu {
+                format!(
+                    "\n  GPU hashes: {} ({:.0} H/s)",
+                    total_gpu_hashes,
+                    total_gpu_hashes as f64 / elapsed.max(f64::EPSILON)
+                )
+            } else {
+                "".to_string()
+            }
         ));
 
-        Solution::new(best_hash.d, best_nonce.to_le_bytes())
+        // Log time spent throttled
+        if pool.throttle.is_enabled() {
+            let throttled = pool.throttle.throttled() - throttled_before;
+            println!(
+                "  Throttled: {:.0}% of the round",
+                100.0 * throttled.as_secs_f64() / elapsed.max(f64::EPSILON)
+            );
+        }
+
+        // Log difficulty distribution
+        let first = HISTOGRAM_MIN_DISPLAY.min(best.difficulty) as usize;
+        let last = (best.difficulty as usize).min(HISTOGRAM_SIZE - 1);
+        println!(
+            "  Distribution: {}",
+            (first..=last)
+                .map(|d| format!("{}:{}", d, best.histogram[d]))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        if let Some(path) = &args.log_histogram {
+            let line = json!({
+                "timestamp": Local::now().to_rfc3339(),
+                "challenge": bs58::encode(challenge).into_string(),
+                "best_difficulty": best.difficulty,
+                "histogram": best.histogram[..=last],
+            });
+            if let Err(err) = append_line(path, &line.to_string()) {
+                println!(
+                    "{} Failed to write histogram: {}",
+                    "WARNING".bold().yellow(),
+                    err
+                );
+            }
+        }
+
+        // Log per-core hashrate and flag cores that keep falling behind
+        let cores = pool.core_stats(&job);
+        if args.verbose {
+            println!("  {:>6} {:>10} {:>10}", "Core", "Hashes", "H/s");
+            for core in cores.iter() {
+                println!(
+                    "  {:>6} {:>10} {:>10.0}",
+                    core.core_id, core.hashes, core.hashrate
+                );
+            }
+        }
+        if !skip {
+            for (core_id, percent, streak) in
+                pool.track_slow_cores(&cores, args.slow_core_threshold)
+            {
+                if streak.ge(&SLOW_CORE_ROUNDS) && (streak % SLOW_CORE_ROUNDS).eq(&0) {
+                    println!(
+                        "{} Core {} ran at {:.0}% of the median hashrate for {} rounds. Consider excluding it.",
+                        "WARNING".bold().yellow(),
+                        core_id,
+                        percent,
+                        streak
+                    );
+                }
+            }
+        }
+
+        HashResult {
+            solution: Solution::new(best.hash.d, best.nonce.to_le_bytes()),
+            cores,
+            hashes: total_hashes + total_gpu_hashes,
+            elapsed,
+            stalled: job.stall_flag.load(Ordering::Relaxed),
+        }
+    }
+
+    // Bus to submit a solution worth the reward to. The --bus pin if it can pay the reward,
+    // else one picked by the strategy from those not excluded, tried or under --min-bus-rewards,
+    // or a random one if none of those can be read.
+    async fn find_bus(
+        &self,
+        args: &MineArgs,
+        reward: u64,
+        tried: &[usize],
+    ) -> (Pubkey, Option<Bus>) {
+        let mut busses = self.fetch_busses().await.unwrap_or_default();
+        if let Some(pin) = args
+            .bus
+            .map(|id| id as usize)
+            .filter(|i| !tried.contains(i))
+        {
+            match busses[pin] {
+                Some(bus) if bus.rewards.lt(&reward) => println!(
+                    "{} Bus {} has {} ORE left, less than the solution's reward. Picking another bus.",
+                    "WARNING".bold().yellow(),
+                    pin,
+                    amount_u64_to_string(bus.rewards)
+                ),
+                bus => return (bus_pubkeys()[pin], bus),
+            }
+        }
+        let allowed: Vec<usize> = (0..BUS_COUNT)
+            .filter(|i| !args.exclude_bus.contains(&(*i as u64)) && !tried.contains(i))
+            .collect();
+        let min_rewards = args.min_bus_rewards.map_or(0, amount_f64_to_u64);
+        for (i, bus) in busses.iter_mut().enumerate() {
+            let poor = bus.is_some_and(|bus| bus.rewards.lt(&min_rewards));
+            if !allowed.contains(&i) || args.bus.eq(&Some(i as u64)) || poor {
+                *bus = None;
+            }
+        }
+        match args.bus_strategy.select(&busses, args.bus_weight_exponent) {
+            Some(i) => (bus_pubkeys()[i], busses[i]),
+            None => {
+                let i = allowed
+                    .get(rand::thread_rng().gen_range(0..allowed.len().max(1)))
+                    .copied()
+                    .unwrap_or(0);
+                (bus_pubkeys()[i], None)
+            }
+        }
+    }
+
+    // Explains a read that retrying won't fix, with the cluster it was made on
+    async fn report_wrong_cluster(&self, err: &UtilsError, once: bool) {
+        let genesis_hash = self
+            .rpc_client
+            .get_genesis_hash()
+            .await
+            .map_or("unknown".to_string(), |hash| hash.to_string());
+        let problem = match err {
+            UtilsError::NotFound(address) => format!("Account {} was not found", address),
+            UtilsError::Deserialize(address) => {
+                format!("Account {} is not the expected ORE account", address)
+            }
+            UtilsError::WrongOwner(address, owner) => {
+                format!(
+                    "Account {} is owned by {}, not this program",
+                    address, owner
+                )
+            }
+            err => err.to_string(),
+        };
+        println!(
+            "{} {} on {} (genesis hash {}). Is this the right cluster, and the ORE program {}?",
+            "ERROR".bold().red(),
+            problem,
+            self.rpc_client.url(),
+            genesis_hash,
+            program_id()
+        );
+        if once {
+            println!("{}", json!({ "landed": false, "error": problem }));
+            std::process::exit(EXIT_WRONG_CLUSTER);
+        }
     }
 
     pub fn check_num_cores(&self, cores: u64) {
@@ -184,27 +1838,328 @@ impl Miner {
             );
         }
     }
+}
+
+// Hashes per second of the cores, measured with a short burst before the workers start
+fn warm_up_hashrate(core_ids: &[CoreId]) -> Option<f64> {
+    if core_ids.is_empty() {
+        return None;
+    }
+    let started = Instant::now();
+    let handles: Vec<_> = core_ids
+        .iter()
+        .copied()
+        .map(|core_id| {
+            std::thread::spawn(move || {
+                let _ = core_affinity::set_for_current(core_id);
+                let mut memory = equix::SolverMemory::new();
+                let mut hashes = 0u64;
+                let mut nonce = 0u64;
+                while started.elapsed().lt(&WARM_UP_TIME) {
+                    if drillx::hash_with_memory(&mut memory, &[0; 32], &nonce.to_le_bytes()).is_ok()
+                    {
+                        hashes += 1;
+                    }
+                    nonce += 1;
+                }
+                hashes
+            })
+        })
+        .collect();
+    let hashes: u64 = handles.into_iter().filter_map(|h| h.join().ok()).sum();
+    Some(hashes as f64 / started.elapsed().as_secs_f64())
+}
 
-    async fn should_reset(&self, config: Config) -> bool {
-        let clock = get_clock(&self.rpc_client).await;
-        config
-            .last_reset_at
-            .saturating_add(EPOCH_DURATION)
-            .saturating_sub(5) // Buffer
-            .le(&clock.unix_timestamp)
+// Warns once per floor if it is usually out of reach within the mining window
+fn warn_eta(min_difficulty: u32, hashrate: f64, buffer_time: u64, warned: &mut Option<u32>) {
+    let window = EPOCH_WINDOW.saturating_sub(buffer_time) as f64;
+    let Some(eta) = eta_secs(min_difficulty, hashrate) else {
+        return;
+    };
+    if eta.le(&window) || (*warned).eq(&Some(min_difficulty)) {
+        return;
     }
+    println!(
+        "{} At {:.0} H/s, difficulty {} takes ~{} on average, longer than the {:.0} sec window ({:.0}% chance within it). Consider --min-difficulty {}.",
+        "WARNING".bold().yellow(),
+        hashrate,
+        min_difficulty,
+        format_eta(eta),
+        window,
+        probability_within(min_difficulty, hashrate, window) * 100.0,
+        reachable_difficulty(hashrate, window)
+    );
+    *warned = Some(min_difficulty);
+}
+
+fn warn_proof_not_updated(wait: ProofWait) {
+    println!(
+        "{} The proof did not update within {}s. Mining the same challenge again.",
+        "WARNING".bold().yellow(),
+        wait.timeout.as_secs()
+    );
+}
+
+fn should_reset(state: &RoundState) -> bool {
+    state
+        .config
+        .last_reset_at
+        .saturating_add(EPOCH_DURATION)
+        .saturating_sub(5) // Buffer
+        .le(&state.now())
+}
 
-    async fn get_cutoff(&self, proof: Proof, buffer_time: u64) -> u64 {
-        let clock = get_clock(&self.rpc_client).await;
-        proof
-            .last_hash_at
-            .saturating_add(60)
-            .saturating_sub(buffer_time as i64)
-            .saturating_sub(clock.unix_timestamp)
-            .max(0) as u64
+// Reports whether any of the signatures of a timed out submission eventually lands
+fn watch_signatures(rpc_client: Arc<RpcClient>, sigs: Vec<Signature>, label: String) {
+    tokio::spawn(async move {
+        let started = Instant::now();
+        while started.elapsed().lt(&LATE_CONFIRM_WINDOW) {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            let Ok(statuses) = rpc_client.get_signature_statuses(&sigs).await else {
+                continue;
+            };
+            for (sig, status) in sigs.iter().zip(statuses.value) {
+                match status {
+                    Some(status) if status.err.is_some() => {
+                        println!(
+                            "{}{} Timed out transaction {} landed but failed: {}",
+                            label,
+                            "INFO".bold().blue(),
+                            sig,
+                            status.err.unwrap()
+                        );
+                        return;
+                    }
+                    Some(status) if status.confirmation_status.is_some() => {
+                        println!(
+                            "{}{} Timed out transaction {} landed after {}s",
+                            label,
+                            "INFO".bold().blue(),
+                            sig,
+                            started.elapsed().as_secs()
+                        );
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        println!(
+            "{}{} Timed out transaction {} never landed",
+            label,
+            "INFO".bold().blue(),
+            sigs.last().map(|sig| sig.to_string()).unwrap_or_default()
+        );
+    });
+}
+
+// When the proof's window to submit its solution closes
+fn window_closes_at(state: &RoundState) -> Instant {
+    let remaining = state
+        .proof
+        .last_hash_at
+        .saturating_add(EPOCH_WINDOW as i64)
+        .saturating_sub(state.now())
+        .max(0);
+    Instant::now() + Duration::from_secs(remaining as u64)
+}
+
+fn get_cutoff(state: &RoundState, buffer_time: u64) -> u64 {
+    state
+        .proof
+        .last_hash_at
+        .saturating_add(EPOCH_WINDOW as i64)
+        .saturating_sub(buffer_time as i64)
+        .saturating_sub(state.now())
+        .max(0) as u64
+}
+
+// Partition the nonce space by worker index, not by core id
+pub fn nonce_start(index: u64, workers: u64) -> u64 {
+    u64::MAX.saturating_div(workers).saturating_mul(index)
+}
+
+// Flags the round as stale once the proof moves on to another challenge
+async fn watch_challenge(
+    rpc_client: Arc<RpcClient>,
+    rpc_pool: RpcPool,
+    authority: Pubkey,
+    mut updates: Option<ProofUpdates>,
+    challenge: [u8; 32],
+    stale: Arc<AtomicBool>,
+) {
+    let address = proof_pubkey(authority);
+    loop {
+        match updates.as_mut() {
+            Some(updates) => updates.wait(CHALLENGE_POLL_INTERVAL).await,
+            None => tokio::time::sleep(CHALLENGE_POLL_INTERVAL).await,
+        }
+
+        // Notified proofs are free, and polling is only an optimisation, so leave the rate
+        // limited rpc to the submission
+        let proof = match updates.as_ref().and_then(ProofUpdates::latest) {
+            Some(proof) => proof,
+            None if rpc_pool.is_backing_off() => continue,
+            None => {
+                let Ok(data) = rpc_client.get_account_data(&address).await else {
+                    continue;
+                };
+                let Ok(proof) = Proof::try_from_bytes(&data) else {
+                    continue;
+                };
+                *proof
+            }
+        };
+        if proof.challenge.ne(&challenge) {
+            stale.store(true, Ordering::Relaxed);
+            return;
+        }
     }
 }
 
+// Refetches the cached busses from shortly before the cutoff until aborted, so they're
+// fresh when the solution is found
+async fn refresh_busses(miner: Miner, rpc_pool: RpcPool, cutoff: Instant) {
+    let ttl = miner.bus_cache.ttl();
+    if ttl.is_zero() {
+        return;
+    }
+    tokio::time::sleep_until(cutoff.checked_sub(ttl / 2).unwrap_or(cutoff).into()).await;
+    loop {
+        if !rpc_pool.is_backing_off() {
+            miner.refresh_busses().await.ok();
+        }
+        tokio::time::sleep(ttl / 2).await;
+    }
+}
+
+// Refetches the cached blockhash while hashing, so the submission doesn't wait on one
+async fn refresh_blockhash(miner: Miner, rpc_pool: RpcPool) {
+    let max_age = miner.send.blockhash_max_age;
+    if max_age.is_zero() {
+        return;
+    }
+    loop {
+        if !rpc_pool.is_backing_off() {
+            miner.refresh_blockhash(&miner.rpc_client).await.ok();
+        }
+        tokio::time::sleep(max_age / 2).await;
+    }
+}
+
+pub async fn wait_for_interrupt(pool: &WorkerPool) {
+    while !pool.interrupt.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+fn save_checkpoint(job: &Job, path: &str) {
+    let checkpoint = Checkpoint {
+        challenge: job.challenge,
+        nonces: job
+            .nonces
+            .iter()
+            .map(|n| n.load(Ordering::Relaxed))
+            .collect(),
+        best: *job.best.lock().unwrap(),
+    };
+    if let Err(err) = checkpoint.save(path) {
+        job.progress_bar.println(format!(
+            "  {} Failed to save checkpoint: {}",
+            "WARNING".bold().yellow(),
+            err
+        ));
+    }
+}
+
+// Base fee plus priority fee, in lamports
+fn estimate_fee(priority_fee: u64, compute_budget: u32) -> u64 {
+    5000 + priority_fee.saturating_mul(compute_budget as u64) / 1_000_000
+}
+
+// Fills in the reward of a submitted round once the proof has updated
+// and appends it to the stats file
+fn record_round(args: &MineArgs, last_round: SubmittedRound, balance: u64) -> RoundRecord {
+    let mut record = last_round.record;
+    record.reward = balance.saturating_sub(last_round.balance);
+    if let Some(path) = &args.stats_file {
+        if let Err(err) = record.append(path, args.stats_format) {
+            println!(
+                "{} Failed to write stats: {}",
+                "WARNING".bold().yellow(),
+                err
+            );
+        }
+    }
+    record
+}
+
+// Ends a `--once` run whose round produced nothing to submit
+fn exit_once(args: &MineArgs, reason: &str) {
+    if args.once {
+        println!("{}", json!({ "landed": false, "error": reason }));
+        std::process::exit(EXIT_NO_SOLUTION);
+    }
+}
+
+fn session_earned(wallets: &[Wallet]) -> u64 {
+    wallets
+        .iter()
+        .map(|w| (w.balance + w.claimed).saturating_sub(w.start_balance.unwrap_or(w.balance)))
+        .sum()
+}
+
+// Reward the program would pay for a solution, before any liveness penalty
+fn estimate_reward(config: Config, balance: u64, difficulty: u32, bus: Option<Bus>) -> u64 {
+    let shift = difficulty.saturating_sub(config.min_difficulty as u32);
+    let reward = config
+        .base_reward_rate
+        .saturating_mul(2u64.saturating_pow(shift));
+    let reward = if config.top_balance.gt(&0) {
+        (reward as f64 * calculate_multiplier(balance, config.top_balance)) as u64
+    } else {
+        reward
+    };
+    bus.map_or(reward, |bus| reward.min(bus.rewards))
+}
+
 fn calculate_multiplier(balance: u64, top_balance: u64) -> f64 {
-    1.0 + (balance as f64 / top_balance as f64).min(1.0f64)
+    1.0 + stake_share(balance, top_balance)
+}
+
+// Fraction of the top stake the balance reaches, capped at all of it
+fn stake_share(balance: u64, top_balance: u64) -> f64 {
+    (balance as f64 / top_balance as f64).min(1.0f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_nonces_are_disjoint_and_cover_the_space() {
+        for workers in [1, 2, 3, 8, 24, 64] {
+            let starts: Vec<u64> = (0..workers).map(|i| nonce_start(i, workers)).collect();
+            assert_eq!(starts[0], 0);
+            for pair in starts.windows(2) {
+                assert!(pair[0].lt(&pair[1]));
+            }
+
+            // Every share is the same size, and the last one runs to u64::MAX with less
+            // than one nonce per worker left over
+            let share = u64::MAX / workers;
+            for (i, start) in starts.iter().enumerate() {
+                assert_eq!(*start, share * i as u64);
+            }
+            let last = starts[workers as usize - 1];
+            assert!((u64::MAX - last - share).lt(&workers));
+        }
+    }
+
+    #[test]
+    fn start_nonces_depend_on_the_worker_index_only() {
+        // Workers pinned to cores 3, 7 and 12 still get the first three shares of three
+        let starts: Vec<u64> = (0..3).map(|i| nonce_start(i, 3)).collect();
+        assert_eq!(starts, vec![0, u64::MAX / 3, u64::MAX / 3 * 2]);
+    }
 }
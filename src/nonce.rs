@@ -0,0 +1,69 @@
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::nonce_utils,
+};
+use solana_program::{hash::Hash, pubkey::Pubkey, system_instruction, system_program};
+use solana_sdk::{nonce::State, signature::Signer};
+
+use crate::{send_and_confirm::ComputeBudget, Miner};
+
+// Seed of the nonce account derived from the signer
+const NONCE_SEED: &str = "ore-nonce";
+
+impl Miner {
+    pub async fn nonce(&self) {
+        let signer = self.signer();
+        let address =
+            Pubkey::create_with_seed(&signer.pubkey(), NONCE_SEED, &system_program::id()).unwrap();
+
+        // Skip if the account already exists
+        if self.rpc_client.get_account(&address).await.is_ok() {
+            println!("Nonce account: {}", address);
+            return;
+        }
+
+        // Create the account, with its rent paid by the fee payer
+        let lamports = self
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(State::size())
+            .await
+            .expect("Failed to fetch rent");
+        let ixs = system_instruction::create_nonce_account_with_seed(
+            &self.fee_payer().pubkey(),
+            &address,
+            &signer.pubkey(),
+            NONCE_SEED,
+            &signer.pubkey(),
+            lamports,
+        );
+        let mut miner = self.clone();
+        miner.send.nonce_account = None;
+        match miner
+            .send_and_confirm(&ixs, ComputeBudget::Dynamic, false)
+            .await
+        {
+            Ok(_) => {
+                println!("Nonce account: {}", address);
+                println!("Pass --nonce-account {} to send with it", address);
+            }
+            Err(err) => err.report("nonce account creation"),
+        }
+    }
+
+    // Blockhash stored in a nonce account
+    pub async fn nonce_blockhash(&self, address: Pubkey) -> ClientResult<Hash> {
+        let to_client_error = |err: nonce_utils::Error| ClientError {
+            request: None,
+            kind: ClientErrorKind::Custom(err.to_string()),
+        };
+        let account = nonce_utils::get_account_with_commitment(
+            &self.rpc_client,
+            &address,
+            self.rpc_client.commitment(),
+        )
+        .await
+        .map_err(to_client_error)?;
+        let data = nonce_utils::data_from_account(&account).map_err(to_client_error)?;
+        Ok(data.blockhash())
+    }
+}
@@ -0,0 +1,76 @@
+use std::io;
+
+// Socket (physical package) a core belongs to
+#[cfg(target_os = "linux")]
+pub fn socket_of(core_id: usize) -> Option<usize> {
+    std::fs::read_to_string(format!(
+        "/sys/devices/system/cpu/cpu{}/topology/physical_package_id",
+        core_id
+    ))
+    .ok()?
+    .trim()
+    .parse()
+    .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn socket_of(_core_id: usize) -> Option<usize> {
+    None
+}
+
+// Physical core (socket and core id) a logical core belongs to
+#[cfg(target_os = "linux")]
+pub fn physical_core_of(core_id: usize) -> Option<(usize, usize)> {
+    let core = std::fs::read_to_string(format!(
+        "/sys/devices/system/cpu/cpu{}/topology/core_id",
+        core_id
+    ))
+    .ok()?
+    .trim()
+    .parse()
+    .ok()?;
+    Some((socket_of(core_id)?, core))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn physical_core_of(_core_id: usize) -> Option<(usize, usize)> {
+    None
+}
+
+// Spreads the calling thread's future allocations across every online node
+// instead of placing them on the node it runs on
+#[cfg(target_os = "linux")]
+pub fn interleave_memory() -> io::Result<()> {
+    use std::str::FromStr;
+
+    use crate::pool::CoreList;
+
+    const MPOL_INTERLEAVE: libc::c_long = 3;
+    let online = std::fs::read_to_string("/sys/devices/system/node/online")?;
+    let nodes = CoreList::from_str(online.trim())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut mask = vec![0u64; nodes.0.iter().max().unwrap_or(&0) / 64 + 1];
+    for node in nodes.0 {
+        mask[node / 64] |= 1 << (node % 64);
+    }
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            MPOL_INTERLEAVE,
+            mask.as_ptr(),
+            mask.len() * 64 + 1,
+        )
+    };
+    if result.ne(&0) {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn interleave_memory() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "numa memory policies are not supported on this platform",
+    ))
+}
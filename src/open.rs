@@ -1,22 +1,74 @@
+use colored::*;
 use solana_sdk::signature::Signer;
 
-use crate::{send_and_confirm::ComputeBudget, utils::proof_pubkey, Miner};
+use crate::{
+    cluster::for_program,
+    send_and_confirm::ComputeBudget,
+    utils::{proof_pubkey, try_get_proof},
+    Miner,
+};
 
 impl Miner {
-    pub async fn open(&self) {
+    // Returns true once the proof account exists
+    pub async fn open(&self) -> bool {
         // Return early if miner is already registered
         let signer = self.signer();
         let fee_payer = self.fee_payer();
-        let proof_address = proof_pubkey(signer.pubkey());
-        if self.rpc_client.get_account(&proof_address).await.is_ok() {
-            return;
+        let authority = self.authority();
+        let proof_address = proof_pubkey(authority);
+        let proof = match try_get_proof(&self.rpc_client, authority).await {
+            Ok(proof) => proof,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return false;
+            }
+        };
+        if let Some(proof) = proof {
+            println!("Found proof account {}", proof_address);
+
+            // A delegated miner can only mine a proof that names it as the miner
+            if proof.miner.ne(&signer.pubkey()) {
+                println!(
+                    "{} Keypair {} is not the miner of proof account {} (miner is {}). The authority must delegate mining to it first.",
+                    "ERROR".bold().red(),
+                    signer.pubkey(),
+                    proof_address,
+                    proof.miner
+                );
+                return false;
+            }
+            return true;
+        }
+
+        // Only the authority can open its proof account
+        if !self.require_authority("open the proof account") {
+            return false;
         }
 
         // Sign and send transaction.
-        println!("Generating challenge...");
-        let ix = ore_api::instruction::open(signer.pubkey(), signer.pubkey(), fee_payer.pubkey());
-        self.send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
+        println!("No proof account found. Generating challenge...");
+        let ix = for_program(
+            ore_api::instruction::open(signer.pubkey(), signer.pubkey(), fee_payer.pubkey()),
+            signer.pubkey(),
+        );
+        match self
+            .send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
             .await
-            .ok();
+        {
+            Ok(_) => return true,
+            Err(err) => err.report("open"),
+        }
+
+        // Another process may have opened the account in the meantime
+        if self.rpc_client.get_account(&proof_address).await.is_ok() {
+            println!("Proof account {} was opened elsewhere", proof_address);
+            return true;
+        }
+        println!(
+            "{} Failed to open proof account {}",
+            "ERROR".bold().red(),
+            proof_address
+        );
+        false
     }
 }
@@ -0,0 +1,45 @@
+use std::str::FromStr;
+
+use colored::*;
+use solana_program::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::{
+    args::PdasArgs,
+    cluster::program_id,
+    utils::{
+        bus_pubkeys, config_pubkey, mint_pubkey, proof_pubkey, treasury_pubkey,
+        treasury_tokens_pubkey,
+    },
+    Miner,
+};
+
+impl Miner {
+    pub fn pdas(&self, args: PdasArgs) {
+        let authority = match args.authority {
+            Some(authority) => match Pubkey::from_str(&authority) {
+                Ok(authority) => authority,
+                Err(_) => {
+                    println!("Invalid address: {:?}", authority);
+                    return;
+                }
+            },
+            None => self.authority(),
+        };
+        println!("{}: {}", "Program".bold(), program_id());
+        println!("{}: {}", "Authority".bold(), authority);
+        println!("{}: {}", "Proof".bold(), proof_pubkey(authority));
+        println!(
+            "{}: {}",
+            "Token account".bold(),
+            get_associated_token_address(&authority, &mint_pubkey())
+        );
+        println!("{}: {}", "Config".bold(), config_pubkey());
+        println!("{}: {}", "Mint".bold(), mint_pubkey());
+        println!("{}: {}", "Treasury".bold(), treasury_pubkey());
+        println!("{}: {}", "Treasury tokens".bold(), treasury_tokens_pubkey());
+        for (id, address) in bus_pubkeys().iter().enumerate() {
+            println!("{}: {}", format!("Bus {}", id).bold(), address);
+        }
+    }
+}
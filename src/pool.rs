@@ -0,0 +1,909 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use colored::*;
+use core_affinity::CoreId;
+use drillx::{equix, Hash};
+use indicatif::ProgressBar;
+
+use crate::{
+    args::MineArgs,
+    core_classes::efficiency_cores,
+    eta::{eta_secs, format_eta},
+    mine::nonce_start,
+    numa::{interleave_memory, physical_core_of},
+    thermal::{Throttle, POWER_CYCLE},
+};
+
+// Difficulties at or above the last bucket are tallied together
+pub const HISTOGRAM_SIZE: usize = 64;
+
+// How long a stopped round waits for the remaining workers to return
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+// The best hash found by a worker (or by the whole pool) in a round
+pub struct WorkerResult {
+    pub nonce: u64,
+    pub difficulty: u32,
+    pub hash: Hash,
+    pub histogram: [u64; HISTOGRAM_SIZE],
+}
+
+impl WorkerResult {
+    pub fn new(nonce: u64) -> Self {
+        Self {
+            nonce,
+            difficulty: 0,
+            hash: Hash::default(),
+            histogram: [0; HISTOGRAM_SIZE],
+        }
+    }
+
+    // Returns true if the hash improved on the best
+    pub fn record(&mut self, nonce: u64, hx: Hash) -> bool {
+        let difficulty = hx.difficulty();
+        self.histogram[(difficulty as usize).min(HISTOGRAM_SIZE - 1)] += 1;
+        if difficulty.gt(&self.difficulty) {
+            self.nonce = nonce;
+            self.difficulty = difficulty;
+            self.hash = hx;
+            return true;
+        }
+        false
+    }
+
+    pub fn merge(&mut self, other: WorkerResult) {
+        for (a, b) in self.histogram.iter_mut().zip(other.histogram) {
+            *a += b;
+        }
+        if other.difficulty.gt(&self.difficulty) {
+            self.nonce = other.nonce;
+            self.difficulty = other.difficulty;
+            self.hash = other.hash;
+        }
+    }
+}
+
+// A single round of hashing, shared by every worker in the pool
+pub struct Job {
+    pub challenge: [u8; 32],
+    pub cutoff_time: u64,
+    pub min_difficulty: u32,
+    pub continue_after_min: bool,
+    pub max_hash_time: Option<u64>,
+    pub timer: Instant,
+    pub progress_bar: ProgressBar,
+    pub interrupt: Arc<AtomicBool>,
+    pub stale: Arc<AtomicBool>,
+    pub stop_flag: AtomicBool,
+    pub floor_flag: AtomicBool,
+    pub cutoff_flag: AtomicBool,
+    pub deadline_flag: AtomicBool,
+    pub stall_flag: AtomicBool,
+    pub hashrate: AtomicU64,
+    pub gpu_hash_count: AtomicU64,
+    pub gpu_hashrate: AtomicU64,
+    pub nonces: Vec<AtomicU64>,
+    pub ranges: Vec<(u64, u64)>,
+    pub worker_hashes: Vec<AtomicU64>,
+    pub global_best: AtomicU32,
+    pub best: Mutex<Option<(u64, [u8; 16], u32)>>,
+}
+
+impl Job {
+    pub fn new(
+        challenge: [u8; 32],
+        cutoff_time: u64,
+        min_difficulty: u32,
+        args: &MineArgs,
+        progress_bar: ProgressBar,
+        pool: &WorkerPool,
+        start_nonces: Vec<u64>,
+    ) -> Self {
+        Self {
+            challenge,
+            cutoff_time,
+            min_difficulty,
+            continue_after_min: args.continue_after_min,
+            max_hash_time: args.max_hash_time,
+            timer: Instant::now(),
+            progress_bar,
+            interrupt: pool.interrupt.clone(),
+            stale: pool.stale.clone(),
+            stop_flag: AtomicBool::new(false),
+            floor_flag: AtomicBool::new(false),
+            cutoff_flag: AtomicBool::new(false),
+            deadline_flag: AtomicBool::new(false),
+            stall_flag: AtomicBool::new(false),
+            hashrate: AtomicU64::new(0),
+            gpu_hash_count: AtomicU64::new(0),
+            gpu_hashrate: AtomicU64::new(0),
+            worker_hashes: start_nonces.iter().map(|_| AtomicU64::new(0)).collect(),
+            nonces: start_nonces.into_iter().map(AtomicU64::new).collect(),
+            ranges: pool.ranges(),
+            global_best: AtomicU32::new(0),
+            best: Mutex::new(None),
+        }
+    }
+
+    // Hashes computed by the cpu workers so far
+    pub fn hash_count(&self) -> u64 {
+        self.worker_hashes
+            .iter()
+            .map(|h| h.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    // Spinner message for the current progress
+    pub fn status(&self, gpu: bool, throttle: &Throttle) -> String {
+        let best = self.global_best.load(Ordering::Relaxed);
+        let hashrate =
+            self.hashrate.load(Ordering::Relaxed) + self.gpu_hashrate.load(Ordering::Relaxed);
+        format!(
+            "MIN_DIFFICULTY: {} > {} Mining... {} H/s{}{}, {} hashes ({} sec remaining){}{}",
+            self.min_difficulty,
+            best,
+            self.hashrate.load(Ordering::Relaxed),
+            if gpu {
+                format!(" + GPU {} H/s", self.gpu_hashrate.load(Ordering::Relaxed))
+            } else {
+                "".to_string()
+            },
+            match eta_secs(self.min_difficulty, hashrate as f64) {
+                Some(eta) if best.lt(&self.min_difficulty) =>
+                    format!(", ~{} to floor", format_eta(eta)),
+                _ => "".to_string(),
+            },
+            self.hash_count(),
+            self.cutoff_time
+                .saturating_sub(self.timer.elapsed().as_secs()),
+            if throttle.is_active() {
+                " [THROTTLED]"
+            } else {
+                ""
+            },
+            if throttle.duty_cycle().lt(&100) {
+                format!(" [duty {}%]", throttle.duty_cycle())
+            } else {
+                "".to_string()
+            },
+        )
+    }
+
+    // Publishes a worker's best hash if it beats every other worker,
+    // returns true if it did
+    fn record_global(&self, result: &WorkerResult) -> bool {
+        if result
+            .difficulty
+            .le(&self.global_best.load(Ordering::Relaxed))
+        {
+            return false;
+        }
+        if self
+            .global_best
+            .fetch_max(result.difficulty, Ordering::Relaxed)
+            .ge(&result.difficulty)
+        {
+            return false;
+        }
+        self.record_best(result);
+        true
+    }
+
+    // Publishes an improved best hash for checkpointing
+    fn record_best(&self, result: &WorkerResult) {
+        let mut best = self.best.lock().unwrap();
+        if best.map_or(true, |(_, _, d)| result.difficulty.gt(&d)) {
+            *best = Some((result.nonce, result.hash.d, result.difficulty));
+        }
+    }
+
+    // Returns true if the worker should stop hashing
+    fn should_stop(&self) -> bool {
+        if self.stop_flag.load(Ordering::Relaxed)
+            || self.interrupt.load(Ordering::Relaxed)
+            || self.stale.load(Ordering::Relaxed)
+        {
+            return true;
+        }
+
+        // Exit if cutoff time has passed
+        // (when continuing after min, only once the floor has been reached)
+        let elapsed = self.timer.elapsed().as_secs();
+        if elapsed.ge(&self.cutoff_time)
+            && (!self.continue_after_min || self.floor_flag.load(Ordering::Relaxed))
+        {
+            self.cutoff_flag.store(true, Ordering::Relaxed);
+            self.stop_flag.store(true, Ordering::Relaxed);
+            return true;
+        }
+
+        // Exit if the hard time limit has passed, regardless of the floor
+        if self.max_hash_time.is_some_and(|t| elapsed.ge(&t)) {
+            self.deadline_flag.store(true, Ordering::Relaxed);
+            self.stop_flag.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    // Records the worker's best difficulty and returns true if the worker should stop hashing
+    fn reached_floor(&self, best_difficulty: u32) -> bool {
+        if best_difficulty.lt(&self.min_difficulty) {
+            return false;
+        }
+        self.floor_flag.store(true, Ordering::Relaxed);
+        if !self.continue_after_min || self.timer.elapsed().as_secs().ge(&self.cutoff_time) {
+            self.stop_flag.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+}
+
+// A list of core ids such as `0,1,16-23`
+#[derive(Clone, Debug)]
+pub struct CoreList(pub Vec<usize>);
+
+impl FromStr for CoreList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ids = vec![];
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let parse = |id: &str| {
+                id.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid core id `{}`", id))
+            };
+            match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start.gt(&end) {
+                        return Err(format!("invalid core range `{}`", part));
+                    }
+                    ids.extend(start..=end);
+                }
+                None => ids.push(parse(part)?),
+            }
+        }
+        if ids.is_empty() {
+            return Err("no core ids given".to_string());
+        }
+        Ok(Self(ids))
+    }
+}
+
+// Picks the cores to pin workers to. Included cores are used as given,
+// otherwise the first `cores` cores that are not excluded.
+// With `physical_only`, hyperthread siblings of an already picked core are skipped,
+// and efficiency cores of hybrid cpus are skipped unless included or `use_efficiency` is set.
+// Efficiency cores are only known when the kernel reports the core types, or when
+// `calibrate` (--calibrate-cores) measures them.
+pub fn select_cores(
+    cores: u64,
+    include: Option<&CoreList>,
+    exclude: Option<&CoreList>,
+    physical_only: bool,
+    use_efficiency: bool,
+    calibrate: bool,
+) -> Result<Vec<CoreId>, String> {
+    let core_ids = core_affinity::get_core_ids().unwrap_or_default();
+    let known = |list: &CoreList| match list
+        .0
+        .iter()
+        .find(|id| !core_ids.iter().any(|c| c.id.eq(*id)))
+    {
+        Some(id) => Err(format!(
+            "Core {} does not exist (available: 0-{})",
+            id,
+            core_ids.len().saturating_sub(1)
+        )),
+        None => Ok(()),
+    };
+    let excluded = |id: usize| exclude.is_some_and(|list| list.0.contains(&id));
+    if let Some(list) = exclude {
+        known(list)?;
+    }
+    if let Some(list) = include {
+        known(list)?;
+    }
+    let mut candidates = core_ids
+        .into_iter()
+        .filter(|c| include.map_or(true, |list| list.0.contains(&c.id)) && !excluded(c.id))
+        .collect::<Vec<_>>();
+    if physical_only {
+        match candidates
+            .iter()
+            .map(|c| physical_core_of(c.id))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(physical) => {
+                let mut seen = vec![];
+                candidates = candidates
+                    .into_iter()
+                    .zip(physical)
+                    .filter(|(_, p)| {
+                        let first = !seen.contains(p);
+                        seen.push(*p);
+                        first
+                    })
+                    .map(|(c, _)| c)
+                    .collect();
+            }
+            None => println!(
+                "{} Could not detect the core topology. Using hyperthread siblings too.",
+                "WARNING".bold().yellow()
+            ),
+        }
+    }
+    if include.is_none() && !use_efficiency {
+        let efficiency = efficiency_cores(calibrate);
+        let skipped = candidates
+            .iter()
+            .filter(|c| efficiency.contains(&c.id))
+            .count();
+        if skipped.gt(&0) && skipped.lt(&candidates.len()) {
+            println!(
+                "Skipping {} efficiency cores. Pass --use-efficiency-cores to mine on them too.",
+                skipped
+            );
+            candidates.retain(|c| !efficiency.contains(&c.id));
+        }
+    }
+    let selected = match include {
+        Some(_) => candidates,
+        None => candidates.into_iter().take(cores as usize).collect(),
+    };
+    if selected.is_empty() {
+        return Err("No cores left to mine on".to_string());
+    }
+    Ok(selected)
+}
+
+// Hashes computed by one cpu worker during a round
+#[derive(Clone, Debug)]
+pub struct CoreStats {
+    pub core_id: usize,
+    pub hashes: u64,
+    pub hashrate: f64,
+}
+
+// How the worker threads are set up
+#[derive(Clone, Copy, Debug)]
+pub struct WorkerConfig {
+    pub gpu: bool,
+    pub nice: Option<i32>,
+    pub numa_interleave: bool,
+    pub machine_id: u64,
+    pub machine_count: u64,
+}
+
+// Result of a round, sent back by the worker of a slot
+type SlotResult = (usize, Arc<Job>, WorkerResult);
+
+// Hashing threads created once and reused across rounds
+pub struct WorkerPool {
+    senders: Mutex<Vec<Sender<Arc<Job>>>>,
+    results: Receiver<SlotResult>,
+    result_sender: Sender<SlotResult>,
+    cores: Vec<CoreId>,
+    config: WorkerConfig,
+    slices: u64,
+    machine_id: u64,
+    machine_count: u64,
+    core_ids: Vec<usize>,
+    slow_streaks: Mutex<Vec<u32>>,
+    range: Mutex<Option<(u64, u64)>>,
+    pub gpu: bool,
+    pub interrupt: Arc<AtomicBool>,
+    pub stale: Arc<AtomicBool>,
+    pub throttle: Arc<Throttle>,
+    pub current: Arc<Mutex<Option<Arc<Job>>>>,
+}
+
+impl WorkerPool {
+    pub fn new(core_ids: Vec<CoreId>, config: WorkerConfig, throttle: Arc<Throttle>) -> Self {
+        let gpu = config.gpu;
+        let workers = core_ids.len() as u64;
+        let slices = if gpu { workers + 1 } else { workers };
+        let (result_sender, results) = channel();
+        let mut pool = Self {
+            senders: Mutex::new(vec![]),
+            results,
+            result_sender,
+            cores: core_ids.clone(),
+            config,
+            slices,
+            machine_id: config.machine_id,
+            machine_count: config.machine_count.max(1),
+            core_ids: core_ids.iter().map(|c| c.id).collect(),
+            slow_streaks: Mutex::new(vec![0; workers as usize]),
+            range: Mutex::new(None),
+            gpu,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            stale: Arc::new(AtomicBool::new(false)),
+            throttle,
+            current: Arc::new(Mutex::new(None)),
+        };
+
+        // Dispatch the last nonce slice to the gpu
+        let slots = if cfg!(feature = "gpu") && gpu {
+            slices
+        } else {
+            workers
+        };
+        let senders = (0..slots as usize).map(|i| pool.spawn_worker(i)).collect();
+        pool.senders = Mutex::new(senders);
+        pool
+    }
+
+    // Starts the worker of a slot on a new thread, returning the channel its jobs go to
+    fn spawn_worker(&self, index: usize) -> Sender<Arc<Job>> {
+        let (sender, jobs) = channel();
+        let results = self.result_sender.clone();
+        match self.cores.get(index).copied() {
+            Some(core_id) => {
+                let config = self.config;
+                let throttle = self.throttle.clone();
+                std::thread::spawn(move || {
+                    cpu_worker(index, core_id, config, throttle, jobs, results)
+                });
+            }
+            #[cfg(feature = "gpu")]
+            None => {
+                std::thread::spawn(move || gpu_worker(index, jobs, results));
+            }
+            #[cfg(not(feature = "gpu"))]
+            None => unreachable!("no gpu slot without the gpu feature"),
+        }
+        sender
+    }
+
+    pub fn core_ids(&self) -> Vec<usize> {
+        self.core_ids.clone()
+    }
+
+    pub fn slots(&self) -> usize {
+        self.senders.lock().unwrap().len()
+    }
+
+    // Restricts fresh challenges to the nonces in [start, end), as assigned by a coordinator
+    pub fn set_range(&self, range: Option<(u64, u64)>) {
+        *self.range.lock().unwrap() = range;
+    }
+
+    // Nonces [start, end) of each slot
+    // (each worker's slice is split again between machines, or taken from the assigned range)
+    pub fn ranges(&self) -> Vec<(u64, u64)> {
+        slot_ranges(
+            self.slots() as u64,
+            self.slices,
+            (self.machine_id, self.machine_count),
+            *self.range.lock().unwrap(),
+        )
+    }
+
+    // Initial nonce of each slot when starting a fresh challenge
+    pub fn start_nonces(&self) -> Vec<u64> {
+        self.ranges().into_iter().map(|(start, _)| start).collect()
+    }
+
+    // Hashes and hashrate of each cpu worker in the job
+    pub fn core_stats(&self, job: &Job) -> Vec<CoreStats> {
+        let elapsed = job.timer.elapsed().as_secs_f64().max(f64::EPSILON);
+        self.core_ids
+            .iter()
+            .zip(job.worker_hashes.iter())
+            .map(|(core_id, hashes)| {
+                let hashes = hashes.load(Ordering::Relaxed);
+                CoreStats {
+                    core_id: *core_id,
+                    hashes,
+                    hashrate: hashes as f64 / elapsed,
+                }
+            })
+            .collect()
+    }
+
+    // Counts consecutive rounds each core ran below the given percentage of the median hashrate
+    // and returns the streak of every core that is currently slow
+    pub fn track_slow_cores(&self, stats: &[CoreStats], threshold: f64) -> Vec<(usize, f64, u32)> {
+        let mut rates = stats.iter().map(|c| c.hashrate).collect::<Vec<_>>();
+        rates.sort_by(|a, b| a.total_cmp(b));
+        let Some(median) = rates.get(rates.len() / 2).copied().filter(|m| m.gt(&0.0)) else {
+            return vec![];
+        };
+        let mut streaks = self.slow_streaks.lock().unwrap();
+        let mut slow = vec![];
+        for (streak, core) in streaks.iter_mut().zip(stats) {
+            let percent = 100.0 * core.hashrate / median;
+            if percent.lt(&threshold) {
+                *streak += 1;
+                slow.push((core.core_id, percent, *streak));
+            } else {
+                *streak = 0;
+            }
+        }
+        slow
+    }
+
+    // Wakes every worker with the job and merges their results
+    pub fn run(&self, job: Arc<Job>) -> WorkerResult {
+        *self.current.lock().unwrap() = Some(job.clone());
+        let slots = {
+            let senders = self.senders.lock().unwrap();
+            for sender in senders.iter() {
+                sender.send(job.clone()).ok();
+            }
+            senders.len()
+        };
+        // Stop waiting on workers that do not return shortly after the round stops
+        // (late results of earlier rounds are discarded)
+        let mut best = WorkerResult::new(0);
+        let mut returned = vec![false; slots];
+        let mut pending = slots;
+        let mut stopped_at = None;
+        while pending.gt(&0) {
+            match self.results.recv_timeout(Duration::from_millis(100)) {
+                Ok((index, from, result)) => {
+                    if Arc::ptr_eq(&from, &job) && !returned[index] {
+                        returned[index] = true;
+                        best.merge(result);
+                        pending -= 1;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if job.should_stop()
+                        && stopped_at
+                            .get_or_insert_with(Instant::now)
+                            .elapsed()
+                            .ge(&STOP_GRACE)
+                    {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        // Replace wedged workers, so the next round doesn't wait on them again. Dropping the
+        // old channel lets a worker that comes back exit.
+        let mut senders = self.senders.lock().unwrap();
+        for (index, _) in returned
+            .iter()
+            .enumerate()
+            .filter(|(_, returned)| !**returned)
+        {
+            let worker = match self.core_ids.get(index) {
+                Some(core_id) => format!("Core {}", core_id),
+                None => "The GPU".to_string(),
+            };
+            job.progress_bar.println(format!(
+                "{} {} didn't stop within {} sec. Replacing its thread.",
+                "WARNING".bold().yellow(),
+                worker,
+                STOP_GRACE.as_secs()
+            ));
+            senders[index] = self.spawn_worker(index);
+        }
+        drop(senders);
+        *self.current.lock().unwrap() = None;
+        best
+    }
+}
+
+// Nonces [start, end) of each of the slots. The space, or the assigned range, is cut into slices
+// per machine, and machine `id` of `count` takes every count-th slice starting at its id.
+// An assigned range narrower than the slots leaves all but the last of them empty.
+fn slot_ranges(
+    slots: u64,
+    slices: u64,
+    (machine_id, machine_count): (u64, u64),
+    range: Option<(u64, u64)>,
+) -> Vec<(u64, u64)> {
+    if let Some((start, end)) = range {
+        let step = end.saturating_sub(start) / slots.max(1);
+        return (0..slots)
+            .map(|i| {
+                let slot_end = if (i + 1).eq(&slots) {
+                    end
+                } else {
+                    start + step * (i + 1)
+                };
+                (start + step * i, slot_end)
+            })
+            .collect();
+    }
+    let count = slices * machine_count;
+    (0..slots)
+        .map(|i| {
+            let index = i * machine_count + machine_id;
+            let end = if (index + 1).eq(&count) {
+                u64::MAX
+            } else {
+                nonce_start(index + 1, count)
+            };
+            (nonce_start(index, count), end)
+        })
+        .collect()
+}
+
+fn cpu_worker(
+    index: usize,
+    core_id: CoreId,
+    config: WorkerConfig,
+    throttle: Arc<Throttle>,
+    jobs: Receiver<Arc<Job>>,
+    results: Sender<SlotResult>,
+) {
+    // Pin to core
+    let _ = core_affinity::set_for_current(core_id);
+
+    // Lower priority so other workloads stay responsive
+    if let Some(nice) = config.nice {
+        if let Err(err) = set_thread_nice(nice) {
+            if index.eq(&0) {
+                println!(
+                    "{} Failed to lower thread priority: {}",
+                    "WARNING".bold().yellow(),
+                    err
+                );
+            }
+        }
+    }
+
+    // Allocate solver memory after pinning, so it lands on the local numa node,
+    // and keep it alive across rounds
+    if config.numa_interleave {
+        if let Err(err) = interleave_memory() {
+            if index.eq(&0) {
+                println!(
+                    "{} Failed to interleave memory: {}",
+                    "WARNING".bold().yellow(),
+                    err
+                );
+            }
+        }
+    }
+    let mut memory = equix::SolverMemory::new();
+
+    // Park until the next job arrives
+    for job in jobs.iter() {
+        let (start, end) = job.ranges[index];
+
+        // A range narrower than the workers leaves some of them nothing to hash
+        if start.ge(&end) {
+            if results
+                .send((index, job, WorkerResult::new(start)))
+                .is_err()
+            {
+                break;
+            }
+            continue;
+        }
+        let mut nonce = job.nonces[index].load(Ordering::Relaxed);
+        if nonce.lt(&start) || nonce.ge(&end) {
+            nonce = start;
+        }
+        let mut best = WorkerResult::new(nonce);
+        let mut hashes = 0;
+        let mut cycle_start = Instant::now();
+        while !job.should_stop() {
+            // Create hash
+            let hash_start = Instant::now();
+            if let Ok(hx) =
+                drillx::hash_with_memory(&mut memory, &job.challenge, &nonce.to_le_bytes())
+            {
+                hashes += 1;
+                job.worker_hashes[index].store(hashes, Ordering::Relaxed);
+                job.nonces[index].store(nonce, Ordering::Relaxed);
+                if best.record(nonce, hx) && job.record_global(&best) {
+                    // Exit loop if difficulty meets or exceeds min_difficulty
+                    if job.reached_floor(best.difficulty) {
+                        break;
+                    }
+
+                    // Show the improvement right away
+                    job.progress_bar
+                        .set_message(job.status(config.gpu, &throttle));
+                }
+            }
+            // Stop at the end of the range instead of running into the next worker's
+            nonce += 1;
+            if nonce.ge(&end) {
+                job.progress_bar.println(format!(
+                    "{} Core {} exhausted its nonce range",
+                    "WARNING".bold().yellow(),
+                    core_id.id
+                ));
+                break;
+            }
+
+            // Sleep as long as the hash took while the cpu is too hot
+            if throttle.is_active() {
+                std::thread::sleep(hash_start.elapsed());
+            }
+
+            // Hash for the power limit's share of each cycle and sleep for the rest
+            let power_limit = throttle.power_limit();
+            if power_limit.lt(&100) {
+                let active = POWER_CYCLE * power_limit / 100;
+                if cycle_start.elapsed().ge(&active) {
+                    std::thread::sleep(POWER_CYCLE - active);
+                    cycle_start = Instant::now();
+                }
+            }
+        }
+
+        // Return the best nonce
+        if results.send((index, job, best)).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(feature = "gpu")]
+fn gpu_worker(index: usize, jobs: Receiver<Arc<Job>>, results: Sender<SlotResult>) {
+    // Kept across rounds, the batch buffer is large
+    let mut buffer = crate::gpu::batch_buffer();
+
+    // Park until the next job arrives
+    for job in jobs.iter() {
+        let (start, end) = job.ranges[index];
+
+        // A range narrower than the workers leaves some of them nothing to hash
+        if start.ge(&end) {
+            if results
+                .send((index, job, WorkerResult::new(start)))
+                .is_err()
+            {
+                break;
+            }
+            continue;
+        }
+        let mut nonce = job.nonces[index].load(Ordering::Relaxed);
+        if nonce.lt(&start) || nonce.ge(&end) {
+            nonce = start;
+        }
+        let mut best = WorkerResult::new(nonce);
+        while !job.should_stop() {
+            // Hash a batch on the device
+            let mut improved = false;
+            for (n, hx) in crate::gpu::hash_batch(&mut buffer, &job.challenge, nonce) {
+                improved |= best.record(n, hx);
+            }
+
+            // Exit loop if difficulty meets or exceeds min_difficulty
+            if improved && job.record_global(&best) && job.reached_floor(best.difficulty) {
+                break;
+            }
+            job.gpu_hash_count
+                .fetch_add(crate::gpu::batch_size(), Ordering::Relaxed);
+            nonce = nonce.saturating_add(crate::gpu::batch_size());
+            job.nonces[index].store(nonce, Ordering::Relaxed);
+            if nonce.ge(&end) {
+                job.progress_bar.println(format!(
+                    "{} GPU exhausted its nonce range",
+                    "WARNING".bold().yellow()
+                ));
+                break;
+            }
+        }
+
+        // Return the best nonce
+        if results.send((index, job, best)).is_err() {
+            break;
+        }
+    }
+}
+
+// Applies a niceness level to the calling thread only
+#[cfg(target_os = "linux")]
+fn set_thread_nice(nice: i32) -> std::io::Result<()> {
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) }.ne(&0) {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Windows has no niceness, so map it onto the thread priority classes
+#[cfg(windows)]
+fn set_thread_nice(nice: i32) -> std::io::Result<()> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_IDLE,
+        THREAD_PRIORITY_LOWEST, THREAD_PRIORITY_NORMAL,
+    };
+    let priority = match nice {
+        i32::MIN..=0 => THREAD_PRIORITY_NORMAL,
+        1..=9 => THREAD_PRIORITY_BELOW_NORMAL,
+        10..=18 => THREAD_PRIORITY_LOWEST,
+        _ => THREAD_PRIORITY_IDLE,
+    };
+    if unsafe { SetThreadPriority(GetCurrentThread(), priority) }.eq(&0) {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn set_thread_nice(_nice: i32) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "per-thread priority is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sorted ranges must chain from 0 to u64::MAX without gaps or overlaps
+    fn assert_covers_space(mut ranges: Vec<(u64, u64)>) {
+        ranges.sort();
+        assert_eq!(ranges.first().unwrap().0, 0);
+        assert_eq!(ranges.last().unwrap().1, u64::MAX);
+        for pair in ranges.windows(2) {
+            assert!(pair[0].0.lt(&pair[0].1));
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn worker_ranges_cover_the_space() {
+        for workers in [1, 3, 64] {
+            let ranges = slot_ranges(workers, workers, (0, 1), None);
+            assert_eq!(ranges.len() as u64, workers);
+            assert_eq!(ranges.last().unwrap().1, u64::MAX);
+            assert_covers_space(ranges);
+        }
+    }
+
+    #[test]
+    fn machines_split_the_space() {
+        for (workers, machines) in [(1, 2), (3, 4), (64, 3)] {
+            let ranges: Vec<(u64, u64)> = (0..machines)
+                .flat_map(|id| slot_ranges(workers, workers, (id, machines), None))
+                .collect();
+            assert_eq!(ranges.len() as u64, workers * machines);
+            assert_covers_space(ranges);
+        }
+    }
+
+    #[test]
+    fn narrow_range_leaves_workers_empty() {
+        for (workers, width) in [(4, 0), (4, 1), (4, 3), (64, 10)] {
+            let ranges = slot_ranges(workers, workers, (0, 1), Some((1000, 1000 + width)));
+            assert_eq!(ranges.len() as u64, workers);
+            let hashed: Vec<(u64, u64)> = ranges
+                .into_iter()
+                .filter(|(start, end)| start.lt(end))
+                .collect();
+            let nonces: u64 = hashed.iter().map(|(start, end)| end - start).sum();
+            assert_eq!(nonces, width);
+            for (start, end) in hashed {
+                assert!(start.ge(&1000) && end.le(&(1000 + width)));
+            }
+        }
+    }
+
+    #[test]
+    fn assigned_range_is_split_between_workers() {
+        for workers in [1, 3, 64] {
+            let ranges = slot_ranges(workers, workers, (0, 1), Some((1000, 1_000_000)));
+            assert_eq!(ranges.first().unwrap().0, 1000);
+            assert_eq!(ranges.last().unwrap().1, 1_000_000);
+            for pair in ranges.windows(2) {
+                assert_eq!(pair[0].1, pair[1].0);
+            }
+        }
+    }
+}
@@ -1,25 +1,42 @@
 use std::str::FromStr;
 
+use colored::*;
 use ore_api::consts::TOKEN_DECIMALS;
 use solana_program::pubkey::Pubkey;
-use solana_sdk::signature::Signer;
 use spl_token::amount_to_ui_amount;
 
 use crate::{
     args::ProofArgs,
-    utils::{get_proof, proof_pubkey},
+    utils::{get_proof_at, proof_pubkey},
     Miner,
 };
 
 impl Miner {
     pub async fn proof(&self, args: ProofArgs) {
-        let signer = self.signer();
-        let address = if let Some(address) = args.address {
-            Pubkey::from_str(&address).unwrap()
-        } else {
-            proof_pubkey(signer.pubkey())
+        let address = match (args.address, args.authority) {
+            (Some(address), _) => match Pubkey::from_str(&address) {
+                Ok(address) => address,
+                Err(_) => {
+                    println!("Invalid address: {:?}", address);
+                    return;
+                }
+            },
+            (None, Some(authority)) => match Pubkey::from_str(&authority) {
+                Ok(authority) => proof_pubkey(authority),
+                Err(_) => {
+                    println!("Invalid authority: {:?}", authority);
+                    return;
+                }
+            },
+            (None, None) => proof_pubkey(self.authority()),
+        };
+        let proof = match get_proof_at(&self.rpc_client, address).await {
+            Ok(proof) => proof,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return;
+            }
         };
-        let proof = get_proof(&self.rpc_client, address).await;
         println!("Address: {:?}", address);
         println!("Authority: {:?}", proof.authority);
         println!(
@@ -28,7 +45,7 @@ impl Miner {
         );
         println!(
             "Last hash: {}",
-            solana_sdk::hash::Hash::new_from_array(proof.last_hash).to_string()
+            solana_sdk::hash::Hash::new_from_array(proof.last_hash)
         );
         println!("Last hash at: {:?}", proof.last_hash_at);
         println!("Last stake at: {:?}", proof.last_stake_at);
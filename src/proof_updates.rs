@@ -0,0 +1,174 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use colored::*;
+use futures::StreamExt;
+use ore_api::state::Proof;
+use ore_utils::AccountDeserialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig};
+use tokio::sync::watch;
+
+use crate::{utils::proof_pubkey, Miner};
+
+// Time before resubscribing after a subscription drops
+pub const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(2);
+
+// Proof accounts pushed by the websocket or Geyser subscriptions, while any is up
+#[derive(Clone)]
+pub struct ProofUpdates {
+    live: Arc<AtomicUsize>,
+    proof: watch::Receiver<Option<Proof>>,
+}
+
+impl ProofUpdates {
+    // The last proof notified, or None to poll the rpc instead
+    pub fn latest(&self) -> Option<Proof> {
+        if self.live.load(Ordering::Relaxed).eq(&0) {
+            return None;
+        }
+        *self.proof.borrow()
+    }
+
+    // Waits for the next notification, or for the timeout while every subscription is down
+    pub async fn wait(&mut self, timeout: Duration) {
+        if self.live.load(Ordering::Relaxed).eq(&0) {
+            tokio::time::sleep(timeout).await;
+            return;
+        }
+        let _ = tokio::time::timeout(timeout, self.proof.changed()).await;
+    }
+}
+
+// One subscription feeding proofs into the updates
+#[derive(Clone)]
+pub struct ProofSender {
+    live: Arc<AtomicUsize>,
+    sender: Arc<watch::Sender<Option<Proof>>>,
+}
+
+impl ProofSender {
+    pub fn connected(&self) {
+        self.live.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Forgets the last proof once no subscription is left to keep it current
+    pub fn disconnected(&self) {
+        if self.live.fetch_sub(1, Ordering::Relaxed).eq(&1) {
+            self.sender.send_replace(None);
+        }
+    }
+
+    // Publishes a proof unless another subscription already published a newer one.
+    // Returns false once nobody is listening.
+    pub fn send(&self, proof: Proof) -> bool {
+        self.sender.send_if_modified(|latest| {
+            if latest.is_some_and(|latest| latest.last_hash_at.gt(&proof.last_hash_at)) {
+                return false;
+            }
+            *latest = Some(proof);
+            true
+        });
+        !self.sender.is_closed()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+impl Miner {
+    // Subscribes to the proof of the authority over the websocket and Geyser, if they are set
+    pub fn subscribe_proof(&self, authority: Pubkey) -> Option<ProofUpdates> {
+        let ws_url = self.send.ws_url.clone();
+        #[cfg(feature = "geyser")]
+        let geyser = self.send.geyser.clone();
+        #[cfg(not(feature = "geyser"))]
+        let geyser: Option<()> = None;
+        if ws_url.is_none() && geyser.is_none() {
+            return None;
+        }
+        let live = Arc::new(AtomicUsize::new(0));
+        let (sender, proof) = watch::channel(None);
+        let sender = ProofSender {
+            live: live.clone(),
+            sender: Arc::new(sender),
+        };
+        if let Some(ws_url) = ws_url {
+            tokio::spawn(subscribe(
+                ws_url,
+                proof_pubkey(authority),
+                self.send.commitment,
+                sender.clone(),
+            ));
+        }
+        #[cfg(feature = "geyser")]
+        if let Some(geyser) = geyser {
+            tokio::spawn(crate::geyser::subscribe(
+                geyser,
+                self.clone(),
+                authority,
+                sender,
+            ));
+        }
+        Some(ProofUpdates { live, proof })
+    }
+}
+
+// Forwards every proof notification, resubscribing whenever the websocket drops
+async fn subscribe(
+    ws_url: String,
+    address: Pubkey,
+    commitment: CommitmentConfig,
+    sender: ProofSender,
+) {
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(commitment),
+        ..Default::default()
+    };
+    let mut warned = false;
+    loop {
+        if let Ok(pubsub) = PubsubClient::new(&ws_url).await {
+            if let Ok((mut stream, _)) = pubsub
+                .account_subscribe(&address, Some(config.clone()))
+                .await
+            {
+                sender.connected();
+                warned = false;
+                while let Some(response) = stream.next().await {
+                    let Some(account) = response.value.decode::<Account>() else {
+                        continue;
+                    };
+                    if let Ok(proof) = Proof::try_from_bytes(&account.data) {
+                        if !sender.send(*proof) {
+                            sender.disconnected();
+                            return;
+                        }
+                    }
+                }
+                sender.disconnected();
+            }
+        }
+
+        // Poll the rpc until the subscription is back
+        if sender.is_closed() {
+            return;
+        }
+        if !warned {
+            println!(
+                "{} Proof subscription dropped. Polling the rpc until it reconnects.",
+                "WARNING".bold().yellow()
+            );
+            warned = true;
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
@@ -0,0 +1,66 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// Largest message accepted from a peer
+const MAX_MESSAGE_SIZE: u32 = 1 << 20;
+
+// Messages between a coordinator and its workers, sent as a big-endian u32 length
+// followed by that many bytes of JSON
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    // Worker announces itself after connecting
+    Hello {
+        cores: u64,
+    },
+
+    // Coordinator assigns a nonce range of the current challenge
+    Job {
+        job_id: u64,
+        challenge: [u8; 32],
+        start_nonce: u64,
+        end_nonce: u64,
+        cutoff_time: u64,
+        min_difficulty: u32,
+        continue_after_min: bool,
+    },
+
+    // Worker reports its best hash of the job so far
+    Result {
+        job_id: u64,
+        nonce: u64,
+        digest: [u8; 16],
+        difficulty: u32,
+        hashes: u64,
+    },
+
+    // Coordinator ends the job
+    Stop {
+        job_id: u64,
+    },
+}
+
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Message> {
+    let len = reader.read_u32().await?;
+    if len.gt(&MAX_MESSAGE_SIZE) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes is too large", len),
+        ));
+    }
+    let mut buf = vec![0; len as usize];
+    reader.read_exact(&mut buf).await?;
+    serde_json::from_slice(&buf).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+) -> io::Result<()> {
+    let buf = serde_json::to_vec(message)?;
+    writer.write_u32(buf.len() as u32).await?;
+    writer.write_all(&buf).await?;
+    writer.flush().await
+}
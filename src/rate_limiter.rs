@@ -0,0 +1,91 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// Shortest wait before a queued request checks the bucket again
+const MIN_WAIT: Duration = Duration::from_millis(1);
+
+// A token bucket pacing requests to a number per second, shared by every copy.
+// Critical requests take a token as soon as one is free, others wait while any are queued.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    bucket: Arc<Mutex<Bucket>>,
+    critical: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    // Up to one second's worth of requests may go out in a burst
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: rate.max(1.0),
+                refilled_at: Instant::now(),
+            })),
+            critical: Arc::new(AtomicUsize::new(0)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // Requests waiting for a token
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    // Waits for a token
+    pub async fn acquire(&self, critical: bool) {
+        let _queued = Queued::new(self, critical);
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * self.rate;
+                bucket.tokens = (bucket.tokens + refill).min(self.rate.max(1.0));
+                bucket.refilled_at = now;
+                let yields = !critical && self.critical.load(Ordering::Relaxed).gt(&0);
+                if bucket.tokens.ge(&1.0) && !yields {
+                    bucket.tokens -= 1.0;
+                    break;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / self.rate).max(MIN_WAIT)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// A place in the queue, given up when the request gets its token or is dropped
+struct Queued<'a> {
+    limiter: &'a RateLimiter,
+    critical: bool,
+}
+
+impl<'a> Queued<'a> {
+    fn new(limiter: &'a RateLimiter, critical: bool) -> Self {
+        limiter.queued.fetch_add(1, Ordering::Relaxed);
+        if critical {
+            limiter.critical.fetch_add(1, Ordering::Relaxed);
+        }
+        Self { limiter, critical }
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        if self.critical {
+            self.limiter.critical.fetch_sub(1, Ordering::Relaxed);
+        }
+        self.limiter.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -1,3 +1,5 @@
+use colored::*;
+
 use crate::{
     utils::{amount_u64_to_string, get_config},
     Miner,
@@ -5,7 +7,13 @@ use crate::{
 
 impl Miner {
     pub async fn rewards(&self) {
-        let config = get_config(&self.rpc_client).await;
+        let config = match get_config(&self.rpc_client).await {
+            Ok(config) => config,
+            Err(err) => {
+                println!("{} {}", "ERROR".bold().red(), err);
+                return;
+            }
+        };
         let base_reward_rate = config.base_reward_rate;
 
         let mut s = format!(
@@ -0,0 +1,36 @@
+use colored::*;
+
+use crate::{args::RpcBenchArgs, Miner};
+
+impl Miner {
+    pub async fn rpc_bench(&self, args: RpcBenchArgs) {
+        let probes = self.rpc_pool.probe(args.samples.max(1)).await;
+        let best_slot = probes.iter().filter_map(|probe| probe.slot).max();
+        println!(
+            "{:>10}  {:>12}  {:>6}  {}",
+            "Latency".bold(),
+            "Slot".bold(),
+            "Lag".bold(),
+            "RPC".bold()
+        );
+        for probe in probes {
+            let lag = best_slot
+                .zip(probe.slot)
+                .map(|(best, slot)| best.saturating_sub(slot));
+            println!(
+                "{:>10}  {:>12}  {:>6}  {}{}",
+                probe
+                    .latency
+                    .map_or("-".to_string(), |l| format!("{}ms", l.as_millis())),
+                probe.slot.map_or("-".to_string(), |slot| slot.to_string()),
+                lag.map_or("-".to_string(), |lag| lag.to_string()),
+                probe.url,
+                match (probe.latency, probe.lagging) {
+                    (None, _) => " (unreachable)".red().to_string(),
+                    (_, true) => " (lagging)".yellow().to_string(),
+                    _ => "".to_string(),
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use colored::*;
+use futures::{future::join_all, stream::FuturesUnordered, StreamExt};
+use rand::Rng;
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSendTransactionConfig,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::rate_limiter::RateLimiter;
+
+// Consecutive failures before an endpoint is taken out of rotation
+const MAX_FAILURES: u64 = 3;
+
+// How long a failed endpoint sits out before it is tried again
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+// Backoff after the first rate limited response, doubling on each one after it
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Rounds through the endpoints while they are all rate limited
+const RATE_LIMIT_RETRIES: usize = 5;
+
+// Probe latency of an endpoint that hasn't answered a probe
+const UNPROBED: u64 = u64::MAX;
+
+struct Endpoint {
+    sender: HttpSender,
+    url: String,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    failures: AtomicU64,
+    latency_ms: AtomicU64,
+    down_until: Mutex<Option<Instant>>,
+    rate_limits: AtomicU64,
+    backoff_until: Mutex<Option<Instant>>,
+    // getLatestBlockhash latency and slot at the last probe
+    probe_ms: AtomicU64,
+    slot: AtomicU64,
+    // Too many slots behind the best endpoint to read from
+    lagging: AtomicBool,
+}
+
+impl Endpoint {
+    fn is_down(&self) -> bool {
+        self.down_until
+            .lock()
+            .unwrap()
+            .map_or(false, |until| Instant::now().lt(&until))
+    }
+
+    // Time left before the endpoint may be sent to again after rate limiting
+    fn backoff_remaining(&self) -> Option<Duration> {
+        self.backoff_until
+            .lock()
+            .unwrap()
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+    }
+}
+
+// Latency and slot of an endpoint, measured by a probe
+pub struct Probe {
+    pub url: String,
+    pub latency: Option<Duration>,
+    pub slot: Option<u64>,
+    pub lagging: bool,
+}
+
+// Rpc endpoints in order of preference. Requests go to the first healthy one
+// and move on to the next when an endpoint can't be reached. Once probed, blockhashes
+// and sends go to the fastest endpoint and other reads are spread across the rest.
+#[derive(Clone)]
+pub struct RpcPool {
+    endpoints: Arc<Vec<Endpoint>>,
+    active: Arc<AtomicUsize>,
+    verbose: Arc<AtomicBool>,
+    probed: Arc<AtomicBool>,
+    max_slot_lag: Arc<AtomicU64>,
+    next_read: Arc<AtomicUsize>,
+    limiter: Option<RateLimiter>,
+}
+
+impl RpcPool {
+    pub fn new(urls: Vec<String>) -> Self {
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint {
+                sender: HttpSender::new(url.clone()),
+                url,
+                requests: AtomicU64::new(0),
+                errors: AtomicU64::new(0),
+                failures: AtomicU64::new(0),
+                latency_ms: AtomicU64::new(0),
+                down_until: Mutex::new(None),
+                rate_limits: AtomicU64::new(0),
+                backoff_until: Mutex::new(None),
+                probe_ms: AtomicU64::new(UNPROBED),
+                slot: AtomicU64::new(0),
+                lagging: AtomicBool::new(false),
+            })
+            .collect();
+        Self {
+            endpoints: Arc::new(endpoints),
+            active: Arc::new(AtomicUsize::new(0)),
+            verbose: Arc::new(AtomicBool::new(false)),
+            probed: Arc::new(AtomicBool::new(false)),
+            max_slot_lag: Arc::new(AtomicU64::new(u64::MAX)),
+            next_read: Arc::new(AtomicUsize::new(0)),
+            limiter: None,
+        }
+    }
+
+    // This pool, sending at most the given number of requests per second across endpoints
+    pub fn with_rate_limit(mut self, rate: f64) -> Self {
+        self.limiter = Some(RateLimiter::new(rate)).filter(|_| rate.gt(&0.0));
+        self
+    }
+
+    // Requests waiting on the rate limit
+    pub fn queue_depth(&self) -> Option<usize> {
+        self.limiter.as_ref().map(RateLimiter::queue_depth)
+    }
+
+    // Slots an endpoint may be behind the best one before reads skip it
+    pub fn set_max_slot_lag(&self, max_slot_lag: u64) {
+        self.max_slot_lag.store(max_slot_lag, Ordering::Relaxed);
+    }
+
+    // Measures every endpoint, keeping the fastest of the samples, and marks the ones too
+    // far behind the best slot as lagging
+    pub async fn probe(&self, samples: usize) -> Vec<Probe> {
+        let measured = join_all(self.endpoints.iter().map(|endpoint| async move {
+            let mut latency: Option<Duration> = None;
+            for _ in 0..samples {
+                let started = Instant::now();
+                if endpoint
+                    .sender
+                    .send(RpcRequest::GetLatestBlockhash, json!([]))
+                    .await
+                    .is_ok()
+                {
+                    let elapsed = started.elapsed();
+                    latency = Some(latency.map_or(elapsed, |l| l.min(elapsed)));
+                }
+            }
+            let slot = endpoint
+                .sender
+                .send(RpcRequest::GetSlot, json!([]))
+                .await
+                .ok()
+                .and_then(|slot| slot.as_u64());
+            (latency, slot)
+        }))
+        .await;
+        let best_slot = measured.iter().filter_map(|(_, slot)| *slot).max();
+        let max_slot_lag = self.max_slot_lag.load(Ordering::Relaxed);
+        let mut probes = vec![];
+        for (endpoint, (latency, slot)) in self.endpoints.iter().zip(measured) {
+            let lag = best_slot
+                .zip(slot)
+                .map(|(best, slot)| best.saturating_sub(slot));
+            let lagging = lag.map_or(true, |lag| lag.gt(&max_slot_lag));
+            endpoint.probe_ms.store(
+                latency.map_or(UNPROBED, |l| l.as_millis() as u64),
+                Ordering::Relaxed,
+            );
+            endpoint.slot.store(slot.unwrap_or(0), Ordering::Relaxed);
+            let was_lagging = endpoint.lagging.swap(lagging, Ordering::Relaxed);
+            if lagging && !was_lagging {
+                if let Some(lag) = lag {
+                    println!(
+                        "{} RPC {} is {} slots behind. Skipping it for reads.",
+                        "INFO".bold().blue(),
+                        endpoint.url,
+                        lag
+                    );
+                }
+            }
+            probes.push(Probe {
+                url: endpoint.url.clone(),
+                latency,
+                slot,
+                lagging,
+            });
+        }
+        self.probed.store(true, Ordering::Relaxed);
+        probes
+    }
+
+    // Probes the endpoints now and on every interval after
+    pub fn spawn_probes(&self, interval: Duration) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            loop {
+                pool.probe(1).await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    // Logs backoff on rate limited endpoints
+    pub fn set_verbose(&self, verbose: bool) {
+        self.verbose.store(verbose, Ordering::Relaxed);
+    }
+
+    // Whether every healthy endpoint is backing off, so reads that only inform can be skipped
+    pub fn is_backing_off(&self) -> bool {
+        self.endpoints
+            .iter()
+            .filter(|endpoint| !endpoint.is_down())
+            .all(|endpoint| endpoint.backoff_remaining().is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    // Healthy endpoints first, those backing off next with the soonest free first,
+    // then the ones sitting out as a last resort
+    fn candidates(&self, request: RpcRequest) -> Vec<usize> {
+        let (up, down): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|i| !self.endpoints[*i].is_down());
+        let (mut limited, mut free): (Vec<usize>, Vec<usize>) = up
+            .into_iter()
+            .partition(|i| self.endpoints[*i].backoff_remaining().is_some());
+        limited.sort_by_key(|i| self.endpoints[*i].backoff_remaining());
+        if self.probed.load(Ordering::Relaxed) {
+            free.sort_by_key(|i| self.endpoints[*i].probe_ms.load(Ordering::Relaxed));
+            if !is_latency_critical(request) {
+                free = self.spread_reads(free);
+            }
+        }
+        free.into_iter().chain(limited).chain(down).collect()
+    }
+
+    // Healthy endpoints, fastest first, reordered so reads take turns on all but the
+    // fastest, with lagging endpoints last
+    fn spread_reads(&self, free: Vec<usize>) -> Vec<usize> {
+        let (mut current, lagging): (Vec<usize>, Vec<usize>) = free
+            .into_iter()
+            .partition(|i| !self.endpoints[*i].lagging.load(Ordering::Relaxed));
+        if current.len().gt(&1) {
+            let fastest = current.remove(0);
+            let turn = self.next_read.fetch_add(1, Ordering::Relaxed) % current.len();
+            current.rotate_left(turn);
+            current.push(fastest);
+        }
+        current.into_iter().chain(lagging).collect()
+    }
+
+    fn record_success(&self, index: usize, elapsed: Duration) {
+        let endpoint = &self.endpoints[index];
+        endpoint.failures.store(0, Ordering::Relaxed);
+        endpoint.rate_limits.store(0, Ordering::Relaxed);
+        endpoint
+            .latency_ms
+            .store(elapsed.as_millis() as u64, Ordering::Relaxed);
+        if endpoint.down_until.lock().unwrap().take().is_some() {
+            println!(
+                "{} RPC {} is healthy again",
+                "INFO".bold().blue(),
+                endpoint.url
+            );
+        }
+        // Reads take turns once probed, so only moving off a failing endpoint is a failover
+        let previous = self.active.swap(index, Ordering::Relaxed);
+        if previous.ne(&index)
+            && self.endpoints[previous]
+                .failures
+                .load(Ordering::Relaxed)
+                .gt(&0)
+        {
+            println!(
+                "{} RPC failover: {} -> {}",
+                "INFO".bold().blue(),
+                self.endpoints[previous].url,
+                endpoint.url
+            );
+        }
+    }
+
+    fn record_failure(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        endpoint.errors.fetch_add(1, Ordering::Relaxed);
+        let failures = endpoint.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures.ge(&MAX_FAILURES) {
+            *endpoint.down_until.lock().unwrap() = Some(Instant::now() + RETRY_INTERVAL);
+        }
+    }
+
+    fn record_rate_limit(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        endpoint.errors.fetch_add(1, Ordering::Relaxed);
+        let limits = endpoint.rate_limits.fetch_add(1, Ordering::Relaxed);
+        let delay = backoff_delay(limits as u32);
+        *endpoint.backoff_until.lock().unwrap() = Some(Instant::now() + delay);
+        if self.verbose.load(Ordering::Relaxed) {
+            println!(
+                "{} RPC {} rate limited ({} in a row). Backing off {}ms.",
+                "INFO".bold().blue(),
+                endpoint.url,
+                limits + 1,
+                delay.as_millis()
+            );
+        }
+    }
+
+    // Per-endpoint counters for the stats output
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.endpoints
+                .iter()
+                .map(|endpoint| {
+                    json!({
+                        "url": endpoint.url,
+                        "requests": endpoint.requests.load(Ordering::Relaxed),
+                        "errors": endpoint.errors.load(Ordering::Relaxed),
+                        "latency_ms": endpoint.latency_ms.load(Ordering::Relaxed),
+                        "healthy": !endpoint.is_down(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    pub fn print_summary(&self) {
+        for endpoint in self.endpoints.iter() {
+            println!(
+                "  RPC {}: {} requests, {} errors, {}ms last latency{}",
+                endpoint.url,
+                endpoint.requests.load(Ordering::Relaxed),
+                endpoint.errors.load(Ordering::Relaxed),
+                endpoint.latency_ms.load(Ordering::Relaxed),
+                if endpoint.is_down() { " (down)" } else { "" }
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for RpcPool {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let mut last_err = None;
+        for _ in 0..=RATE_LIMIT_RETRIES {
+            for index in self.candidates(request) {
+                let endpoint = &self.endpoints[index];
+
+                // Every endpoint ahead of this one failed, so wait out its backoff
+                if let Some(remaining) = endpoint.backoff_remaining() {
+                    tokio::time::sleep(remaining).await;
+                }
+                if let Some(limiter) = &self.limiter {
+                    limiter.acquire(is_latency_critical(request)).await;
+                }
+                endpoint.requests.fetch_add(1, Ordering::Relaxed);
+                let started = Instant::now();
+                match endpoint.sender.send(request, params.clone()).await {
+                    Ok(value) => {
+                        self.record_success(index, started.elapsed());
+                        return Ok(value);
+                    }
+
+                    // The endpoint is rate limiting, so try the next one while it backs off
+                    Err(err) if is_rate_limited(&err) => {
+                        self.record_rate_limit(index);
+                        last_err = Some(err);
+                    }
+                    Err(err) => match err.kind() {
+                        // The endpoint couldn't be reached, so try the next one
+                        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => {
+                            self.record_failure(index);
+                            last_err = Some(err);
+                        }
+
+                        // The endpoint answered, so the error is the request's own
+                        _ => {
+                            self.record_success(index, started.elapsed());
+                            return Err(err);
+                        }
+                    },
+                }
+            }
+
+            // Go round again only if rate limiting is what failed the last endpoint
+            if !last_err.as_ref().is_some_and(is_rate_limited) {
+                break;
+            }
+        }
+        Err(last_err.expect("No rpc endpoints"))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.endpoints[self.active.load(Ordering::Relaxed)]
+            .sender
+            .get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.endpoints[self.active.load(Ordering::Relaxed)]
+            .url
+            .clone()
+    }
+}
+
+struct Target {
+    client: RpcClient,
+    url: String,
+    accepted: AtomicU64,
+    first_landed: AtomicU64,
+}
+
+// Endpoints a signed transaction is sent to at once. Each counts how often it was
+// the first to accept a transaction that went on to land.
+#[derive(Clone)]
+pub struct Broadcast {
+    targets: Arc<Vec<Target>>,
+}
+
+impl Broadcast {
+    pub fn new(urls: Vec<String>, commitment: CommitmentConfig) -> Self {
+        let mut targets: Vec<Target> = vec![];
+        for url in urls {
+            if targets.iter().any(|target| target.url.eq(&url)) {
+                continue;
+            }
+            targets.push(Target {
+                client: RpcClient::new_with_commitment(url.clone(), commitment),
+                url,
+                accepted: AtomicU64::new(0),
+                first_landed: AtomicU64::new(0),
+            });
+        }
+        Self {
+            targets: Arc::new(targets),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    // Sends the transaction to every endpoint concurrently. Returns the index of the
+    // first endpoint to accept it, or none if all of them had already processed it.
+    // Fails only if no endpoint accepted it.
+    pub async fn send(
+        &self,
+        tx: &Transaction,
+        config: RpcSendTransactionConfig,
+    ) -> ClientResult<(Signature, Option<usize>)> {
+        let mut sends: FuturesUnordered<_> = self
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(index, target)| async move {
+                (
+                    index,
+                    target.client.send_transaction_with_config(tx, config).await,
+                )
+            })
+            .collect();
+        let mut first = None;
+        let mut already_processed = false;
+        let mut last_err = None;
+        while let Some((index, res)) = sends.next().await {
+            match res {
+                Ok(_) => {
+                    self.targets[index].accepted.fetch_add(1, Ordering::Relaxed);
+                    first.get_or_insert(index);
+                }
+
+                // A laggard seeing a copy another endpoint already landed isn't a failure
+                Err(err) if is_already_processed(&err) => already_processed = true,
+                Err(err) => last_err = Some(err),
+            }
+        }
+        match (first, already_processed, last_err) {
+            (Some(_), _, _) | (None, true, _) => Ok((tx.signatures[0], first)),
+            (None, false, Some(err)) => Err(err),
+            (None, false, None) => panic!("No broadcast endpoints"),
+        }
+    }
+
+    // Credits the endpoint that first accepted a transaction that landed
+    pub fn credit(&self, index: usize) {
+        self.targets[index]
+            .first_landed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Per-endpoint counters for the stats output
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.targets
+                .iter()
+                .map(|target| {
+                    json!({
+                        "url": target.url,
+                        "accepted": target.accepted.load(Ordering::Relaxed),
+                        "first_landed": target.first_landed.load(Ordering::Relaxed),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    pub fn print_summary(&self) {
+        for target in self.targets.iter() {
+            println!(
+                "  Broadcast {}: {} accepted, first to land {} times",
+                target.url,
+                target.accepted.load(Ordering::Relaxed),
+                target.first_landed.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+// Requests the time to land a transaction waits on
+fn is_latency_critical(request: RpcRequest) -> bool {
+    matches!(
+        request,
+        RpcRequest::SendTransaction | RpcRequest::GetLatestBlockhash
+    )
+}
+
+// Whether the endpoint answered with HTTP 429 or a "too many requests" error
+pub fn is_rate_limited(err: &ClientError) -> bool {
+    if let ClientErrorKind::Reqwest(err) = err.kind() {
+        if err.status().is_some_and(|status| status.as_u16().eq(&429)) {
+            return true;
+        }
+    }
+    err.to_string().to_lowercase().contains("too many requests")
+}
+
+// Exponential backoff after the given number of rate limits in a row, with up to
+// half of it added or taken away at random so clients don't retry in lockstep
+pub fn backoff_delay(rate_limits: u32) -> Duration {
+    let delay = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(rate_limits.min(16)))
+        .min(MAX_BACKOFF);
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+}
+
+fn is_already_processed(err: &ClientError) -> bool {
+    matches!(
+        err.get_transaction_error(),
+        Some(TransactionError::AlreadyProcessed)
+    ) || err.to_string().contains("already been processed")
+}
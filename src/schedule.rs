@@ -0,0 +1,184 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, Local, Timelike, Weekday};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// A daily mining window, e.g. `22:00-07:00` or `sat,sun@00:00-24:00`
+#[derive(Clone, Debug)]
+pub struct ActiveHours {
+    days: Vec<Weekday>,
+    start: u32,
+    end: u32,
+}
+
+impl ActiveHours {
+    // Windows that wrap midnight are matched against the day they started on
+    fn contains(&self, time: DateTime<Local>) -> bool {
+        let minute = time.hour() * 60 + time.minute();
+        let today = time.weekday();
+        if self.start.lt(&self.end) {
+            self.start.le(&minute) && minute.lt(&self.end) && self.days.contains(&today)
+        } else {
+            (self.start.le(&minute) && self.days.contains(&today))
+                || (minute.lt(&self.end) && self.days.contains(&today.pred()))
+        }
+    }
+}
+
+impl FromStr for ActiveHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (days, hours) = match s.split_once('@') {
+            Some((days, hours)) => (parse_days(days)?, hours),
+            None => (ALL_DAYS.to_vec(), s),
+        };
+        let (start, end) = hours
+            .split_once('-')
+            .ok_or(format!("Invalid window `{}`, expected HH:MM-HH:MM", hours))?;
+        let start = parse_minute(start)?;
+        let end = parse_minute(end)?;
+        if start.eq(&MINUTES_PER_DAY) {
+            return Err(format!("Window `{}` can't start at 24:00", hours));
+        }
+        if start.eq(&end) {
+            return Err(format!("Window `{}` is empty", hours));
+        }
+        Ok(Self { days, start, end })
+    }
+}
+
+const ALL_DAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+// Parses a list of days like `mon-fri` or `sat,sun`
+fn parse_days(s: &str) -> Result<Vec<Weekday>, String> {
+    let mut days = vec![];
+    for part in s.split(',') {
+        let parse = |d: &str| {
+            Weekday::from_str(d.trim()).map_err(|_| format!("Invalid day of week `{}`", d))
+        };
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let mut day = parse(first)?;
+                let last = parse(last)?;
+                while day.ne(&last) {
+                    days.push(day);
+                    day = day.succ();
+                }
+                days.push(last);
+            }
+            None => days.push(parse(part)?),
+        }
+    }
+    Ok(days)
+}
+
+// Parses `HH:MM` into minutes since midnight (`24:00` is allowed as an end time)
+fn parse_minute(s: &str) -> Result<u32, String> {
+    let err = || format!("Invalid time `{}`, expected HH:MM", s);
+    let (hour, minute) = s.trim().split_once(':').ok_or_else(err)?;
+    let hour = u32::from_str(hour).map_err(|_| err())?;
+    let minute = u32::from_str(minute).map_err(|_| err())?;
+    let total = hour * 60 + minute;
+    if minute.ge(&60) || total.gt(&MINUTES_PER_DAY) {
+        return Err(err());
+    }
+    Ok(total)
+}
+
+// Returns true if mining is allowed at the given time
+pub fn is_active(windows: &[ActiveHours], time: DateTime<Local>) -> bool {
+    windows.is_empty() || windows.iter().any(|w| w.contains(time))
+}
+
+// Returns the start of the next active window, searching up to a week ahead
+pub fn next_active(windows: &[ActiveHours], time: DateTime<Local>) -> Option<DateTime<Local>> {
+    let time = time.with_second(0)?.with_nanosecond(0)?;
+    (1..=7 * MINUTES_PER_DAY as i64)
+        .map(|m| time + Duration::minutes(m))
+        .find(|t| is_active(windows, *t))
+}
+
+// Parses a wall time like `90`, `45s`, `30m`, `2h` or `1h30m` (bare numbers are seconds)
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let err = || {
+        format!(
+            "Invalid duration `{}`, expected e.g. 45s, 30m, 2h or 1h30m",
+            s
+        )
+    };
+    let mut secs = 0u64;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(err()),
+        };
+        let value = u64::from_str(&digits).map_err(|_| err())?;
+        secs = secs.saturating_add(value.saturating_mul(unit));
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        secs = secs.saturating_add(u64::from_str(&digits).map_err(|_| err())?);
+    }
+    if secs.eq(&0) {
+        return Err(err());
+    }
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        // 2024-06-01 is a Saturday
+        Local
+            .with_ymd_and_hms(2024, 6, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn whole_day_window() {
+        let window = ActiveHours::from_str("sat,sun@00:00-24:00").unwrap();
+        assert!(window.contains(at(1, 0, 0)));
+        assert!(window.contains(at(1, 23, 59)));
+        assert!(window.contains(at(2, 12, 0)));
+        assert!(!window.contains(at(3, 0, 0)));
+    }
+
+    #[test]
+    fn window_wrapping_midnight() {
+        let window = ActiveHours::from_str("22:00-07:00").unwrap();
+        assert!(window.contains(at(1, 23, 0)));
+        assert!(window.contains(at(2, 6, 59)));
+        assert!(!window.contains(at(2, 7, 0)));
+        assert!(!window.contains(at(2, 12, 0)));
+    }
+
+    #[test]
+    fn invalid_windows() {
+        assert!(ActiveHours::from_str("24:00-07:00").is_err());
+        assert!(ActiveHours::from_str("08:00-08:00").is_err());
+        assert!(ActiveHours::from_str("08:00-24:01").is_err());
+        assert!(ActiveHours::from_str("08:60-09:00").is_err());
+    }
+}
@@ -1,38 +1,465 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
+use clap::ValueEnum;
 use colored::*;
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use ore_api::{error::OreError, state::Proof};
+use ore_utils::AccountDeserialize;
 use solana_client::{
     client_error::{ClientError, ClientErrorKind, Result as ClientResult},
-    rpc_config::RpcSendTransactionConfig,
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_config::{
+        RpcSendTransactionConfig, RpcSignatureSubscribeConfig, RpcSimulateTransactionConfig,
+        RpcTransactionConfig,
+    },
+    rpc_request::{RpcError, RpcResponseErrorData},
+    rpc_response::{RpcSignatureResult, RpcSimulateTransactionResult},
 };
 use solana_program::{
-    instruction::Instruction,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
     native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    system_instruction,
 };
 use solana_rpc_client::spinner;
 use solana_sdk::{
-    commitment_config::CommitmentLevel,
+    commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
+    packet::PACKET_DATA_SIZE,
     signature::{Signature, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::UiTransactionEncoding;
+use tokio::sync::Notify;
+
+use crate::{
+    cluster::program_id,
+    rpc_pool::{backoff_delay, is_rate_limited, Broadcast},
+    tx_log::{instruction_kind, TxLogEntry},
+    utils::observe_slot,
+    Miner,
 };
-use solana_transaction_status::{TransactionConfirmationStatus, UiTransactionEncoding};
 
-use crate::Miner;
+// Last blockhash fetched, with its last valid block height and when it was fetched
+static BLOCKHASH_CACHE: Mutex<Option<(Hash, u64, Instant)>> = Mutex::new(None);
+
+// Set once a failed TPU submission has been reported
+static TPU_WARNED: AtomicBool = AtomicBool::new(false);
 
-const MIN_SOL_BALANCE: f64 = 0.005;
+// Set once the dynamic fee fallback has been reported
+static DYNAMIC_FEE_WARNED: AtomicBool = AtomicBool::new(false);
+
+// Set once the websocket fallback has been reported
+static WS_WARNED: AtomicBool = AtomicBool::new(false);
 
 const RPC_RETRIES: usize = 0;
-const _SIMULATION_RETRIES: usize = 4;
-const GATEWAY_RETRIES: usize = 150;
 const CONFIRM_RETRIES: usize = 8;
 
 const CONFIRM_DELAY: u64 = 500;
-const GATEWAY_DELAY: u64 = 0; //300;
+
+// Times the meta of a landed transaction is fetched before giving up on it
+const META_RETRIES: usize = 3;
+
+pub const MAX_CU_LIMIT: u32 = 1_400_000;
+
+// Program and instruction discriminator of each instruction in a transaction
+type IxShape = Vec<(Pubkey, Option<u8>)>;
+
+// Compute unit limits measured by simulation
+static SIMULATED_CU_LIMITS: OnceLock<Mutex<HashMap<IxShape, u32>>> = OnceLock::new();
+
+// Most compute units a landed transaction of each instruction shape consumed
+static CONSUMED_CU: OnceLock<Mutex<HashMap<IxShape, u64>>> = OnceLock::new();
+
+fn ix_shape(ixs: &[Instruction]) -> IxShape {
+    ixs.iter()
+        .map(|ix| (ix.program_id, ix.data.first().copied()))
+        .collect()
+}
 
 pub enum ComputeBudget {
     Dynamic,
     Fixed(u32),
+    // Simulated usage plus a margin, or the given limit if simulation fails
+    Simulated(u32),
+}
+
+// Which rpc polls signature statuses
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ConfirmRpc {
+    Read,
+    Send,
+}
+
+// Where transactions are sent and confirmed
+#[derive(Clone)]
+pub struct SendConfig {
+    pub send_rpc: Option<Arc<RpcClient>>,
+    // Set when there is more than one endpoint to send through
+    pub broadcast: Option<Broadcast>,
+    pub confirm_rpc: ConfirmRpc,
+    pub retries: usize,
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+    pub skip_preflight: bool,
+    pub cu_margin: u64,
+    pub ws_url: Option<String>,
+    // Geyser gRPC endpoint streaming the proof and bus accounts
+    #[cfg(feature = "geyser")]
+    pub geyser: Option<crate::geyser::GeyserConfig>,
+    pub tx_log: Option<String>,
+    pub nonce_account: Option<Pubkey>,
+    pub confirm_timeout: Option<Duration>,
+    // SOL the fee payer keeps in reserve
+    pub min_sol_balance: f64,
+    // Simulate signed transactions instead of sending them
+    pub dry_run: bool,
+    // Oldest cached blockhash a transaction is signed with
+    pub blockhash_max_age: Duration,
+    // Send to the leaders over QUIC before the rpc
+    pub tpu: bool,
+    // Write failed preflight simulations to the tx log instead of printing them
+    pub quiet: bool,
+    // Level accounts are read and transactions are confirmed at
+    pub commitment: CommitmentConfig,
+}
+
+// The round a mine submission belongs to. The fee is no longer bumped once the deadline
+// has passed, retries stop once the proof at the address moves on from the challenge, and
+// `sent` is notified when the transaction is first broadcast.
+#[derive(Clone, Copy)]
+pub struct SubmitRound<'a> {
+    pub deadline: Instant,
+    pub proof: (Pubkey, [u8; 32]),
+    pub sent: &'a Notify,
+}
+
+// Slot and result of a transaction that landed
+type Landed = (u64, Result<(), TransactionError>);
+
+// A landed transaction
+#[derive(Clone, Copy, Debug)]
+pub struct Confirmed {
+    pub signature: Signature,
+    pub slot: Option<u64>,
+    // Time from the last broadcast to confirmation
+    pub latency: Duration,
+    // When the transaction was first broadcast
+    pub sent_at: Option<Instant>,
+    // Jito tip of the last bundle accepted with the transaction
+    pub tip: u64,
+    // Compute units consumed and fee paid, read from the transaction meta
+    pub compute_units: Option<u64>,
+    pub fee_paid: Option<u64>,
+}
+
+// Why a transaction didn't land
+#[derive(Debug)]
+pub enum SubmitError {
+    // An instruction failed with a custom program error, on-chain or in preflight
+    ProgramError(u32),
+    // The transaction failed for another reason, on-chain or in preflight
+    TransactionError(TransactionError),
+    // Retries ran out on a blockhash that was no longer valid
+    BlockhashExpired,
+    // Confirmation took longer than --confirm-timeout. The signatures sent may still land.
+    Timeout { signatures: Vec<Signature> },
+    // The deadline passed before the transaction landed
+    WindowClosed { attempts: usize },
+    // The RPC couldn't be reached, or failed the request
+    RpcError(ClientError),
+    // The signed transaction is over the packet size, in bytes
+    SizeLimit(u64),
+    // Retries ran out without the transaction landing
+    Dropped { attempts: usize },
+    // The proof moved on to a new challenge, so another solution landed first
+    Stale,
+    // The fee payer's balance, in lamports, is at or below --min-sol-balance
+    InsufficientBalance(u64),
+}
+
+impl SubmitError {
+    // The ORE program error the transaction failed with, on-chain or in preflight
+    pub fn ore_error(&self) -> Option<OreError> {
+        match self {
+            Self::ProgramError(code) => ore_error_code(*code),
+            _ => None,
+        }
+    }
+
+    // Outcome counted in the transaction stats
+    pub fn outcome(&self) -> &'static str {
+        match self {
+            Self::ProgramError(_) | Self::TransactionError(_) => "failed",
+            Self::BlockhashExpired
+            | Self::Timeout { .. }
+            | Self::WindowClosed { .. }
+            | Self::Dropped { .. } => "expired",
+            Self::RpcError(_) | Self::SizeLimit(_) | Self::InsufficientBalance(_) => "error",
+            Self::Stale => "stale",
+        }
+    }
+
+    // Logs why a one-off transaction, such as a claim, didn't land
+    pub fn report(&self, action: &str) {
+        match self {
+            Self::Timeout { signatures } => println!(
+                "{} The {} was not confirmed in time and may still land: {}",
+                "WARNING".bold().yellow(),
+                action,
+                signatures
+                    .iter()
+                    .map(|sig| sig.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::RpcError(err) => println!(
+                "{} The RPC failed the {}: {}",
+                "ERROR".bold().red(),
+                action,
+                err
+            ),
+            Self::InsufficientBalance(balance) => println!(
+                "{} The {} was not sent: the fee payer has {} SOL, at or below --min-sol-balance. Top it up and try again.",
+                "ERROR".bold().red(),
+                action,
+                lamports_to_sol(*balance)
+            ),
+            _ => println!("{} The {} failed: {}", "ERROR".bold().red(), action, self),
+        }
+    }
+}
+
+impl From<TransactionError> for SubmitError {
+    fn from(err: TransactionError) -> Self {
+        match err {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+                Self::ProgramError(code)
+            }
+            err => Self::TransactionError(err),
+        }
+    }
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ProgramError(code) => match ore_error_code(*code) {
+                Some(ore_err) => write!(f, "program error 0x{:x} ({})", code, ore_err),
+                None => write!(f, "program error 0x{:x}", code),
+            },
+            Self::TransactionError(err) => write!(f, "{}", err),
+            Self::BlockhashExpired => write!(f, "blockhash expired"),
+            Self::Timeout { .. } => write!(f, "confirmation timed out"),
+            Self::WindowClosed { attempts } => {
+                write!(f, "window closed after {} attempts", attempts)
+            }
+            Self::RpcError(err) => write!(f, "rpc error: {}", err),
+            Self::SizeLimit(size) => write!(
+                f,
+                "transaction is {} bytes, over the {} byte limit",
+                size, PACKET_DATA_SIZE
+            ),
+            Self::Dropped { attempts } => write!(f, "not landed after {} attempts", attempts),
+            Self::Stale => write!(f, "challenge changed, another solution landed first"),
+            Self::InsufficientBalance(balance) => write!(
+                f,
+                "fee payer has {} SOL, at or below the reserve",
+                lamports_to_sol(*balance)
+            ),
+        }
+    }
+}
+
+// The ORE program error behind a custom instruction error
+pub fn ore_error(err: &TransactionError) -> Option<OreError> {
+    let TransactionError::InstructionError(_, InstructionError::Custom(code)) = err else {
+        return None;
+    };
+    ore_error_code(*code)
+}
+
+fn ore_error_code(code: u32) -> Option<OreError> {
+    [
+        OreError::NeedsReset,
+        OreError::HashInvalid,
+        OreError::HashTooEasy,
+        OreError::ClaimTooLarge,
+        OreError::ClockInvalid,
+        OreError::Spam,
+        OreError::MaxSupply,
+        OreError::AuthFailed,
+    ]
+    .into_iter()
+    .find(|ore_err| (*ore_err as u32).eq(&code))
+}
+
+fn is_blockhash_not_found(err: &ClientError) -> bool {
+    matches!(
+        err.get_transaction_error(),
+        Some(TransactionError::BlockhashNotFound)
+    )
+}
+
+// Simulation the rpc ran when preflight rejected a transaction
+fn preflight_result(err: &ClientError) -> Option<&RpcSimulateTransactionResult> {
+    match err.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => Some(result),
+        _ => None,
+    }
+}
+
+// A program log line, naming the ORE error a failed ORE instruction returned
+fn decode_log(line: &str) -> String {
+    let failed = format!("Program {} failed: custom program error: 0x", program_id());
+    line.strip_prefix(&failed)
+        .and_then(|code| u32::from_str_radix(code, 16).ok())
+        .and_then(ore_error_code)
+        .map_or(line.to_string(), |ore_err| {
+            format!("{} ({})", line, ore_err)
+        })
+}
+
+// A transaction error, with the readable ORE error if there is one
+fn describe_tx_error(err: &TransactionError) -> String {
+    match ore_error(err) {
+        Some(ore_err) => format!("{} ({})", err, ore_err),
+        None => err.to_string(),
+    }
+}
+
+// Outcome of a transaction from its websocket notification, or None if none arrives in time
+async fn notification(
+    pubsub: &PubsubClient,
+    sig: Signature,
+    commitment: CommitmentConfig,
+) -> Option<Landed> {
+    let config = RpcSignatureSubscribeConfig {
+        commitment: Some(commitment),
+        enable_received_notification: Some(false),
+    };
+    let (mut stream, unsubscribe) = pubsub.signature_subscribe(&sig, Some(config)).await.ok()?;
+    let timeout = Duration::from_millis(CONFIRM_DELAY * CONFIRM_RETRIES as u64);
+    let response = tokio::time::timeout(timeout, stream.next())
+        .await
+        .ok()
+        .flatten();
+    drop(stream);
+    unsubscribe().await;
+    let response = response?;
+    match response.value {
+        RpcSignatureResult::ProcessedSignature(result) => Some((
+            response.context.slot,
+            match result.err {
+                Some(err) => Err(err),
+                None => Ok(()),
+            },
+        )),
+        RpcSignatureResult::ReceivedSignature(_) => None,
+    }
+}
+
+// Whether the proof has moved on from the challenge, treating fetch errors as unchanged
+pub async fn challenge_moved(
+    client: &RpcClient,
+    proof_address: Pubkey,
+    challenge: [u8; 32],
+) -> bool {
+    let Ok(data) = client.get_account_data(&proof_address).await else {
+        return false;
+    };
+    Proof::try_from_bytes(&data).is_ok_and(|proof| proof.challenge.ne(&challenge))
+}
+
+// Compute units consumed and fee paid by a landed transaction, or None if its meta
+// can't be fetched. Transactions can't be read at processed, so that reads at confirmed.
+async fn transaction_meta(
+    client: &RpcClient,
+    sig: &Signature,
+    commitment: CommitmentConfig,
+) -> Option<(Option<u64>, u64)> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(match commitment.is_finalized() {
+            true => CommitmentConfig::finalized(),
+            false => CommitmentConfig::confirmed(),
+        }),
+        max_supported_transaction_version: Some(0),
+    };
+    for _ in 0..META_RETRIES {
+        if let Ok(tx) = client.get_transaction_with_config(sig, config).await {
+            let meta = tx.transaction.meta?;
+            return Some((meta.compute_units_consumed.into(), meta.fee));
+        }
+        tokio::time::sleep(Duration::from_millis(CONFIRM_DELAY)).await;
+    }
+    None
+}
+
+// Prints the simulation behind a failed preflight: the instruction that failed,
+// compute units consumed and the program logs
+fn print_preflight(
+    progress_bar: &ProgressBar,
+    tx: &Transaction,
+    sim: &RpcSimulateTransactionResult,
+    logs: &[String],
+) {
+    progress_bar.println("  Preflight simulation:");
+    if let Some(TransactionError::InstructionError(index, _)) = &sim.err {
+        let program = tx
+            .message
+            .instructions
+            .get(*index as usize)
+            .and_then(|ix| tx.message.account_keys.get(ix.program_id_index as usize));
+        match program {
+            Some(program) => progress_bar.println(format!(
+                "    Failed instruction: {} (program {})",
+                index, program
+            )),
+            None => progress_bar.println(format!("    Failed instruction: {}", index)),
+        }
+    }
+    if let Some(units) = sim.units_consumed {
+        progress_bar.println(format!("    Compute units consumed: {}", units));
+    }
+    for log in logs {
+        progress_bar.println(format!("    {}", log));
+    }
+}
+
+// The first of the transactions to land at the commitment, or None while all are pending
+async fn confirmation(
+    client: &RpcClient,
+    sigs: &[Signature],
+    commitment: CommitmentConfig,
+) -> ClientResult<Option<(Signature, Landed)>> {
+    let statuses = client.get_signature_statuses(sigs).await?;
+    for (sig, status) in sigs.iter().zip(statuses.value) {
+        let Some(status) = status else {
+            continue;
+        };
+        if let Some(err) = status.err {
+            return Ok(Some((*sig, (status.slot, Err(err)))));
+        }
+        if status.satisfies_commitment(commitment) {
+            return Ok(Some((*sig, (status.slot, Ok(())))));
+        }
+    }
+    Ok(None)
 }
 
 impl Miner {
@@ -41,130 +468,441 @@ impl Miner {
         ixs: &[Instruction],
         compute_budget: ComputeBudget,
         skip_confirm: bool,
-    ) -> ClientResult<Signature> {
+    ) -> Result<Confirmed, SubmitError> {
+        self.send_and_confirm_by(ixs, compute_budget, skip_confirm, None, None)
+            .await
+    }
+
+    // Sends and confirms, bound to the round of a mine submission if given.
+    // A scaled fee is the lowest price paid, with the difficulty that drove it.
+    pub async fn send_and_confirm_by(
+        &self,
+        ixs: &[Instruction],
+        compute_budget: ComputeBudget,
+        skip_confirm: bool,
+        round: Option<SubmitRound<'_>>,
+        scaled_fee: Option<(u64, u32)>,
+    ) -> Result<Confirmed, SubmitError> {
+        let deadline = round.map(|round| round.deadline);
+        let stale_on = round.map(|round| round.proof);
         let signer = self.signer();
         let client = self.rpc_client.clone();
+        let confirm_client = match (self.send.confirm_rpc, &self.send.send_rpc) {
+            (ConfirmRpc::Send, Some(send_rpc)) => send_rpc.clone(),
+            _ => client.clone(),
+        };
         let fee_payer = self.fee_payer();
+        let fees = self.fees.for_kind(&instruction_kind(ixs));
 
-        // Return error, if balance is zero
-        self.check_balance().await;
+        // Don't spend fees the reserve can't cover
+        if let Some(balance) = self.below_reserve().await {
+            return Err(SubmitError::InsufficientBalance(balance));
+        }
 
         // Set compute budget
+        let cu_limit = match compute_budget {
+            // TODO simulate
+            ComputeBudget::Dynamic => MAX_CU_LIMIT,
+            ComputeBudget::Fixed(cus) => cus,
+            ComputeBudget::Simulated(fallback) => self.simulated_cu_limit(ixs, fallback).await,
+        };
         let mut final_ixs = vec![];
-        match compute_budget {
-            ComputeBudget::Dynamic => {
-                // TODO simulate
-                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(1_400_000))
-            }
-            ComputeBudget::Fixed(cus) => {
-                final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cus))
-            }
+
+        // Advance the nonce first, as the runtime requires
+        if let Some(nonce_account) = self.send.nonce_account {
+            final_ixs.push(system_instruction::advance_nonce_account(
+                &nonce_account,
+                &signer.pubkey(),
+            ));
         }
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(cu_limit));
 
         // Set compute unit price
-        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
-            self.priority_fee.unwrap_or(0),
-        ));
+        let cu_price = scaled_fee.map_or(fees.static_fee(), |(price, _)| price);
+        let price_index = final_ixs.len();
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(cu_price));
 
         // Add in user instructions
         final_ixs.extend_from_slice(ixs);
 
+        // Accounts the transaction write-locks, which dynamic fees are priced against
+        let mut writable_accounts = vec![];
+        for meta in ixs.iter().flat_map(|ix| ix.accounts.iter()) {
+            if meta.is_writable && !writable_accounts.contains(&meta.pubkey) {
+                writable_accounts.push(meta.pubkey);
+            }
+        }
+
         // Build tx
         let send_cfg = RpcSendTransactionConfig {
-            skip_preflight: true,
-            preflight_commitment: Some(CommitmentLevel::Confirmed),
+            skip_preflight: self.send.skip_preflight,
+            preflight_commitment: Some(self.send.commitment.commitment),
             encoding: Some(UiTransactionEncoding::Base64),
             max_retries: Some(RPC_RETRIES),
             min_context_slot: None,
         };
         let mut tx = Transaction::new_with_payer(&final_ixs, Some(&fee_payer.pubkey()));
+        let mut tip_tx = None;
+        let mut tip = 0;
+        let mut tipped = 0;
 
         // Submit tx
         let progress_bar = spinner::new_progress_bar();
         let mut attempts = 0;
+        let mut last_err: Option<ClientError>;
+        let mut sent_at = Instant::now();
+        let mut sent_slot = None;
+        let mut first_sent_at = None;
+        let mut min_price = scaled_fee.map_or(0, |(price, _)| price);
+        let mut pending = vec![];
+        let mut first_accepted = HashMap::new();
+        let mut rate_limits = 0;
+        let mut last_valid_height = u64::MAX;
+        let mut expired = false;
+        let mut entry = TxLogEntry {
+            kind: instruction_kind(ixs),
+            signature: String::new(),
+            attempt: 0,
+            cu_price,
+            cu_limit,
+            difficulty: scaled_fee.map(|(_, difficulty)| difficulty),
+            fee_provider: None,
+            fee_estimate: None,
+            compute_units: None,
+            fee: None,
+            logs: None,
+            outcome: "sent",
+            path: "rpc",
+            slot: None,
+            error: None,
+        };
+
+        // Subscribe to confirmations over websocket, polling if that's unavailable
+        let pubsub = match (&self.send.ws_url, skip_confirm) {
+            (Some(ws_url), false) => match PubsubClient::new(ws_url).await {
+                Ok(pubsub) => Some(pubsub),
+                Err(err) => {
+                    if !WS_WARNED.swap(true, Ordering::Relaxed) {
+                        progress_bar.println(format!(
+                            "  {} Websocket unavailable ({}). Polling for confirmations instead.",
+                            "WARNING".bold().yellow(),
+                            err
+                        ));
+                    }
+                    None
+                }
+            },
+            _ => None,
+        };
         loop {
             progress_bar.set_message(format!("Submitting transaction... (attempt {})", attempts,));
 
+            // Don't broadcast again if an earlier attempt landed in the meantime
+            if attempts.gt(&0) && !skip_confirm {
+                let mut sigs = pending.clone();
+                sigs.push(tx.signatures[0]);
+                if let Ok(Some((sig, (slot, result)))) =
+                    confirmation(&confirm_client, &sigs, self.send.commitment).await
+                {
+                    entry.signature = sig.to_string();
+                    return self
+                        .landed(
+                            &progress_bar,
+                            &entry,
+                            ixs,
+                            (slot, result),
+                            sent_at.elapsed(),
+                            first_accepted.get(&sig).copied(),
+                        )
+                        .await
+                        .map(|confirmed| Confirmed {
+                            sent_at: first_sent_at,
+                            tip: tipped,
+                            ..confirmed
+                        });
+                }
+            }
+
+            // Then stop retrying once another solution has landed against the proof
+            if let (true, Some((proof_address, challenge))) = (attempts.gt(&0), stale_on) {
+                if challenge_moved(&client, proof_address, challenge).await {
+                    progress_bar.finish_with_message(format!(
+                        "{}: Challenge changed before the transaction landed",
+                        "INFO".bold().blue()
+                    ));
+                    self.log_tx(&TxLogEntry {
+                        outcome: "stale",
+                        error: Some("Challenge changed".to_string()),
+                        ..entry.clone()
+                    });
+                    return Err(SubmitError::Stale);
+                }
+            }
+
+            // Raise the fee if the transaction hasn't landed within the configured slots
+            let replaced = tx.signatures[0];
+            let mut bumped = false;
+            if let (Some(bump_after), Some(sent)) = (fees.bump_after_slots, sent_slot) {
+                let open = deadline.map_or(true, |deadline| Instant::now().lt(&deadline));
+                if let (true, Ok(slot)) = (open, client.get_slot().await) {
+                    let fee = self
+                        .fees
+                        .cap((entry.cu_price as f64 * fees.bump_factor).ceil() as u64);
+                    if slot.saturating_sub(sent).ge(&bump_after) && fee.gt(&entry.cu_price) {
+                        progress_bar.println(format!(
+                            "  Not landed after {} slots. Bumping priority fee to {} microlamports",
+                            slot.saturating_sub(sent),
+                            fee
+                        ));
+                        final_ixs[price_index] =
+                            ComputeBudgetInstruction::set_compute_unit_price(fee);
+                        tx = Transaction::new_with_payer(&final_ixs, Some(&fee_payer.pubkey()));
+                        entry.cu_price = fee;
+                        min_price = fee;
+                        bumped = true;
+                    }
+                }
+            }
+
             // Sign tx with a new blockhash (after approximately ~45 sec)
-            if attempts % 10 == 0 {
+            if attempts % 10 == 0 || bumped || expired {
+                expired = false;
+                // The replaced transaction can still land
+                if attempts.gt(&0) && replaced.ne(&Signature::default()) {
+                    pending.push(replaced);
+                }
+                sent_slot = None;
+
                 // Reset the compute unit price
-                if self.dynamic_fee {
-                    let fee = if let Some(fee) = self.dynamic_fee().await {
-                        progress_bar.println(format!("  Priority fee: {} microlamports", fee));
-                        fee
-                    } else {
-                        let fee = self.priority_fee.unwrap_or(0);
-                        progress_bar.println(format!("  {} Dynamic fees not supported by this RPC. Falling back to static value: {} microlamports", "WARNING".bold().yellow(), fee));
-                        fee
-                    };
-                    final_ixs.remove(1);
-                    final_ixs.insert(1, ComputeBudgetInstruction::set_compute_unit_price(fee));
+                if fees.dynamic_fee && !bumped {
+                    let fee = match self.dynamic_fee(&writable_accounts, &fees).await {
+                        Ok((estimate, provider)) => {
+                            entry.fee_provider = Some(provider);
+                            entry.fee_estimate = Some(estimate);
+                            let fee = fees.bound(estimate);
+                            if fee.lt(&estimate) {
+                                progress_bar.println(format!(
+                                    "  {} Dynamic fee estimate of {} microlamports capped to {} microlamports",
+                                    "WARNING".bold().yellow(),
+                                    estimate,
+                                    fee
+                                ));
+                            }
+                            fee
+                        }
+                        Err(err) => {
+                            entry.fee_provider = None;
+                            entry.fee_estimate = None;
+                            let fee = fees.static_fee();
+                            if !DYNAMIC_FEE_WARNED.swap(true, Ordering::Relaxed) {
+                                progress_bar.println(format!("  {} Dynamic fees not supported by this RPC ({}). Falling back to static value: {} microlamports", "WARNING".bold().yellow(), err, fee));
+                            }
+                            fee
+                        }
+                    }
+                    .max(min_price);
+                    match (entry.fee_provider, entry.fee_estimate) {
+                        (Some(provider), Some(estimate)) => progress_bar.println(format!(
+                            "  Priority fee: {} microlamports ({} estimate: {})",
+                            fee, provider, estimate
+                        )),
+                        _ => progress_bar.println(format!("  Priority fee: {} microlamports", fee)),
+                    }
+                    final_ixs[price_index] = ComputeBudgetInstruction::set_compute_unit_price(fee);
+                    tx = Transaction::new_with_payer(&final_ixs, Some(&fee_payer.pubkey()));
+                    entry.cu_price = fee;
                 }
 
-                // Resign the tx
-                let (hash, _slot) = client
-                    .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+                // Resign the tx, with the stored nonce if there is one. Each rebuilt tx
+                // advances the same nonce, so only one of them can land.
+                let latest_hash;
+                (latest_hash, last_valid_height) = self
+                    .blockhash(&client)
                     .await
-                    .unwrap();
+                    .map_err(SubmitError::RpcError)?;
+                let hash = match self.send.nonce_account {
+                    Some(nonce_account) => self
+                        .nonce_blockhash(nonce_account)
+                        .await
+                        .map_err(SubmitError::RpcError)?,
+                    None => latest_hash,
+                };
                 if signer.pubkey() == fee_payer.pubkey() {
                     tx.sign(&[&signer], hash);
                 } else {
                     tx.sign(&[&signer, &fee_payer], hash);
                 }
+
+                // Pay the tip to the next tip account
+                if self.jito.is_some() {
+                    tip = self.jito_tip().await;
+                    progress_bar.println(format!("  Jito tip: {} lamports", tip));
+                    tip_tx = Some(self.tip_tx(tip, latest_hash));
+                }
             }
+            entry.signature = tx.signatures[0].to_string();
+            entry.attempt = attempts;
+
+            // Don't send what no leader would accept
+            let size = bincode::serialized_size(&tx).unwrap_or(0);
+            if size.gt(&(PACKET_DATA_SIZE as u64)) {
+                progress_bar.finish_with_message(format!(
+                    "{}: Transaction is {} bytes, over the {} byte limit",
+                    "ERROR".bold().red(),
+                    size,
+                    PACKET_DATA_SIZE
+                ));
+                return Err(SubmitError::SizeLimit(size));
+            }
+
+            // Simulate instead of sending
+            if self.send.dry_run {
+                progress_bar.finish_and_clear();
+                return self.simulate_signed(&tx, tip).await;
+            }
+
+            // Send bundle, falling back to the rpc if the block engine rejects it
+            let mut bundled = false;
+            let mut bundle_accepted = false;
+            if let (Some(jito), Some(tip_tx)) = (&self.jito, &tip_tx) {
+                match self.send_bundle(&jito.url, &[&tx, tip_tx]).await {
+                    Ok(_) => {
+                        tipped = tip;
+                        bundle_accepted = true;
+                        bundled = !jito.rpc_fallback;
+                    }
+                    Err(err) => progress_bar.println(format!(
+                        "  {} Bundle submission failed ({}). Sending to the RPC instead.",
+                        "WARNING".bold().yellow(),
+                        err
+                    )),
+                }
+            }
+
+            // Send straight to the leaders, falling back to the rpc if that fails
+            let mut direct = false;
+            if self.send.tpu && !bundled {
+                match self.send_tpu(&tx).await {
+                    Ok(()) => direct = true,
+                    Err(err) => {
+                        if !TPU_WARNED.swap(true, Ordering::Relaxed) {
+                            progress_bar.println(format!(
+                                "  {} TPU submission failed ({}). Sending to the RPC instead.",
+                                "WARNING".bold().yellow(),
+                                err
+                            ));
+                        }
+                    }
+                }
+            }
+            entry.path = match (bundled, direct, bundle_accepted) {
+                (true, _, _) => "jito",
+                (false, true, _) => "tpu",
+                (false, false, true) => "jito+rpc",
+                (false, false, false) => "rpc",
+            };
 
             // Send transaction
-            match client.send_transaction_with_config(&tx, send_cfg).await {
+            let mut rejected = false;
+            let sent = if bundled || direct {
+                Ok(tx.signatures[0])
+            } else if let Some(broadcast) = &self.send.broadcast {
+                broadcast.send(&tx, send_cfg).await.map(|(sig, first)| {
+                    if let Some(first) = first {
+                        first_accepted.entry(sig).or_insert(first);
+                    }
+                    sig
+                })
+            } else if let Some(send_rpc) = &self.send.send_rpc {
+                match send_rpc.send_transaction_with_config(&tx, send_cfg).await {
+                    Ok(sig) => Ok(sig),
+                    Err(err) => {
+                        progress_bar.println(format!(
+                            "  {} Send RPC failed ({}). Sending to the read RPC instead.",
+                            "WARNING".bold().yellow(),
+                            err.kind()
+                        ));
+                        client.send_transaction_with_config(&tx, send_cfg).await
+                    }
+                }
+            } else {
+                client.send_transaction_with_config(&tx, send_cfg).await
+            };
+            match sent {
                 Ok(sig) => {
+                    last_err = None;
+                    sent_at = Instant::now();
+                    if let (None, Some(round)) = (first_sent_at, round) {
+                        round.sent.notify_one();
+                    }
+                    first_sent_at.get_or_insert(sent_at);
+                    self.log_tx(&entry);
+                    if sent_slot.is_none() && fees.bump_after_slots.is_some() {
+                        sent_slot = client.get_slot().await.ok();
+                    }
+
                     // Skip confirmation
                     if skip_confirm {
                         progress_bar.finish_with_message(format!("Sent: {}", sig));
-                        return Ok(sig);
+                        return Ok(Confirmed {
+                            signature: sig,
+                            slot: None,
+                            latency: Duration::ZERO,
+                            sent_at: first_sent_at,
+                            tip: tipped,
+                            compute_units: None,
+                            fee_paid: None,
+                        });
                     }
 
-                    // Confirm transaction
-                    for _ in 0..CONFIRM_RETRIES {
-                        std::thread::sleep(Duration::from_millis(CONFIRM_DELAY));
-                        match client.get_signature_statuses(&[sig]).await {
-                            Ok(signature_statuses) => {
-                                for status in signature_statuses.value {
-                                    if let Some(status) = status {
-                                        if let Some(err) = status.err {
-                                            progress_bar.finish_with_message(format!(
-                                                "{}: {}",
-                                                "ERROR".bold().red(),
-                                                err
-                                            ));
-                                            return Err(ClientError {
-                                                request: None,
-                                                kind: ClientErrorKind::Custom(err.to_string()),
-                                            });
-                                        }
-                                        if let Some(confirmation) = status.confirmation_status {
-                                            match confirmation {
-                                                TransactionConfirmationStatus::Processed => {}
-                                                TransactionConfirmationStatus::Confirmed
-                                                | TransactionConfirmationStatus::Finalized => {
-                                                    progress_bar.finish_with_message(format!(
-                                                        "{} {}",
-                                                        "OK".bold().green(),
-                                                        sig
-                                                    ));
-                                                    return Ok(sig);
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
+                    // Confirm transaction, waiting on the websocket notification if subscribed
+                    // and polling once as a backstop
+                    let polls = if pubsub.is_some() { 1 } else { CONFIRM_RETRIES };
+                    for _ in 0..polls {
+                        let notified = match &pubsub {
+                            Some(pubsub) => notification(pubsub, sig, self.send.commitment).await,
+                            None => {
+                                std::thread::sleep(Duration::from_millis(CONFIRM_DELAY));
+                                None
+                            }
+                        };
+                        let status = match notified {
+                            Some(status) => Ok(Some((sig, status))),
+                            None => {
+                                let mut sigs = pending.clone();
+                                sigs.push(sig);
+                                confirmation(&confirm_client, &sigs, self.send.commitment).await
+                            }
+                        };
+                        match status {
+                            Ok(Some((sig, (slot, result)))) => {
+                                entry.signature = sig.to_string();
+                                return self
+                                    .landed(
+                                        &progress_bar,
+                                        &entry,
+                                        ixs,
+                                        (slot, result),
+                                        sent_at.elapsed(),
+                                        first_accepted.get(&sig).copied(),
+                                    )
+                                    .await
+                                    .map(|confirmed| Confirmed {
+                                        sent_at: first_sent_at,
+                                        tip: tipped,
+                                        ..confirmed
+                                    });
                             }
+                            Ok(None) => {}
 
                             // Handle confirmation errors
                             Err(err) => {
                                 progress_bar.set_message(format!(
                                     "{}: {}",
                                     "ERROR".bold().red(),
-                                    err.kind().to_string()
+                                    err.kind()
                                 ));
+                                last_err = Some(err);
                             }
                         }
                     }
@@ -172,99 +910,411 @@ impl Miner {
 
                 // Handle submit errors
                 Err(err) => {
-                    progress_bar.set_message(format!(
-                        "{}: {}",
-                        "ERROR".bold().red(),
-                        err.kind().to_string()
-                    ));
+                    progress_bar.set_message(format!("{}: {}", "ERROR".bold().red(), err.kind()));
+                    rejected = is_blockhash_not_found(&err);
+                    let preflight = preflight_result(&err);
+                    let logs = preflight.map(|sim| {
+                        sim.logs
+                            .iter()
+                            .flatten()
+                            .map(|line| decode_log(line))
+                            .collect::<Vec<_>>()
+                    });
+                    self.log_tx(&TxLogEntry {
+                        outcome: "error",
+                        error: Some(err.kind().to_string()),
+                        compute_units: preflight.and_then(|sim| sim.units_consumed),
+                        logs: logs.clone().filter(|_| self.send.quiet),
+                        ..entry.clone()
+                    });
+
+                    // An instruction failing in preflight fails the same way on retry
+                    if let Some(tx_err @ TransactionError::InstructionError(..)) =
+                        err.get_transaction_error()
+                    {
+                        if let (Some(sim), Some(logs), false) = (preflight, &logs, self.send.quiet)
+                        {
+                            print_preflight(&progress_bar, &tx, sim, logs);
+                        }
+                        progress_bar.finish_with_message(format!(
+                            "{}: {}",
+                            "ERROR".bold().red(),
+                            describe_tx_error(&tx_err)
+                        ));
+                        return Err(tx_err.into());
+                    }
+                    last_err = Some(err);
                 }
             }
 
-            // Retry
-            std::thread::sleep(Duration::from_millis(GATEWAY_DELAY));
+            // Retry, backing off exponentially up to the max delay
             attempts += 1;
-            if attempts > GATEWAY_RETRIES {
-                progress_bar.finish_with_message(format!("{}: Max retries", "ERROR".bold().red()));
-                return Err(ClientError {
-                    request: None,
-                    kind: ClientErrorKind::Custom("Max retries".into()),
+            if attempts > self.send.retries {
+                let reason = last_err.as_ref().map_or("Max retries".to_string(), |err| {
+                    format!("Max retries ({})", err.kind())
+                });
+                progress_bar.finish_with_message(format!("{}: {}", "ERROR".bold().red(), reason));
+                self.log_tx(&TxLogEntry {
+                    outcome: "dropped",
+                    error: Some(reason),
+                    ..entry.clone()
+                });
+                return Err(match last_err {
+                    Some(err) if is_blockhash_not_found(&err) => SubmitError::BlockhashExpired,
+                    Some(err) => SubmitError::RpcError(err),
+                    None => SubmitError::Dropped { attempts },
+                });
+            }
+
+            // Give up once the deadline passes, as the solution is no longer worth landing
+            if deadline.is_some_and(|deadline| Instant::now().ge(&deadline)) {
+                progress_bar.finish_with_message(format!(
+                    "{}: Window closed before the transaction landed",
+                    "ERROR".bold().red()
+                ));
+                self.log_tx(&TxLogEntry {
+                    outcome: "dropped",
+                    error: Some("Window closed".to_string()),
+                    ..entry.clone()
                 });
+                return Err(SubmitError::WindowClosed { attempts });
             }
+
+            // Hand the signatures back if confirmation is taking too long
+            if let (Some(timeout), Some(first_sent_at)) = (self.send.confirm_timeout, first_sent_at)
+            {
+                if first_sent_at.elapsed().ge(&timeout) {
+                    progress_bar.finish_with_message(format!(
+                        "{}: Not confirmed after {}s",
+                        "ERROR".bold().red(),
+                        timeout.as_secs()
+                    ));
+                    let mut sigs = pending.clone();
+                    sigs.push(tx.signatures[0]);
+                    return Err(SubmitError::Timeout { signatures: sigs });
+                }
+            }
+
+            // Re-sign right away if the blockhash has expired
+            if self.send.nonce_account.is_none() {
+                let too_old = client
+                    .get_block_height()
+                    .await
+                    .is_ok_and(|height| height.gt(&last_valid_height));
+                if rejected || too_old {
+                    progress_bar.println("  Blockhash expired. Re-signing with a fresh one.");
+                    BLOCKHASH_CACHE.lock().unwrap().take();
+                    expired = true;
+                }
+            }
+            let mut delay = self
+                .send
+                .retry_delay
+                .saturating_mul(2u32.saturating_pow(attempts.min(32) as u32 - 1))
+                .min(self.send.max_retry_delay);
+
+            // Back off further while the send endpoints are rate limiting
+            if last_err.as_ref().is_some_and(is_rate_limited) {
+                let backoff = backoff_delay(rate_limits);
+                rate_limits += 1;
+                progress_bar.set_message(format!(
+                    "Rate limited. Retrying in {}ms... (attempt {})",
+                    backoff.as_millis(),
+                    attempts
+                ));
+                delay = delay.max(backoff);
+            } else {
+                rate_limits = 0;
+            }
+            tokio::time::sleep(delay).await;
         }
     }
 
-    pub async fn check_balance(&self) {
-        // Throw error if balance is less than min
-        if let Ok(balance) = self
+    // Fetches the latest blockhash into the cache
+    pub async fn refresh_blockhash(&self, client: &RpcClient) -> ClientResult<(Hash, u64)> {
+        let (hash, last_valid_height) = client
+            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+            .await?;
+        *BLOCKHASH_CACHE.lock().unwrap() = Some((hash, last_valid_height, Instant::now()));
+        Ok((hash, last_valid_height))
+    }
+
+    // The cached blockhash while it is younger than --blockhash-max-age-ms, else a fresh one
+    async fn blockhash(&self, client: &RpcClient) -> ClientResult<(Hash, u64)> {
+        let cached = *BLOCKHASH_CACHE.lock().unwrap();
+        if let Some((hash, last_valid_height, fetched_at)) = cached {
+            if fetched_at.elapsed().lt(&self.send.blockhash_max_age) {
+                return Ok((hash, last_valid_height));
+            }
+        }
+        self.refresh_blockhash(client).await
+    }
+
+    // Simulates a signed transaction and reports what sending it would have done
+    async fn simulate_signed(&self, tx: &Transaction, tip: u64) -> Result<Confirmed, SubmitError> {
+        let sim = self
             .rpc_client
-            .get_balance(&self.fee_payer().pubkey())
+            .simulate_transaction_with_config(
+                tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: true,
+                    commitment: Some(self.rpc_client.commitment()),
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    ..Default::default()
+                },
+            )
             .await
-        {
-            if balance <= sol_to_lamports(MIN_SOL_BALANCE) {
-                panic!(
-                    "{} Insufficient balance: {} SOL\nPlease top up with at least {} SOL",
+            .map_err(SubmitError::RpcError)?
+            .value;
+        println!(
+            "{} Simulated {} without sending it",
+            "DRY RUN".bold().blue(),
+            tx.signatures[0]
+        );
+        for log in sim.logs.unwrap_or_default() {
+            println!("  {}", decode_log(&log));
+        }
+        if let Some(units) = sim.units_consumed {
+            println!("  Compute units consumed: {}", units);
+        }
+        if let Ok(fee) = self.rpc_client.get_fee_for_message(&tx.message).await {
+            let tip = if self.jito.is_some() { tip + 5000 } else { 0 };
+            println!("  Fee: {} SOL", lamports_to_sol(fee + tip));
+        }
+        match sim.err {
+            None => {
+                println!("  Result: {}", "OK".bold().green());
+                Ok(Confirmed {
+                    signature: tx.signatures[0],
+                    slot: None,
+                    latency: Duration::ZERO,
+                    sent_at: None,
+                    tip,
+                    compute_units: sim.units_consumed,
+                    fee_paid: None,
+                })
+            }
+            Some(err) => {
+                println!("  Result: {}", describe_tx_error(&err).bold().red());
+                Err(err.into())
+            }
+        }
+    }
+
+    // Reports a transaction that landed, successfully or not
+    async fn landed(
+        &self,
+        progress_bar: &ProgressBar,
+        entry: &TxLogEntry,
+        ixs: &[Instruction],
+        (slot, result): Landed,
+        latency: Duration,
+        first_accepted: Option<usize>,
+    ) -> Result<Confirmed, SubmitError> {
+        match result {
+            Ok(()) => {
+                if let (Some(broadcast), Some(index)) = (&self.send.broadcast, first_accepted) {
+                    broadcast.credit(index);
+                }
+                progress_bar.finish_with_message(format!(
+                    "{} {}",
+                    "OK".bold().green(),
+                    entry.signature
+                ));
+
+                // Read what the transaction actually used and paid
+                let signature = Signature::from_str(&entry.signature).unwrap();
+                let meta =
+                    transaction_meta(&self.rpc_client, &signature, self.send.commitment).await;
+                observe_slot(slot);
+                let compute_units = meta.and_then(|(compute_units, _)| compute_units);
+                if let Some(compute_units) = compute_units {
+                    let consumed = CONSUMED_CU.get_or_init(Default::default);
+                    let mut consumed = consumed.lock().unwrap();
+                    let max = consumed.entry(ix_shape(ixs)).or_default();
+                    *max = compute_units.max(*max);
+                }
+                self.log_tx(&TxLogEntry {
+                    outcome: "landed",
+                    slot: Some(slot),
+                    compute_units,
+                    fee: meta.map(|(_, fee)| fee),
+                    ..entry.clone()
+                });
+                Ok(Confirmed {
+                    signature,
+                    slot: Some(slot),
+                    latency,
+                    sent_at: None,
+                    tip: 0,
+                    compute_units,
+                    fee_paid: meta.map(|(_, fee)| fee),
+                })
+            }
+            Err(err) => {
+                progress_bar.finish_with_message(format!(
+                    "{}: {}",
                     "ERROR".bold().red(),
-                    lamports_to_sol(balance),
-                    MIN_SOL_BALANCE
+                    describe_tx_error(&err)
+                ));
+                self.log_tx(&TxLogEntry {
+                    outcome: "failed",
+                    slot: Some(slot),
+                    error: Some(describe_tx_error(&err)),
+                    ..entry.clone()
+                });
+                Err(err.into())
+            }
+        }
+    }
+
+    // Appends to the --tx-log file, if one was given
+    fn log_tx(&self, entry: &TxLogEntry) {
+        if let Some(path) = &self.send.tx_log {
+            if let Err(err) = entry.append(path) {
+                println!(
+                    "{} Failed to write to the transaction log {}: {}",
+                    "WARNING".bold().yellow(),
+                    path,
+                    err
                 );
             }
         }
     }
 
-    // TODO
-    fn _simulate(&self) {
-
-        // Simulate tx
-        // let mut sim_attempts = 0;
-        // 'simulate: loop {
-        //     let sim_res = client
-        //         .simulate_transaction_with_config(
-        //             &tx,
-        //             RpcSimulateTransactionConfig {
-        //                 sig_verify: false,
-        //                 replace_recent_blockhash: true,
-        //                 commitment: Some(self.rpc_client.commitment()),
-        //                 encoding: Some(UiTransactionEncoding::Base64),
-        //                 accounts: None,
-        //                 min_context_slot: Some(slot),
-        //                 inner_instructions: false,
-        //             },
-        //         )
-        //         .await;
-        //     match sim_res {
-        //         Ok(sim_res) => {
-        //             if let Some(err) = sim_res.value.err {
-        //                 println!("Simulaton error: {:?}", err);
-        //                 sim_attempts += 1;
-        //             } else if let Some(units_consumed) = sim_res.value.units_consumed {
-        //                 if dynamic_cus {
-        //                     println!("Dynamic CUs: {:?}", units_consumed);
-        //                     let cu_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(
-        //                         units_consumed as u32 + 1000,
-        //                     );
-        //                     let cu_price_ix =
-        //                         ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee);
-        //                     let mut final_ixs = vec![];
-        //                     final_ixs.extend_from_slice(&[cu_budget_ix, cu_price_ix]);
-        //                     final_ixs.extend_from_slice(ixs);
-        //                     tx = Transaction::new_with_payer(&final_ixs, Some(&signer.pubkey()));
-        //                 }
-        //                 break 'simulate;
-        //             }
-        //         }
-        //         Err(err) => {
-        //             println!("Simulaton error: {:?}", err);
-        //             sim_attempts += 1;
-        //         }
-        //     }
-
-        //     // Abort if sim fails
-        //     if sim_attempts.gt(&SIMULATION_RETRIES) {
-        //         return Err(ClientError {
-        //             request: None,
-        //             kind: ClientErrorKind::Custom("Simulation failed".into()),
-        //         });
-        //     }
-        // }
+    // The fee payer's balance, if it is at or below --min-sol-balance
+    pub async fn below_reserve(&self) -> Option<u64> {
+        let balance = self
+            .rpc_client
+            .get_balance(&self.fee_payer().pubkey())
+            .await
+            .ok()?;
+        balance
+            .le(&sol_to_lamports(self.send.min_sol_balance))
+            .then_some(balance)
+    }
+
+    // Warns that the fee payer needs topping up
+    pub fn warn_broke(&self, balance: u64) {
+        println!(
+            "\n{} Fee payer {} has {} SOL, at or below the {} SOL reserve. Top it up to resume submitting.",
+            "WARNING".bold().yellow(),
+            self.fee_payer().pubkey(),
+            lamports_to_sol(balance),
+            self.send.min_sol_balance
+        );
+    }
+
+    // Whether the instructions, with the budget and nonce instructions added at submission,
+    // fit in a single transaction packet
+    pub fn fits_in_packet(&self, ixs: &[Instruction]) -> bool {
+        let mut final_ixs = vec![];
+        if let Some(nonce_account) = self.send.nonce_account {
+            final_ixs.push(system_instruction::advance_nonce_account(
+                &nonce_account,
+                &self.signer().pubkey(),
+            ));
+        }
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(
+            MAX_CU_LIMIT,
+        ));
+        final_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+            self.fees.static_fee(),
+        ));
+        final_ixs.extend_from_slice(ixs);
+        let tx = Transaction::new_with_payer(&final_ixs, Some(&self.fee_payer().pubkey()));
+        bincode::serialized_size(&tx).is_ok_and(|size| size.le(&(PACKET_DATA_SIZE as u64)))
+    }
+
+    // Compute unit limit from simulating the instructions once per instruction shape. If
+    // simulation fails, the most a landed transaction of the shape consumed, else the fallback.
+    pub async fn simulated_cu_limit(&self, ixs: &[Instruction], fallback: u32) -> u32 {
+        let shape = ix_shape(ixs);
+        let cache = SIMULATED_CU_LIMITS.get_or_init(Default::default);
+        if let Some(limit) = cache.lock().unwrap().get(&shape) {
+            return *limit;
+        }
+
+        // Simulate with the max limit so the measurement isn't capped
+        let mut final_ixs = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(MAX_CU_LIMIT),
+            ComputeBudgetInstruction::set_compute_unit_price(self.fees.static_fee()),
+        ];
+        final_ixs.extend_from_slice(ixs);
+        let tx = Transaction::new_with_payer(&final_ixs, Some(&self.fee_payer().pubkey()));
+        let sim_res = self
+            .rpc_client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.rpc_client.commitment()),
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let units_consumed = match sim_res {
+            Ok(sim_res) if sim_res.value.err.is_none() => sim_res.value.units_consumed,
+            _ => None,
+        };
+        let consumed = CONSUMED_CU
+            .get()
+            .and_then(|consumed| consumed.lock().unwrap().get(&shape).copied());
+        let (units_consumed, simulated) = match (units_consumed, consumed) {
+            (Some(units_consumed), _) => (units_consumed, true),
+            (None, Some(consumed)) => (consumed, false),
+            (None, None) => return fallback,
+        };
+
+        // Add the safety margin
+        let limit =
+            (units_consumed * (100 + self.send.cu_margin) / 100).min(MAX_CU_LIMIT as u64) as u32;
+        // Simulate again next time if it failed
+        if simulated {
+            cache.lock().unwrap().insert(shape, limit);
+        }
+        limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(code: u32) -> TransactionError {
+        TransactionError::InstructionError(0, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn maps_every_ore_error() {
+        let errors = [
+            OreError::NeedsReset,
+            OreError::HashInvalid,
+            OreError::HashTooEasy,
+            OreError::ClaimTooLarge,
+            OreError::ClockInvalid,
+            OreError::Spam,
+            OreError::MaxSupply,
+            OreError::AuthFailed,
+        ];
+        for (code, ore_err) in errors.into_iter().enumerate() {
+            assert_eq!(ore_error(&custom(code as u32)), Some(ore_err));
+        }
+    }
+
+    #[test]
+    fn ignores_other_errors() {
+        assert_eq!(ore_error(&custom(8)), None);
+        assert_eq!(ore_error(&custom(u32::MAX)), None);
+        assert_eq!(
+            ore_error(&TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidAccountData
+            )),
+            None
+        );
+        assert_eq!(ore_error(&TransactionError::BlockhashNotFound), None);
     }
 }
@@ -0,0 +1,104 @@
+use std::{
+    fs,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+use colored::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{pool::Job, thermal::Throttle};
+
+// Keys of the tunables file that can change while mining
+const TUNABLE_KEYS: &[&str] = &["min_difficulty", "priority_fee", "buffer_time"];
+
+// Settings re-read from the tunables file on SIGHUP and applied at the next round
+#[derive(Debug, Default, Deserialize)]
+pub struct Tunables {
+    pub min_difficulty: Option<u32>,
+    pub priority_fee: Option<u64>,
+    pub buffer_time: Option<u64>,
+}
+
+impl Tunables {
+    // Reads a JSON object of tunables, warning about keys that need a restart
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let value: Value = serde_json::from_str(&data).map_err(|err| err.to_string())?;
+        if let Some(map) = value.as_object() {
+            for key in map.keys().filter(|k| !TUNABLE_KEYS.contains(&k.as_str())) {
+                println!(
+                    "{} `{}` cannot change while mining and requires a restart",
+                    "WARNING".bold().yellow(),
+                    key
+                );
+            }
+        }
+        serde_json::from_value(value).map_err(|err| err.to_string())
+    }
+}
+
+// State shared with the signal handlers
+pub struct Signals {
+    pub current: Arc<Mutex<Option<Arc<Job>>>>,
+    pub session: Arc<Mutex<Value>>,
+    pub reload: Arc<AtomicBool>,
+    pub gpu: bool,
+    pub throttle: Arc<Throttle>,
+}
+
+impl Signals {
+    // Dumps live stats on SIGUSR1 and flags a reload on SIGHUP
+    #[cfg(unix)]
+    pub fn listen(self) {
+        use std::sync::atomic::Ordering;
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (Ok(mut usr1), Ok(mut hup)) = (
+            signal(SignalKind::user_defined1()),
+            signal(SignalKind::hangup()),
+        ) else {
+            println!(
+                "{} Failed to install signal handlers",
+                "WARNING".bold().yellow()
+            );
+            return;
+        };
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = usr1.recv() => self.dump(),
+                    _ = hup.recv() => {
+                        self.reload.store(true, Ordering::Relaxed);
+                        println!(
+                            "\n{} Reloading tunables at the next round",
+                            "INFO".bold().blue()
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    // Signals are not available on this platform
+    #[cfg(not(unix))]
+    pub fn listen(self) {}
+
+    #[cfg(unix)]
+    fn dump(&self) {
+        let round = match self.current.lock().unwrap().as_ref() {
+            Some(job) => format!(
+                "{} sec in, {}",
+                job.timer.elapsed().as_secs(),
+                job.status(self.gpu, &self.throttle)
+            ),
+            None => "idle".to_string(),
+        };
+        println!(
+            "\n{} Round: {}\n  Session: {}",
+            "INFO".bold().blue(),
+            round,
+            self.session.lock().unwrap()
+        );
+    }
+}
@@ -4,19 +4,26 @@ use solana_program::pubkey::Pubkey;
 use solana_sdk::signature::Signer;
 
 use crate::{
-    args::StakeArgs, cu_limits::CU_LIMIT_CLAIM, send_and_confirm::ComputeBudget,
-    utils::amount_f64_to_u64, Miner,
+    args::StakeArgs,
+    cluster::for_program,
+    cu_limits::CU_LIMIT_CLAIM,
+    send_and_confirm::ComputeBudget,
+    utils::{amount_u64_to_string, mint_pubkey},
+    Miner,
 };
 
 impl Miner {
     pub async fn stake(&self, args: StakeArgs) {
         // Get signer
+        if !self.require_authority("stake") {
+            return;
+        }
         let signer = self.signer();
         let sender = match args.sender {
             Some(sender) => Pubkey::from_str(&sender).expect("Failed to parse sender address"),
             None => spl_associated_token_account::get_associated_token_address(
                 &signer.pubkey(),
-                &ore_api::consts::MINT_ADDRESS,
+                &mint_pubkey(),
             ),
         };
 
@@ -28,16 +35,29 @@ impl Miner {
 
         // Parse amount
         let amount: u64 = if let Some(amount) = args.amount {
-            amount_f64_to_u64(amount)
+            amount
         } else {
             u64::from_str(token_account.token_amount.amount.as_str())
                 .expect("Failed to parse token balance")
         };
 
         // Send tx
-        let ix = ore_api::instruction::stake(signer.pubkey(), sender, amount);
-        self.send_and_confirm(&[ix], ComputeBudget::Fixed(CU_LIMIT_CLAIM), false)
+        let ix = for_program(
+            ore_api::instruction::stake(signer.pubkey(), sender, amount),
+            signer.pubkey(),
+        );
+        match self
+            .send_and_confirm(&[ix], ComputeBudget::Fixed(CU_LIMIT_CLAIM), false)
             .await
-            .ok();
+        {
+            Ok(_) if self.send.dry_run => {}
+            Ok(_) => println!("Staked {} ORE", amount_u64_to_string(amount)),
+            Err(err) => {
+                err.report("stake");
+                if self.send.dry_run {
+                    std::process::exit(1);
+                }
+            }
+        }
     }
 }
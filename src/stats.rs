@@ -0,0 +1,502 @@
+use std::{
+    fs,
+    io::{self, BufRead},
+    time::Instant,
+};
+
+use clap::ValueEnum;
+use colored::*;
+use serde_json::{json, Value};
+use solana_program::native_token::lamports_to_sol;
+
+use crate::{
+    adaptive::RoundOutcome,
+    pool::CoreStats,
+    rpc_pool::{Broadcast, RpcPool},
+    utils::{amount_u64_to_f64, amount_u64_to_string, append_line},
+};
+
+// Running totals for a mining session
+pub struct SessionStats {
+    pub rounds: u64,
+    pub landed: u64,
+    pub failed: u64,
+    pub hashes: u64,
+    pub fees: u64,
+    pub tips: u64,
+    // Compute units consumed and limits of the landed transactions whose meta was read
+    cu_used: u64,
+    cu_limit: u64,
+    cu_samples: u64,
+    difficulty_sum: u64,
+    best_difficulty: u32,
+    started: Instant,
+    pub tx: TxCounters,
+    // Price of 1 ORE in SOL, to net earnings against fees
+    pub ore_price: Option<f64>,
+    // Treasury balance and top stake at the last round
+    pub treasury_balance: Option<u64>,
+    pub top_balance: Option<u64>,
+    rpc: RpcPool,
+    broadcast: Option<Broadcast>,
+}
+
+// Outcomes of all submissions, carried over between sessions through the stats file
+#[derive(Clone, Copy, Default)]
+pub struct TxCounters {
+    pub submitted: u64,
+    pub landed: u64,
+    pub expired: u64,
+    pub failed: u64,
+}
+
+impl TxCounters {
+    pub fn record(&mut self, outcome: &str) {
+        self.submitted += 1;
+        match outcome {
+            "landed" => self.landed += 1,
+            "expired" => self.expired += 1,
+            "failed" => self.failed += 1,
+            _ => {}
+        }
+    }
+
+    // Compact line for the round header
+    pub fn summary(&self) -> String {
+        format!(
+            "tx: {} landed / {} expired / {} failed ({:.0}%)",
+            self.landed,
+            self.expired,
+            self.failed,
+            self.landed as f64 * 100.0 / self.submitted.max(1) as f64
+        )
+    }
+
+    fn to_json(self) -> Value {
+        json!({
+            "submitted": self.submitted,
+            "landed": self.landed,
+            "expired": self.expired,
+            "failed": self.failed,
+        })
+    }
+}
+
+impl SessionStats {
+    pub fn new(rpc: RpcPool, broadcast: Option<Broadcast>) -> Self {
+        Self {
+            rounds: 0,
+            landed: 0,
+            failed: 0,
+            hashes: 0,
+            fees: 0,
+            tips: 0,
+            cu_used: 0,
+            cu_limit: 0,
+            cu_samples: 0,
+            difficulty_sum: 0,
+            best_difficulty: 0,
+            started: Instant::now(),
+            tx: TxCounters::default(),
+            ore_price: None,
+            treasury_balance: None,
+            top_balance: None,
+            rpc,
+            broadcast,
+        }
+    }
+
+    pub fn record_round(&mut self, difficulty: u32, hashes: u64) {
+        self.rounds += 1;
+        self.hashes += hashes;
+        self.difficulty_sum += difficulty as u64;
+        self.best_difficulty = self.best_difficulty.max(difficulty);
+    }
+
+    // Fee includes the tip
+    pub fn record_tx(&mut self, outcome: &str, fee: u64, tip: u64) {
+        self.tx.record(outcome);
+        if outcome.eq("landed") {
+            self.landed += 1;
+            self.fees += fee;
+            self.tips += tip;
+        } else if outcome.ne("stale") {
+            self.failed += 1;
+        }
+    }
+
+    // Fee of a landed transaction sent outside a round, such as a separate claim
+    pub fn record_fee(&mut self, fee: u64) {
+        self.fees += fee;
+    }
+
+    // Earnings valued in SOL less the fees spent, if the ORE price is known
+    fn net_sol(&self, earned: u64) -> Option<f64> {
+        self.ore_price
+            .map(|price| amount_u64_to_f64(earned) * price - lamports_to_sol(self.fees))
+    }
+
+    pub fn record_cu(&mut self, used: u64, limit: u32) {
+        self.cu_used += used;
+        self.cu_limit += limit as u64;
+        self.cu_samples += 1;
+    }
+
+    // Average compute units consumed against the limit set, such as "avg CU used 187k vs limit 500k"
+    fn cu_usage(&self) -> Option<String> {
+        if self.cu_samples.eq(&0) {
+            return None;
+        }
+        Some(format!(
+            "avg CU used {}k vs limit {}k",
+            self.cu_used / self.cu_samples / 1000,
+            self.cu_limit / self.cu_samples / 1000
+        ))
+    }
+
+    fn average_difficulty(&self) -> f64 {
+        self.difficulty_sum as f64 / self.rounds.max(1) as f64
+    }
+
+    pub fn to_json(&self, earned: u64) -> Value {
+        json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "rounds": self.rounds,
+            "landed": self.landed,
+            "failed": self.failed,
+            "earned": earned,
+            "average_difficulty": self.average_difficulty(),
+            "best_difficulty": self.best_difficulty,
+            "hashes": self.hashes,
+            "fees": self.fees,
+            "fees_sol": lamports_to_sol(self.fees),
+            "net_sol": self.net_sol(earned),
+            "tips": self.tips,
+            "avg_cu_used": self.cu_used.checked_div(self.cu_samples),
+            "avg_cu_limit": self.cu_limit.checked_div(self.cu_samples),
+            "tx": self.tx.to_json(),
+            "treasury_balance": self.treasury_balance,
+            "top_balance": self.top_balance,
+            "rpc": self.rpc.to_json(),
+            "broadcast": self.broadcast.as_ref().map(|broadcast| broadcast.to_json()),
+        })
+    }
+
+    // One line progress report
+    pub fn print_line(&self, earned: u64, json: bool) {
+        if json {
+            println!("{}", self.to_json(earned));
+            return;
+        }
+        println!(
+            "{} {} rounds, {} landed, {} failed, {} ORE earned, avg difficulty {:.1}, best {}, {} SOL in fees",
+            "Session".bold(),
+            self.rounds,
+            self.landed,
+            self.failed,
+            amount_u64_to_string(earned),
+            self.average_difficulty(),
+            self.best_difficulty,
+            lamports_to_sol(self.fees)
+        );
+        match self.net_sol(earned) {
+            Some(net) => println!(
+                "  earned {} ORE, spent {} SOL in fees, net {:.6} SOL",
+                amount_u64_to_string(earned),
+                lamports_to_sol(self.fees),
+                net
+            ),
+            None => println!(
+                "  earned {} ORE, spent {} SOL in fees",
+                amount_u64_to_string(earned),
+                lamports_to_sol(self.fees)
+            ),
+        }
+        if let Some(cu_usage) = self.cu_usage() {
+            println!("  {}", cu_usage);
+        }
+    }
+
+    // Full report on exit
+    pub fn print_summary(&self, earned: u64, json: bool) {
+        if json {
+            println!("{}", self.to_json(earned));
+            return;
+        }
+        let uptime = self.started.elapsed().as_secs();
+        println!(
+            "\n{}\n  Uptime: {}h {}m\n  Rounds: {}\n  Landed: {}\n  Failed: {}\n  Earned: {} ORE\n  Avg difficulty: {:.1}\n  Best difficulty: {}\n  Hashes: {}\n  Fees: {} SOL",
+            "Session summary".bold(),
+            uptime / 3600,
+            uptime % 3600 / 60,
+            self.rounds,
+            self.landed,
+            self.failed,
+            amount_u64_to_string(earned),
+            self.average_difficulty(),
+            self.best_difficulty,
+            self.hashes,
+            lamports_to_sol(self.fees)
+        );
+        if self.tips.gt(&0) {
+            println!(
+                "  Jito tips: {} SOL of the fees",
+                lamports_to_sol(self.tips)
+            );
+        }
+        if let Some(net) = self.net_sol(earned) {
+            println!("  Net: {:.6} SOL", net);
+        }
+        if let Some(cu_usage) = self.cu_usage() {
+            println!("  Compute units: {}", cu_usage);
+        }
+        println!("  All time {}", self.tx.summary());
+        if self.rpc.len().gt(&1) {
+            self.rpc.print_summary();
+        }
+        if let Some(broadcast) = &self.broadcast {
+            broadcast.print_summary();
+        }
+    }
+}
+
+// Layout of the per-round stats file
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum StatsFormat {
+    Jsonl,
+    Csv,
+}
+
+// Columns of a csv stats file, in order
+const CSV_HEADER: &str = "timestamp,authority,challenge,hash_time,hashes,difficulty,min_difficulty,landed,signature,reward,estimated_reward,priority_fee,fee,confirm_latency,outcome";
+
+// One submitted round in the stats file
+pub struct RoundRecord {
+    pub timestamp: String,
+    pub authority: String,
+    pub challenge: String,
+    pub hash_time: f64,
+    pub hashes: u64,
+    pub difficulty: u32,
+    pub min_difficulty: u32,
+    pub landed: bool,
+    pub outcome: &'static str,
+    pub signature: String,
+    pub reward: u64,
+    pub estimated_reward: u64,
+    // Stake at the round, and what it lacked of the top stake
+    pub stake: u64,
+    pub stake_to_max: u64,
+    pub priority_fee: u64,
+    pub fee: u64,
+    pub confirm_latency: f64,
+    pub cores: Vec<CoreStats>,
+}
+
+impl RoundRecord {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "timestamp": self.timestamp,
+            "authority": self.authority,
+            "challenge": self.challenge,
+            "hash_time": self.hash_time,
+            "hashes": self.hashes,
+            "difficulty": self.difficulty,
+            "min_difficulty": self.min_difficulty,
+            "landed": self.landed,
+            "outcome": self.outcome,
+            "signature": self.signature,
+            "reward": self.reward,
+            "estimated_reward": self.estimated_reward,
+            "stake": self.stake,
+            "stake_to_max": self.stake_to_max,
+            "priority_fee": self.priority_fee,
+            "fee": self.fee,
+            "confirm_latency": self.confirm_latency,
+            "cores": self
+                .cores
+                .iter()
+                .map(|c| json!({
+                    "core_id": c.core_id,
+                    "hashes": c.hashes,
+                    "hashrate": c.hashrate.round(),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{:.3},{},{},{},{},{},{},{},{},{},{:.3},{}",
+            self.timestamp,
+            self.authority,
+            self.challenge,
+            self.hash_time,
+            self.hashes,
+            self.difficulty,
+            self.min_difficulty,
+            self.landed,
+            self.signature,
+            self.reward,
+            self.estimated_reward,
+            self.priority_fee,
+            self.fee,
+            self.confirm_latency,
+            self.outcome
+        )
+    }
+
+    // Appends the record, writing the csv header to new files. A csv file written with other
+    // columns is moved aside first, so its rows don't end up under the wrong header.
+    pub fn append(&self, path: &str, format: StatsFormat) -> io::Result<()> {
+        match format {
+            StatsFormat::Jsonl => append_line(path, &self.to_json().to_string()),
+            StatsFormat::Csv => {
+                let header = fs::File::open(path).ok().and_then(|file| {
+                    io::BufReader::new(file)
+                        .lines()
+                        .next()
+                        .and_then(|line| line.ok())
+                });
+                if let Some(header) = header.filter(|h| !h.is_empty() && h.ne(CSV_HEADER)) {
+                    let rotated = rotated_path(path);
+                    fs::rename(path, &rotated)?;
+                    println!(
+                        "{} {} has columns from another version ({}). Moved it to {} and starting a new file.",
+                        "WARNING".bold().yellow(),
+                        path,
+                        header,
+                        rotated
+                    );
+                }
+                let is_empty = fs::metadata(path).map_or(true, |m| m.len().eq(&0));
+                if is_empty {
+                    append_line(path, CSV_HEADER)?;
+                }
+                append_line(path, &self.to_csv())
+            }
+        }
+    }
+}
+
+// First of path.1, path.2, ... that doesn't exist yet
+fn rotated_path(path: &str) -> String {
+    (1..)
+        .map(|i| format!("{}.{}", path, i))
+        .find(|rotated| fs::metadata(rotated).is_err())
+        .unwrap()
+}
+
+// Transaction outcomes recorded in a stats file of either format. Rounds written before
+// outcomes were recorded count as expired if they didn't land.
+pub fn read_tx_counters(path: &str) -> TxCounters {
+    let mut counters = TxCounters::default();
+    let Ok(data) = fs::read_to_string(path) else {
+        return counters;
+    };
+    let outcome = |landed: Option<&str>, outcome: Option<&str>| match (landed, outcome) {
+        (_, Some(outcome)) if !outcome.is_empty() => outcome.to_string(),
+        (Some("true"), _) => "landed".to_string(),
+        _ => "expired".to_string(),
+    };
+    let mut lines = data.lines().peekable();
+    if let Some(header) = lines.peek().filter(|l| l.starts_with("timestamp,")) {
+        let columns: Vec<_> = header.split(',').collect();
+        let column = |name: &str| columns.iter().position(|c| c.eq(&name));
+        let (landed, outcome_column) = (column("landed"), column("outcome"));
+        for line in lines.skip(1) {
+            let values: Vec<_> = line.split(',').collect();
+            let value = |i: Option<usize>| i.and_then(|i| values.get(i).copied());
+            counters.record(&outcome(value(landed), value(outcome_column)));
+        }
+        return counters;
+    }
+    for record in lines.filter_map(|l| serde_json::from_str::<Value>(l).ok()) {
+        let landed = record["landed"]
+            .as_bool()
+            .map(|l| if l { "true" } else { "false" });
+        counters.record(&outcome(landed, record["outcome"].as_str()));
+    }
+    counters
+}
+
+// Difficulty, reward, fee and min difficulty of each round in a stats file of either format
+pub fn read_outcomes(path: &str) -> Vec<(RoundOutcome, Option<u32>)> {
+    let Ok(data) = fs::read_to_string(path) else {
+        return vec![];
+    };
+    let mut lines = data.lines().peekable();
+    if let Some(header) = lines.peek().filter(|l| l.starts_with("timestamp,")) {
+        // Files written by older versions have fewer columns, and a file missing one the
+        // outcomes need, such as a hand-edited one, is skipped
+        let columns: Vec<_> = header.split(',').collect();
+        let column = |name: &str| columns.iter().position(|c| c.eq(&name));
+        let (Some(difficulty), Some(reward), Some(fee)) =
+            (column("difficulty"), column("reward"), column("fee"))
+        else {
+            return vec![];
+        };
+        let min_difficulty = column("min_difficulty");
+        return lines
+            .skip(1)
+            .filter_map(|line| {
+                let values: Vec<_> = line.split(',').collect();
+                let value = |i: usize| values.get(i).and_then(|v| v.parse::<u64>().ok());
+                Some((
+                    RoundOutcome {
+                        difficulty: value(difficulty)? as u32,
+                        reward: value(reward)?,
+                        fee: value(fee)?,
+                    },
+                    min_difficulty.and_then(value).map(|d| d as u32),
+                ))
+            })
+            .collect();
+    }
+    lines
+        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+        .filter_map(|record| {
+            Some((
+                RoundOutcome {
+                    difficulty: record["difficulty"].as_u64()? as u32,
+                    reward: record["reward"].as_u64()?,
+                    fee: record["fee"].as_u64()?,
+                },
+                record["min_difficulty"].as_u64().map(|d| d as u32),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes_of(name: &str, data: &str) -> Vec<(RoundOutcome, Option<u32>)> {
+        let path = std::env::temp_dir().join(format!("ore-stats-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        let outcomes = read_outcomes(path.to_str().unwrap());
+        fs::remove_file(path).unwrap();
+        outcomes
+    }
+
+    #[test]
+    fn reads_csv_outcomes_by_column_name() {
+        let outcomes = outcomes_of(
+            "older",
+            "timestamp,reward,fee,difficulty\nt,100,5000,18\nt,bad,5000,18\n",
+        );
+        assert_eq!(outcomes.len(), 1);
+        let (outcome, min_difficulty) = &outcomes[0];
+        assert_eq!(
+            (outcome.difficulty, outcome.reward, outcome.fee),
+            (18, 100, 5000)
+        );
+        assert_eq!(*min_difficulty, None);
+    }
+
+    #[test]
+    fn skips_csv_files_missing_a_column() {
+        assert!(outcomes_of("missing", "timestamp,difficulty,reward\nt,18,100\n").is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// Degrees below the limit before throttling is lifted
+const HYSTERESIS: f64 = 5.0;
+
+// How often the temperature is sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+// hwmon drivers that report cpu package or core temperatures
+#[cfg(target_os = "linux")]
+const CPU_SENSORS: [&str; 6] = [
+    "coretemp",
+    "k10temp",
+    "zenpower",
+    "cpu_thermal",
+    "soc_thermal",
+    "acpitz",
+];
+
+// Length of one hash-then-sleep cycle under a power limit
+pub const POWER_CYCLE: Duration = Duration::from_millis(100);
+
+// Duty-cycles the hashing threads under a power limit or while the cpu is too hot
+pub struct Throttle {
+    enabled: AtomicBool,
+    active: AtomicBool,
+    throttled_ms: AtomicU64,
+    power_limit: AtomicU32,
+}
+
+impl Throttle {
+    pub fn new(power_limit: u32) -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            active: AtomicBool::new(false),
+            throttled_ms: AtomicU64::new(0),
+            power_limit: AtomicU32::new(power_limit.clamp(1, 100)),
+        }
+    }
+
+    // Percentage of each cycle spent hashing
+    pub fn power_limit(&self) -> u32 {
+        self.power_limit.load(Ordering::Relaxed)
+    }
+
+    // Share of time spent hashing, including thermal throttling
+    pub fn duty_cycle(&self) -> u32 {
+        if self.is_active() {
+            self.power_limit() / 2
+        } else {
+            self.power_limit()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    // Total time spent throttled since the monitor started
+    pub fn throttled(&self) -> Duration {
+        Duration::from_millis(self.throttled_ms.load(Ordering::Relaxed))
+    }
+
+    // Samples the temperature in the background and toggles throttling
+    pub fn monitor(self: &Arc<Self>, max_temp: f64) {
+        self.enabled.store(true, Ordering::Relaxed);
+        let throttle = self.clone();
+        std::thread::spawn(move || {
+            let mut last_sample = Instant::now();
+            loop {
+                std::thread::sleep(SAMPLE_INTERVAL);
+                if throttle.is_active() {
+                    throttle
+                        .throttled_ms
+                        .fetch_add(last_sample.elapsed().as_millis() as u64, Ordering::Relaxed);
+                }
+                last_sample = Instant::now();
+                let Some(temp) = cpu_temperature() else {
+                    continue;
+                };
+                if temp.gt(&max_temp) {
+                    throttle.active.store(true, Ordering::Relaxed);
+                } else if temp.lt(&(max_temp - HYSTERESIS)) {
+                    throttle.active.store(false, Ordering::Relaxed);
+                }
+            }
+        });
+    }
+}
+
+// Hottest cpu sensor in celsius, if one can be read
+#[cfg(target_os = "linux")]
+pub fn cpu_temperature() -> Option<f64> {
+    let mut hottest: Option<f64> = None;
+    for entry in std::fs::read_dir("/sys/class/hwmon").ok()?.flatten() {
+        let path = entry.path();
+        let name = std::fs::read_to_string(path.join("name")).unwrap_or_default();
+        if !CPU_SENSORS.contains(&name.trim()) {
+            continue;
+        }
+        for input in std::fs::read_dir(&path).ok()?.flatten() {
+            let file_name = input.file_name().to_string_lossy().to_string();
+            if !(file_name.starts_with("temp") && file_name.ends_with("_input")) {
+                continue;
+            }
+            let Some(millis) = std::fs::read_to_string(input.path())
+                .ok()
+                .and_then(|s| s.trim().parse::<f64>().ok())
+            else {
+                continue;
+            };
+            let temp = millis / 1000.0;
+            hottest = Some(hottest.map_or(temp, |h| h.max(temp)));
+        }
+    }
+    hottest
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cpu_temperature() -> Option<f64> {
+    None
+}
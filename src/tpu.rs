@@ -0,0 +1,54 @@
+use colored::*;
+use solana_client::{nonblocking::tpu_client::TpuClient, tpu_client::TpuClientConfig};
+use solana_quic_client::{QuicConfig, QuicConnectionManager, QuicPool};
+use solana_sdk::transaction::Transaction;
+use tokio::sync::OnceCell;
+
+use crate::Miner;
+
+type QuicTpuClient = TpuClient<QuicPool, QuicConnectionManager, QuicConfig>;
+
+// Connections to the upcoming leaders, or none if they couldn't be set up
+static TPU_CLIENT: OnceCell<Option<QuicTpuClient>> = OnceCell::const_new();
+
+impl Miner {
+    // Sends the transaction over QUIC to the current and upcoming leaders
+    pub async fn send_tpu(&self, tx: &Transaction) -> Result<(), String> {
+        let client = TPU_CLIENT
+            .get_or_init(|| async {
+                let Some(ws_url) = &self.send.ws_url else {
+                    println!(
+                        "{} The TPU client needs a websocket url. Sending through the RPC instead.",
+                        "WARNING".bold().yellow()
+                    );
+                    return None;
+                };
+                match TpuClient::new(
+                    "ore-cli",
+                    self.rpc_client.clone(),
+                    ws_url,
+                    TpuClientConfig::default(),
+                )
+                .await
+                {
+                    Ok(client) => Some(client),
+                    Err(err) => {
+                        println!(
+                            "{} TPU client unavailable ({}). Sending through the RPC instead.",
+                            "WARNING".bold().yellow(),
+                            err
+                        );
+                        None
+                    }
+                }
+            })
+            .await;
+        let Some(client) = client else {
+            return Err("TPU client unavailable".to_string());
+        };
+        client
+            .try_send_transaction(tx)
+            .await
+            .map_err(|err| err.to_string())
+    }
+}
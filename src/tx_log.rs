@@ -0,0 +1,83 @@
+use std::io;
+
+use chrono::Local;
+use ore_api::instruction::OreInstruction;
+use serde_json::json;
+use solana_program::instruction::Instruction;
+
+use crate::{cluster::program_id, utils::append_line};
+
+// One line of the transaction log
+#[derive(Clone)]
+pub struct TxLogEntry {
+    pub kind: String,
+    pub signature: String,
+    pub attempt: usize,
+    pub cu_price: u64,
+    pub cu_limit: u32,
+    // Solution difficulty the price was scaled by, if it was
+    pub difficulty: Option<u32>,
+    // Dynamic fee provider and the price it estimated, if one was asked
+    pub fee_provider: Option<&'static str>,
+    pub fee_estimate: Option<u64>,
+    // Compute units consumed and fee paid, once the transaction has landed
+    pub compute_units: Option<u64>,
+    pub fee: Option<u64>,
+    // Program logs of a failed preflight, with --quiet
+    pub logs: Option<Vec<String>>,
+    pub outcome: &'static str,
+    // Where the transaction was sent: rpc, tpu, jito, or jito+rpc
+    pub path: &'static str,
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl TxLogEntry {
+    pub fn append(&self, path: &str) -> io::Result<()> {
+        let line = json!({
+            "timestamp": Local::now().to_rfc3339(),
+            "signature": self.signature,
+            "kind": self.kind,
+            "attempt": self.attempt,
+            "retry": self.attempt.gt(&0),
+            "cu_price": self.cu_price,
+            "cu_limit": self.cu_limit,
+            "difficulty": self.difficulty,
+            "fee_provider": self.fee_provider,
+            "fee_estimate": self.fee_estimate,
+            "compute_units": self.compute_units,
+            "fee": self.fee,
+            "logs": self.logs,
+            "outcome": self.outcome,
+            "path": self.path,
+            "slot": self.slot,
+            "error": self.error,
+        });
+        append_line(path, &line.to_string())
+    }
+}
+
+// Ore instructions of a transaction, such as "reset+mine", or "other" if there are none
+pub fn instruction_kind(ixs: &[Instruction]) -> String {
+    let names: Vec<&str> = ixs
+        .iter()
+        .filter(|ix| ix.program_id.eq(&program_id()))
+        .filter_map(|ix| ix.data.first())
+        .map(|discriminator| match *discriminator {
+            d if d.eq(&(OreInstruction::Claim as u8)) => "claim",
+            d if d.eq(&(OreInstruction::Close as u8)) => "close",
+            d if d.eq(&(OreInstruction::Mine as u8)) => "mine",
+            d if d.eq(&(OreInstruction::Open as u8)) => "open",
+            d if d.eq(&(OreInstruction::Reset as u8)) => "reset",
+            d if d.eq(&(OreInstruction::Stake as u8)) => "stake",
+            d if d.eq(&(OreInstruction::Update as u8)) => "update",
+            d if d.eq(&(OreInstruction::Upgrade as u8)) => "upgrade",
+            _ => "unknown",
+        })
+        .collect();
+    if names.is_empty() {
+        "other".to_string()
+    } else {
+        names.join("+")
+    }
+}
@@ -3,9 +3,10 @@ use solana_sdk::{pubkey::Pubkey, signer::Signer};
 use spl_token::amount_to_ui_amount;
 
 use crate::{
+    cluster::for_program,
     cu_limits::CU_LIMIT_UPGRADE,
     send_and_confirm::ComputeBudget,
-    utils::{amount_f64_to_u64_v1, ask_confirm},
+    utils::{amount_f64_to_u64_v1, ask_confirm, mint_pubkey},
     Miner, UpgradeArgs,
 };
 
@@ -38,7 +39,10 @@ impl Miner {
             return;
         }
 
-        let ix = ore_api::instruction::upgrade(signer.pubkey(), beneficiary, sender, amount);
+        let ix = for_program(
+            ore_api::instruction::upgrade(signer.pubkey(), beneficiary, sender, amount),
+            signer.pubkey(),
+        );
         match self
             .send_and_confirm(&[ix], ComputeBudget::Fixed(CU_LIMIT_UPGRADE), false)
             .await
@@ -93,7 +97,7 @@ impl Miner {
         // Derive assoicated token address (ata)
         let token_account_pubkey = spl_associated_token_account::get_associated_token_address(
             &signer.pubkey(),
-            &ore_api::consts::MINT_ADDRESS,
+            &mint_pubkey(),
         );
 
         // Check if ata already exists or init
@@ -102,12 +106,15 @@ impl Miner {
             let ix = spl_associated_token_account::instruction::create_associated_token_account(
                 &signer.pubkey(),
                 &signer.pubkey(),
-                &ore_api::consts::MINT_ADDRESS,
+                &mint_pubkey(),
                 &spl_token::id(),
             );
-            self.send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
+            if let Err(err) = self
+                .send_and_confirm(&[ix], ComputeBudget::Dynamic, false)
                 .await
-                .ok();
+            {
+                err.report("token account creation");
+            }
         }
 
         // Return token account address
@@ -1,67 +1,418 @@
-use std::{io::Read, time::Duration};
+use std::{
+    fmt,
+    fs::OpenOptions,
+    future::Future,
+    io::{Read, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
 
 use cached::proc_macro::cached;
 use ore_api::{
     consts::{
-        CONFIG_ADDRESS, MINT_ADDRESS, PROOF, TOKEN_DECIMALS, TOKEN_DECIMALS_V1, TREASURY_ADDRESS,
+        BUS, BUS_COUNT, CONFIG, MINT, MINT_NOISE, PROOF, TOKEN_DECIMALS, TOKEN_DECIMALS_V1,
+        TREASURY,
     },
     state::{Config, Proof, Treasury},
 };
 use ore_utils::AccountDeserialize;
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::{pubkey::Pubkey, sysvar};
-use solana_sdk::clock::Clock;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient, rpc_config::RpcAccountInfoConfig,
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey, sysvar};
+use solana_sdk::{account::Account, clock::Clock};
 use spl_associated_token_account::get_associated_token_address;
 
-pub async fn _get_treasury(client: &RpcClient) -> Treasury {
-    let data = client
-        .get_account_data(&TREASURY_ADDRESS)
-        .await
-        .expect("Failed to get treasury account");
-    *Treasury::try_from_bytes(&data).expect("Failed to parse treasury account")
+use crate::{
+    busses::{parse_busses, Busses},
+    cluster::program_id,
+    cluster_clock::ClusterClock,
+    proof_updates::ProofUpdates,
+};
+
+// Why reading an account failed
+#[derive(Debug)]
+pub enum UtilsError {
+    // The account doesn't exist
+    NotFound(Pubkey),
+    // The account exists but isn't the expected type
+    Deserialize(Pubkey),
+    // The account is owned by another program than the configured one
+    WrongOwner(Pubkey, Pubkey),
+    // The RPC couldn't be reached, or failed the request
+    Rpc(ClientError),
+    // The RPC didn't answer in time
+    Timeout,
 }
 
-pub async fn get_config(client: &RpcClient) -> Config {
-    let data = client
-        .get_account_data(&CONFIG_ADDRESS)
-        .await
-        .expect("Failed to get config account");
-    *Config::try_from_bytes(&data).expect("Failed to parse config account")
+impl UtilsError {
+    // Whether trying again might succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Rpc(_) | Self::Timeout)
+    }
 }
 
-pub async fn get_proof_with_authority(client: &RpcClient, authority: Pubkey) -> Proof {
-    let proof_address = proof_pubkey(authority);
-    get_proof(client, proof_address).await
+impl fmt::Display for UtilsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound(address) => write!(f, "account {} not found", address),
+            Self::Deserialize(address) => write!(f, "failed to parse account {}", address),
+            Self::WrongOwner(address, owner) => write!(
+                f,
+                "account {} is owned by {}, not the program {}",
+                address,
+                owner,
+                program_id()
+            ),
+            Self::Rpc(err) => write!(f, "rpc error: {}", err),
+            Self::Timeout => write!(f, "rpc request timed out"),
+        }
+    }
 }
 
+impl std::error::Error for UtilsError {}
+
+// How an rpc read is retried
+#[derive(Clone, Copy, Debug)]
+pub struct RpcRetry {
+    pub attempts: usize,
+    // Delay before the second attempt, doubling after each further one
+    pub backoff: Duration,
+    // Time an attempt may take before it counts as failed
+    pub timeout: Duration,
+}
+
+pub const RPC_RETRY: RpcRetry = RpcRetry {
+    attempts: 3,
+    backoff: Duration::from_millis(500),
+    timeout: Duration::from_secs(10),
+};
+
+// Runs the request until it succeeds, fails for good, or the attempts run out
+pub async fn rpc_with_retry<T, F, Fut>(retry: RpcRetry, mut request: F) -> Result<T, UtilsError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, UtilsError>>,
+{
+    let mut backoff = retry.backoff;
+    let mut attempt = 1;
+    loop {
+        let err = match tokio::time::timeout(retry.timeout, request()).await {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(err)) if !err.is_transient() => return Err(err),
+            Ok(Err(err)) => err,
+            Err(_) => UtilsError::Timeout,
+        };
+        if attempt.ge(&retry.attempts) {
+            return Err(err);
+        }
+        attempt += 1;
+        tokio::time::sleep(backoff).await;
+        backoff = backoff.saturating_mul(2);
+    }
+}
+
+// Highest slot a transaction was confirmed in. Even at --commitment, an rpc that lags, or
+// another node behind the same load balancer, can still serve state from before a landed
+// transaction, such as the proof before the hash it accepted, which would be mined again.
+// Reads ask for this slot as their min_context_slot, so they always see what the miner's own
+// transactions wrote, and an rpc that hasn't reached it fails the read, which is retried.
+static MIN_CONTEXT_SLOT: AtomicU64 = AtomicU64::new(0);
+
+// Raises the floor reads are made at, never lowering it for a transaction that landed earlier
+pub fn observe_slot(slot: u64) {
+    MIN_CONTEXT_SLOT.fetch_max(slot, Ordering::Relaxed);
+}
+
+// Reads at the client's commitment, no earlier than the last confirmed transaction
+fn account_config(client: &RpcClient) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(client.commitment()),
+        min_context_slot: Some(MIN_CONTEXT_SLOT.load(Ordering::Relaxed)).filter(|s| s.gt(&0)),
+        ..Default::default()
+    }
+}
+
+// The account, with retries
+async fn get_account(client: &RpcClient, address: Pubkey) -> Result<Account, UtilsError> {
+    rpc_with_retry(RPC_RETRY, || async {
+        client
+            .get_account_with_config(&address, account_config(client))
+            .await
+            .map_err(UtilsError::Rpc)?
+            .value
+            .ok_or(UtilsError::NotFound(address))
+    })
+    .await
+}
+
+// The accounts, in order, with retries
+async fn get_multiple_accounts(
+    client: &RpcClient,
+    addresses: &[Pubkey],
+) -> Result<Vec<Option<Account>>, UtilsError> {
+    rpc_with_retry(RPC_RETRY, || async {
+        let accounts = client
+            .get_multiple_accounts_with_config(addresses, account_config(client))
+            .await
+            .map_err(UtilsError::Rpc)?;
+        Ok(accounts.value)
+    })
+    .await
+}
+
+// Deserializes an account of the configured program
+fn parse_account<T: AccountDeserialize + Copy>(
+    address: Pubkey,
+    account: Option<&Account>,
+) -> Result<T, UtilsError> {
+    let account = account.ok_or(UtilsError::NotFound(address))?;
+    if account.owner.ne(&program_id()) {
+        return Err(UtilsError::WrongOwner(address, account.owner));
+    }
+    T::try_from_bytes(&account.data)
+        .copied()
+        .map_err(|_| UtilsError::Deserialize(address))
+}
+
+pub async fn _get_treasury(client: &RpcClient) -> Result<Treasury, UtilsError> {
+    let account = get_account(client, treasury_pubkey()).await?;
+    parse_account(treasury_pubkey(), Some(&account))
+}
+
+// Tokens held by a token account
+fn parse_token_amount(address: Pubkey, account: Option<&Account>) -> Result<u64, UtilsError> {
+    let account = account.ok_or(UtilsError::NotFound(address))?;
+    spl_token::state::Account::unpack(&account.data)
+        .map(|tokens| tokens.amount)
+        .map_err(|_| UtilsError::Deserialize(address))
+}
+
+// ORE held by the treasury, which pays out every claim
+pub async fn get_treasury_balance(client: &RpcClient) -> Result<u64, UtilsError> {
+    let account = get_account(client, treasury_tokens_pubkey()).await?;
+    parse_token_amount(treasury_tokens_pubkey(), Some(&account))
+}
+
+pub async fn get_config(client: &RpcClient) -> Result<Config, UtilsError> {
+    let account = get_account(client, config_pubkey()).await?;
+    parse_account(config_pubkey(), Some(&account))
+}
+
+// The proof of the authority
+pub async fn get_proof(client: &RpcClient, authority: Pubkey) -> Result<Proof, UtilsError> {
+    get_proof_at(client, proof_pubkey(authority)).await
+}
+
+// The proof of the authority, or None if it hasn't opened one
+pub async fn try_get_proof(
+    client: &RpcClient,
+    authority: Pubkey,
+) -> Result<Option<Proof>, UtilsError> {
+    match get_proof(client, authority).await {
+        Ok(proof) => Ok(Some(proof)),
+        Err(UtilsError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// The proof at the address
+pub async fn get_proof_at(client: &RpcClient, address: Pubkey) -> Result<Proof, UtilsError> {
+    let account = get_account(client, address).await?;
+    parse_account(address, Some(&account))
+}
+
+// Most accounts getMultipleAccounts returns at once
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+// The proofs of the authorities, in order, with None for those that haven't opened one
+pub async fn get_proofs(
+    client: &RpcClient,
+    authorities: &[Pubkey],
+) -> Result<Vec<Option<Proof>>, UtilsError> {
+    let mut proofs = Vec::with_capacity(authorities.len());
+    for chunk in authorities.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let addresses: Vec<Pubkey> = chunk.iter().map(|a| proof_pubkey(*a)).collect();
+        let accounts = get_multiple_accounts(client, &addresses).await?;
+        for (address, account) in addresses.into_iter().zip(accounts) {
+            match account {
+                Some(account) => proofs.push(Some(parse_account(address, Some(&account))?)),
+                None => proofs.push(None),
+            }
+        }
+    }
+    Ok(proofs)
+}
+
+// How long to wait on a proof update before giving up on it
+#[derive(Clone, Copy, Debug)]
+pub struct ProofWait {
+    pub timeout: Duration,
+    pub max_retries: usize,
+}
+
+// Time between proof reads while waiting on an update
+const PROOF_POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+// How often a wait on a proof update is reported
+const PROOF_WAIT_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+// The proof once it has hashed after the given time, or the latest proof read once the wait
+// runs out, with whether it was updated. Notified proofs are used while the subscription is up.
+// Failed reads are retried until the wait runs out without any proof read.
 pub async fn get_updated_proof_with_authority(
     client: &RpcClient,
     authority: Pubkey,
     lash_hash_at: i64,
-) -> Proof {
+    wait: ProofWait,
+    mut updates: Option<ProofUpdates>,
+) -> Result<(Proof, bool), UtilsError> {
+    let started = Instant::now();
+    let mut logged_at = started;
+    let mut retries = 0;
+    let mut latest = None;
     loop {
-        let proof = get_proof_with_authority(client, authority).await;
-        if proof.last_hash_at.gt(&lash_hash_at) {
-            return proof;
+        let read = match updates.as_ref().and_then(ProofUpdates::latest) {
+            Some(proof) => Ok(proof),
+            None => get_proof(client, authority).await,
+        };
+        let expired = started.elapsed().ge(&wait.timeout) || retries.ge(&wait.max_retries);
+        match read {
+            Ok(proof) if proof.last_hash_at.gt(&lash_hash_at) => return Ok((proof, true)),
+            Ok(proof) => latest = Some(proof),
+            Err(err) if expired && latest.is_none() => return Err(err),
+            Err(_) => {}
+        }
+        if expired {
+            if let Some(proof) = latest {
+                return Ok((proof, false));
+            }
+        }
+        if logged_at.elapsed().ge(&PROOF_WAIT_LOG_INTERVAL) {
+            println!(
+                "  Waiting for proof update... {}s",
+                started.elapsed().as_secs()
+            );
+            logged_at = Instant::now();
+        }
+        retries += 1;
+        match updates.as_mut() {
+            Some(updates) => updates.wait(PROOF_POLL_INTERVAL).await,
+            None => tokio::time::sleep(PROOF_POLL_INTERVAL).await,
         }
-        std::thread::sleep(Duration::from_millis(1000));
     }
 }
 
-pub async fn get_proof(client: &RpcClient, address: Pubkey) -> Proof {
-    let data = client
-        .get_account_data(&address)
-        .await
-        .expect("Failed to get miner account");
-    *Proof::try_from_bytes(&data).expect("Failed to parse miner account")
+// Everything a round reads from chain, fetched at a single slot
+pub struct RoundState {
+    pub config: Config,
+    pub treasury_balance: u64,
+    pub proof: Proof,
+    pub cluster_time: i64,
+    pub busses: Busses,
+    pub fetched_at: Instant,
 }
 
-pub async fn get_clock(client: &RpcClient) -> Clock {
-    let data = client
-        .get_account_data(&sysvar::clock::ID)
-        .await
-        .expect("Failed to get miner account");
-    bincode::deserialize::<Clock>(&data).expect("Failed to deserialize clock")
+impl RoundState {
+    // The cluster time, extrapolated from when the clock was read
+    pub fn now(&self) -> i64 {
+        self.cluster_time + self.fetched_at.elapsed().as_secs() as i64
+    }
+}
+
+// Fetches the config, the treasury balance and the clock in one request, leaving out the config
+// and treasury if cached ones are given, and the clock while the local one is synced. Nothing
+// here waits on the proof, so it can be read while the proof is awaited.
+pub async fn fetch_round_context(
+    client: &RpcClient,
+    cached_config: Option<(Config, u64)>,
+    cluster_clock: &ClusterClock,
+) -> Result<(Config, u64), UtilsError> {
+    let synced = cluster_clock.now().is_some();
+    let mut addresses = vec![];
+    if cached_config.is_none() {
+        addresses.extend([config_pubkey(), treasury_tokens_pubkey()]);
+    }
+    if !synced {
+        addresses.push(sysvar::clock::ID);
+    }
+    if addresses.is_empty() {
+        return cached_config.ok_or(UtilsError::NotFound(config_pubkey()));
+    }
+    let accounts = get_multiple_accounts(client, &addresses).await?;
+    let fetched_at = Instant::now();
+    let mut accounts = accounts.into_iter();
+    let context = match cached_config {
+        Some(cached) => cached,
+        None => (
+            parse_account(config_pubkey(), accounts.next().flatten().as_ref())?,
+            parse_token_amount(treasury_tokens_pubkey(), accounts.next().flatten().as_ref())?,
+        ),
+    };
+    if !synced {
+        let account = accounts
+            .next()
+            .flatten()
+            .ok_or(UtilsError::NotFound(sysvar::clock::ID))?;
+        let clock = bincode::deserialize::<Clock>(&account.data)
+            .map_err(|_| UtilsError::Deserialize(sysvar::clock::ID))?;
+        cluster_clock.sync(&clock, fetched_at);
+    }
+    Ok(context)
+}
+
+// Fetches the config, the treasury balance, the proof of the authority, the clock and every bus
+// in one request, leaving out the config and treasury if cached ones are given, and the clock
+// while the local one is synced
+pub async fn fetch_round_state(
+    client: &RpcClient,
+    authority: Pubkey,
+    cached_config: Option<(Config, u64)>,
+    cluster_clock: &ClusterClock,
+) -> Result<RoundState, UtilsError> {
+    let proof_address = proof_pubkey(authority);
+    let cached_time = cluster_clock.now();
+    let mut addresses = vec![proof_address];
+    if cached_time.is_none() {
+        addresses.push(sysvar::clock::ID);
+    }
+    addresses.extend(bus_pubkeys());
+    if cached_config.is_none() {
+        addresses.splice(0..0, [config_pubkey(), treasury_tokens_pubkey()]);
+    }
+    let accounts = get_multiple_accounts(client, &addresses).await?;
+    let fetched_at = Instant::now();
+    let mut accounts = addresses.iter().zip(accounts);
+    let mut next = || accounts.next().and_then(|(_, account)| account);
+    let (config, treasury_balance) = match cached_config {
+        Some(cached) => cached,
+        None => (
+            parse_account(config_pubkey(), next().as_ref())?,
+            parse_token_amount(treasury_tokens_pubkey(), next().as_ref())?,
+        ),
+    };
+    let proof = parse_account(proof_address, next().as_ref())?;
+    let cluster_time = match cached_time {
+        Some(cluster_time) => cluster_time,
+        None => {
+            let account = next().ok_or(UtilsError::NotFound(sysvar::clock::ID))?;
+            let clock = bincode::deserialize::<Clock>(&account.data)
+                .map_err(|_| UtilsError::Deserialize(sysvar::clock::ID))?;
+            cluster_clock.sync(&clock, fetched_at);
+            clock.unix_timestamp
+        }
+    };
+    Ok(RoundState {
+        config,
+        treasury_balance,
+        proof,
+        cluster_time,
+        busses: parse_busses(accounts.map(|(_, account)| account.map(|account| account.data))),
+        fetched_at,
+    })
 }
 
 pub fn amount_u64_to_string(amount: u64) -> String {
@@ -76,6 +427,45 @@ pub fn amount_f64_to_u64(amount: f64) -> u64 {
     (amount * 10f64.powf(TOKEN_DECIMALS as f64)) as u64
 }
 
+// Parses a decimal ORE amount, such as "1.5", into grains exactly
+pub fn amount_string_to_u64(amount: &str) -> Result<u64, String> {
+    let amount = amount.trim();
+    if amount.starts_with('-') {
+        return Err("amount can't be negative".to_string());
+    }
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(format!("invalid amount: {:?}", amount));
+    }
+    if !whole
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("invalid amount: {:?}", amount));
+    }
+    if fraction.len().gt(&(TOKEN_DECIMALS as usize)) {
+        return Err(format!(
+            "amount has more than {} decimal places",
+            TOKEN_DECIMALS
+        ));
+    }
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole
+            .parse::<u64>()
+            .map_err(|_| "amount is too large".to_string())?
+    };
+    let fraction = format!("{:0<width$}", fraction, width = TOKEN_DECIMALS as usize)
+        .parse::<u64>()
+        .unwrap_or(0);
+    whole
+        .checked_mul(10u64.pow(TOKEN_DECIMALS as u32))
+        .and_then(|grains| grains.checked_add(fraction))
+        .ok_or("amount is too large".to_string())
+}
+
 pub fn amount_f64_to_u64_v1(amount: f64) -> u64 {
     (amount * 10f64.powf(TOKEN_DECIMALS_V1 as f64)) as u64
 }
@@ -93,12 +483,196 @@ pub fn ask_confirm(question: &str) -> bool {
     }
 }
 
+pub fn append_line(path: &str, line: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    file.flush()
+}
+
+// Path of a file next to the solana cli config
+pub fn config_dir_path(file_name: &str) -> String {
+    let dir = solana_cli_config::CONFIG_FILE
+        .as_ref()
+        .and_then(|f| Path::new(f).parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default();
+    dir.join(file_name).to_string_lossy().to_string()
+}
+
 #[cached]
 pub fn proof_pubkey(authority: Pubkey) -> Pubkey {
-    Pubkey::find_program_address(&[PROOF, authority.as_ref()], &ore_api::ID).0
+    Pubkey::find_program_address(&[PROOF, authority.as_ref()], &program_id()).0
+}
+
+#[cached]
+pub fn config_pubkey() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG], &program_id()).0
+}
+
+#[cached]
+pub fn mint_pubkey() -> Pubkey {
+    Pubkey::find_program_address(&[MINT, MINT_NOISE.as_slice()], &program_id()).0
+}
+
+#[cached]
+pub fn treasury_pubkey() -> Pubkey {
+    Pubkey::find_program_address(&[TREASURY], &program_id()).0
 }
 
 #[cached]
 pub fn treasury_tokens_pubkey() -> Pubkey {
-    get_associated_token_address(&TREASURY_ADDRESS, &MINT_ADDRESS)
+    get_associated_token_address(&treasury_pubkey(), &mint_pubkey())
+}
+
+#[cached]
+pub fn bus_pubkeys() -> [Pubkey; BUS_COUNT] {
+    std::array::from_fn(|i| Pubkey::find_program_address(&[BUS, &[i as u8]], &program_id()).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use ore_api::consts::{
+        BUS_ADDRESSES, CONFIG_ADDRESS, MINT_ADDRESS, TREASURY_ADDRESS, TREASURY_TOKENS_ADDRESS,
+    };
+    use solana_client::client_error::ClientErrorKind;
+    use solana_sdk::commitment_config::CommitmentConfig;
+
+    use super::*;
+
+    const RETRY: RpcRetry = RpcRetry {
+        attempts: 3,
+        backoff: Duration::from_millis(1),
+        timeout: Duration::from_millis(50),
+    };
+
+    fn rpc_error() -> UtilsError {
+        UtilsError::Rpc(ClientErrorKind::Custom("unavailable".to_string()).into())
+    }
+
+    // Runs rpc_with_retry over the scripted result of each call, returning its result and the
+    // number of calls made
+    async fn scripted(
+        script: impl Fn(usize) -> Result<u64, UtilsError>,
+    ) -> (Result<u64, UtilsError>, usize) {
+        let calls = AtomicUsize::new(0);
+        let result = rpc_with_retry(RETRY, || {
+            let result = script(calls.fetch_add(1, Ordering::Relaxed));
+            async move { result }
+        })
+        .await;
+        (result, calls.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn reads_are_pinned_to_the_last_landed_slot() {
+        // The floor is shared by the whole process, so only ever raise it past its current value
+        let floor = MIN_CONTEXT_SLOT.load(Ordering::Relaxed) + 1_000;
+        observe_slot(floor);
+        observe_slot(floor - 1);
+        observe_slot(0);
+        assert_eq!(MIN_CONTEXT_SLOT.load(Ordering::Relaxed), floor);
+
+        // Reads carry the configured commitment with the floor as their min context slot
+        for commitment in [
+            CommitmentConfig::processed(),
+            CommitmentConfig::confirmed(),
+            CommitmentConfig::finalized(),
+        ] {
+            let client = RpcClient::new_with_commitment("http://localhost:8899".into(), commitment);
+            let config = account_config(&client);
+            assert_eq!(config.commitment, Some(commitment));
+            assert!(config.min_context_slot.is_some_and(|slot| slot.ge(&floor)));
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let (result, calls) =
+            scripted(|call| if call.lt(&2) { Err(rpc_error()) } else { Ok(7) }).await;
+        assert_eq!(result.ok(), Some(7));
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_attempts() {
+        let (result, calls) = scripted(|_| Err(rpc_error())).await;
+        assert!(matches!(result, Err(UtilsError::Rpc(_))));
+        assert_eq!(calls, RETRY.attempts);
+    }
+
+    #[tokio::test]
+    async fn fails_fast_on_permanent_errors() {
+        let address = Pubkey::new_unique();
+        let (result, calls) = scripted(|_| Err(UtilsError::NotFound(address))).await;
+        assert!(matches!(result, Err(UtilsError::NotFound(a)) if a.eq(&address)));
+        assert_eq!(calls, 1);
+        let (result, calls) = scripted(|_| Err(UtilsError::Deserialize(address))).await;
+        assert!(matches!(result, Err(UtilsError::Deserialize(_))));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_requests() {
+        let calls = AtomicUsize::new(0);
+        let result = rpc_with_retry(RETRY, || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async {
+                tokio::time::sleep(RETRY.timeout * 4).await;
+                Ok(0)
+            }
+        })
+        .await;
+        assert!(matches!(result, Err(UtilsError::Timeout)));
+        assert_eq!(calls.load(Ordering::Relaxed), RETRY.attempts);
+    }
+
+    #[test]
+    fn derives_the_ore_program_addresses() {
+        assert_eq!(bus_pubkeys(), BUS_ADDRESSES);
+        assert_eq!(config_pubkey(), CONFIG_ADDRESS);
+        assert_eq!(mint_pubkey(), MINT_ADDRESS);
+        assert_eq!(treasury_pubkey(), TREASURY_ADDRESS);
+        assert_eq!(treasury_tokens_pubkey(), TREASURY_TOKENS_ADDRESS);
+    }
+
+    #[test]
+    fn parses_amounts_exactly() {
+        let grain = 10u64.pow(TOKEN_DECIMALS as u32);
+        let cases = [
+            ("1.5", grain + grain / 2),
+            ("0.000000001", grain / 1_000_000_000),
+            ("0.00000000001", 1),
+            ("2", 2 * grain),
+            ("2.", 2 * grain),
+            (".5", grain / 2),
+            (" 3 ", 3 * grain),
+            ("0", 0),
+            ("184467440.73709551615", u64::MAX),
+        ];
+        for (amount, grains) in cases {
+            assert_eq!(amount_string_to_u64(amount), Ok(grains), "{:?}", amount);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        let cases = [
+            "",
+            ".",
+            "-1",
+            "1e3",
+            "1.2.3",
+            "abc",
+            // More fractional digits than TOKEN_DECIMALS, which would have to be rounded
+            "0.000000000001",
+            // Over u64::MAX grains, by the whole part and by the fraction
+            "184467441",
+            "184467440.73709551616",
+            "99999999999999999999",
+        ];
+        for amount in cases {
+            assert!(amount_string_to_u64(amount).is_err(), "{:?}", amount);
+        }
+    }
 }
@@ -0,0 +1,202 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use colored::*;
+use tokio::{net::TcpStream, sync::mpsc::error::TryRecvError, sync::mpsc::unbounded_channel};
+
+use crate::{
+    args::MineArgs,
+    mine::wait_for_interrupt,
+    pool::WorkerPool,
+    protocol::{read_message, write_message, Message},
+    Miner,
+};
+
+// Delay before reconnecting, doubled after every failed attempt up to the maximum
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// How often improvements of the best hash are streamed to the coordinator
+const RESULT_INTERVAL: Duration = Duration::from_millis(250);
+
+// Hashes nonce ranges for a coordinator until interrupted, reconnecting whenever the connection drops
+pub async fn run(addr: &str, args: &MineArgs, pool: &WorkerPool) {
+    let mut backoff = MIN_BACKOFF;
+    while !pool.interrupt.load(Ordering::Relaxed) {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => {
+                println!("Connected to coordinator at {}", addr);
+                backoff = MIN_BACKOFF;
+                work(stream, args, pool).await;
+                if pool.interrupt.load(Ordering::Relaxed) {
+                    break;
+                }
+                println!(
+                    "{} Lost connection to coordinator",
+                    "WARNING".bold().yellow()
+                );
+            }
+            Err(err) => println!(
+                "{} Failed to connect to coordinator at {}: {}",
+                "WARNING".bold().yellow(),
+                addr,
+                err
+            ),
+        }
+        println!("  Reconnecting in {} sec", backoff.as_secs());
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = wait_for_interrupt(pool) => break,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+// Hashes every job the coordinator assigns until the connection closes
+async fn work(stream: TcpStream, args: &MineArgs, pool: &WorkerPool) {
+    let (mut reader, mut writer) = stream.into_split();
+    let hello = Message::Hello {
+        cores: pool.slots() as u64,
+    };
+    if write_message(&mut writer, &hello).await.is_err() {
+        return;
+    }
+
+    // Forward queued results to the coordinator
+    let (sender, mut outbox) = unbounded_channel();
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = outbox.recv().await {
+            if write_message(&mut writer, &message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Queue messages from the coordinator and abandon the current range on each one
+    let (inbox_sender, mut inbox) = unbounded_channel();
+    let reader_task = {
+        let stale = pool.stale.clone();
+        tokio::spawn(async move {
+            while let Ok(message) = read_message(&mut reader).await {
+                if inbox_sender.send(message).is_err() {
+                    break;
+                }
+                stale.store(true, Ordering::Relaxed);
+            }
+            drop(inbox_sender);
+            stale.store(true, Ordering::Relaxed);
+        })
+    };
+
+    let mut pending = None;
+    loop {
+        // Wait for the next job
+        let message = match pending.take() {
+            Some(message) => message,
+            None => tokio::select! {
+                message = inbox.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                _ = wait_for_interrupt(pool) => break,
+            },
+        };
+        let Message::Job {
+            job_id,
+            challenge,
+            start_nonce,
+            end_nonce,
+            cutoff_time,
+            min_difficulty,
+            continue_after_min,
+        } = message
+        else {
+            continue;
+        };
+
+        // Skip jobs already superseded by another message
+        pool.stale.store(false, Ordering::Relaxed);
+        match inbox.try_recv() {
+            Ok(message) => {
+                pending = Some(message);
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => break,
+            Err(TryRecvError::Empty) => {}
+        }
+
+        // Stream the best hash to the coordinator whenever it improves
+        println!(
+            "\nJob {}: challenge {}, nonces {}..{}",
+            job_id,
+            bs58::encode(challenge).into_string(),
+            start_nonce,
+            end_nonce
+        );
+        let streamer = {
+            let current = pool.current.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let mut reported = 0;
+                loop {
+                    tokio::time::sleep(RESULT_INTERVAL).await;
+                    let Some(job) = current.lock().unwrap().clone() else {
+                        continue;
+                    };
+                    if job.challenge.ne(&challenge) {
+                        continue;
+                    }
+                    let Some((nonce, digest, difficulty)) = *job.best.lock().unwrap() else {
+                        continue;
+                    };
+                    if difficulty.gt(&reported) {
+                        reported = difficulty;
+                        sender
+                            .send(Message::Result {
+                                job_id,
+                                nonce,
+                                digest,
+                                difficulty,
+                                hashes: job.hash_count(),
+                            })
+                            .ok();
+                    }
+                }
+            })
+        };
+
+        // Hash the assigned range
+        let args = MineArgs {
+            continue_after_min,
+            ..args.clone()
+        };
+        pool.set_range(Some((start_nonce, end_nonce)));
+        let result = Miner::find_hash_par(
+            pool,
+            &args,
+            challenge,
+            cutoff_time,
+            min_difficulty,
+            None,
+            "",
+        )
+        .await;
+        pool.set_range(None);
+        streamer.abort();
+
+        // Report the final result
+        if result.hashes.gt(&0) {
+            sender
+                .send(Message::Result {
+                    job_id,
+                    nonce: u64::from_le_bytes(result.solution.n),
+                    digest: result.solution.d,
+                    difficulty: result.solution.to_hash().difficulty(),
+                    hashes: result.hashes,
+                })
+                .ok();
+        }
+    }
+
+    reader_task.abort();
+    writer_task.abort();
+}